@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS sessions_table_owner_idx;
+DROP TABLE IF EXISTS "sessions_table";
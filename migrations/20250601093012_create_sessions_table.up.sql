@@ -0,0 +1,13 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS "sessions_table" (
+    id uuid DEFAULT gen_random_uuid(),
+    owner_id uuid NOT NULL,
+    owner_kind VARCHAR(16) NOT NULL CHECK (owner_kind IN ('user', 'admin')),
+    refresh_token_hash VARCHAR(64) NOT NULL UNIQUE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX IF NOT EXISTS sessions_table_owner_idx ON "sessions_table" (owner_id, owner_kind);
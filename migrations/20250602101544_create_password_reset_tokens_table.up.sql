@@ -0,0 +1,11 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS "password_reset_tokens_table" (
+    id uuid DEFAULT gen_random_uuid(),
+    owner_id uuid NOT NULL,
+    owner_kind VARCHAR(16) NOT NULL CHECK (owner_kind IN ('user', 'admin')),
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (id)
+);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS "email_verification_tokens_table";
+
+ALTER TABLE "user_table"
+DROP COLUMN IF EXISTS email_verified_at;
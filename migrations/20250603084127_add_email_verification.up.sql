@@ -0,0 +1,16 @@
+-- Add up migration script here
+ALTER TABLE "user_table"
+ADD COLUMN IF NOT EXISTS email_verified_at TIMESTAMPTZ;
+
+-- accounts created before verification existed are trusted as is
+UPDATE "user_table" SET email_verified_at = now() WHERE email_verified_at IS NULL;
+
+CREATE TABLE IF NOT EXISTS "email_verification_tokens_table" (
+    id uuid DEFAULT gen_random_uuid(),
+    user_id uuid NOT NULL,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (id)
+);
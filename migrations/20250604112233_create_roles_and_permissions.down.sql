@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS "principal_roles_table";
+DROP TABLE IF EXISTS "role_permissions_table";
+DROP TABLE IF EXISTS "permissions_table";
+DROP TABLE IF EXISTS "roles_table";
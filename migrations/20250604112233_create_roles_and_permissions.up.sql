@@ -0,0 +1,51 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS "roles_table" (
+    id uuid DEFAULT gen_random_uuid(),
+    name VARCHAR(64) NOT NULL UNIQUE,
+    PRIMARY KEY (id)
+);
+
+CREATE TABLE IF NOT EXISTS "permissions_table" (
+    id uuid DEFAULT gen_random_uuid(),
+    name VARCHAR(64) NOT NULL UNIQUE,
+    PRIMARY KEY (id)
+);
+
+CREATE TABLE IF NOT EXISTS "role_permissions_table" (
+    role_id uuid NOT NULL REFERENCES "roles_table" (id) ON DELETE CASCADE,
+    permission_id uuid NOT NULL REFERENCES "permissions_table" (id) ON DELETE CASCADE,
+    PRIMARY KEY (role_id, permission_id)
+);
+
+-- users and admins live in separate tables, so a principal is an (id, kind) pair
+CREATE TABLE IF NOT EXISTS "principal_roles_table" (
+    principal_id uuid NOT NULL,
+    principal_kind VARCHAR(16) NOT NULL CHECK (principal_kind IN ('user', 'admin')),
+    role_id uuid NOT NULL REFERENCES "roles_table" (id) ON DELETE CASCADE,
+    PRIMARY KEY (principal_id, principal_kind, role_id)
+);
+
+INSERT INTO "roles_table" (name) VALUES
+    ('learner'), ('instructor'), ('support'), ('platform-admin')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO "permissions_table" (name) VALUES
+    ('course:purchase'), ('purchases:read'), ('course:manage'), ('session:revoke'), ('roles:manage')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO "role_permissions_table" (role_id, permission_id)
+SELECT r.id, p.id FROM "roles_table" r, "permissions_table" p
+WHERE (r.name = 'learner' AND p.name IN ('course:purchase', 'purchases:read'))
+OR (r.name = 'instructor' AND p.name IN ('course:manage'))
+OR (r.name = 'support' AND p.name IN ('session:revoke'))
+OR (r.name = 'platform-admin')
+ON CONFLICT DO NOTHING;
+
+-- existing accounts keep the access they had with the old per-table secrets
+INSERT INTO "principal_roles_table" (principal_id, principal_kind, role_id)
+SELECT u.id, 'user', r.id FROM "user_table" u, "roles_table" r WHERE r.name = 'learner'
+ON CONFLICT DO NOTHING;
+
+INSERT INTO "principal_roles_table" (principal_id, principal_kind, role_id)
+SELECT a.id, 'admin', r.id FROM "admin_table" a, "roles_table" r WHERE r.name = 'instructor'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS "lessons_table";
+DROP TABLE IF EXISTS "sections_table";
@@ -0,0 +1,24 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS "sections_table" (
+    id uuid DEFAULT gen_random_uuid(),
+    course_id uuid NOT NULL REFERENCES "course_table" (id) ON DELETE CASCADE,
+    title VARCHAR(255) NOT NULL,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX IF NOT EXISTS sections_table_course_idx ON "sections_table" (course_id, position);
+
+CREATE TABLE IF NOT EXISTS "lessons_table" (
+    id uuid DEFAULT gen_random_uuid(),
+    section_id uuid NOT NULL REFERENCES "sections_table" (id) ON DELETE CASCADE,
+    title VARCHAR(255) NOT NULL,
+    body TEXT,
+    video_url VARCHAR(255),
+    duration_seconds INTEGER NOT NULL DEFAULT 0 CHECK (duration_seconds >= 0),
+    is_preview BOOLEAN NOT NULL DEFAULT false,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX IF NOT EXISTS lessons_table_section_idx ON "lessons_table" (section_id, position);
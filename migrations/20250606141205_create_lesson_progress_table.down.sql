@@ -0,0 +1,4 @@
+-- Add down migration script here
+DELETE FROM "permissions_table" WHERE name = 'progress:write';
+
+DROP TABLE IF EXISTS "lesson_progress_table";
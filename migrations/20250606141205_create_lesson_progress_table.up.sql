@@ -0,0 +1,17 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS "lesson_progress_table" (
+    user_id uuid NOT NULL,
+    lesson_id uuid NOT NULL REFERENCES "lessons_table" (id) ON DELETE CASCADE,
+    completed_at TIMESTAMPTZ,
+    last_position_seconds INTEGER NOT NULL DEFAULT 0 CHECK (last_position_seconds >= 0),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, lesson_id)
+);
+
+INSERT INTO "permissions_table" (name) VALUES ('progress:write')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO "role_permissions_table" (role_id, permission_id)
+SELECT r.id, p.id FROM "roles_table" r, "permissions_table" p
+WHERE r.name IN ('learner', 'platform-admin') AND p.name = 'progress:write'
+ON CONFLICT DO NOTHING;
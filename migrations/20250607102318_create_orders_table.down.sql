@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE "purchases_table" DROP COLUMN IF EXISTS order_id;
+
+DROP TABLE IF EXISTS "orders_table";
@@ -0,0 +1,19 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS "orders_table" (
+    id uuid DEFAULT gen_random_uuid(),
+    user_id uuid NOT NULL,
+    course_id uuid NOT NULL,
+    amount INTEGER NOT NULL CHECK (amount >= 0),
+    status VARCHAR(16) NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'paid', 'fulfilled', 'failed', 'refunded')),
+    provider VARCHAR(32) NOT NULL,
+    provider_ref VARCHAR(255) UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX IF NOT EXISTS orders_table_user_idx ON "orders_table" (user_id, course_id);
+
+-- purchases made before orders existed have no order behind them
+ALTER TABLE "purchases_table" ADD COLUMN IF NOT EXISTS order_id uuid REFERENCES "orders_table" (id);
@@ -0,0 +1,7 @@
+-- Add down migration script here
+DELETE FROM "permissions_table" WHERE name = 'order:refund';
+
+DROP TABLE IF EXISTS "refunds_table";
+
+ALTER TABLE "purchases_table" DROP COLUMN IF EXISTS revoked_at;
+ALTER TABLE "purchases_table" DROP COLUMN IF EXISTS purchased_at;
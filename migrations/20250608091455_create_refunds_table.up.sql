@@ -0,0 +1,24 @@
+-- Add up migration script here
+ALTER TABLE "purchases_table" ADD COLUMN IF NOT EXISTS purchased_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+-- a refunded purchase keeps its row, it just stops granting access
+ALTER TABLE "purchases_table" ADD COLUMN IF NOT EXISTS revoked_at TIMESTAMPTZ;
+
+CREATE TABLE IF NOT EXISTS "refunds_table" (
+    id uuid DEFAULT gen_random_uuid(),
+    purchase_id uuid NOT NULL UNIQUE REFERENCES "purchases_table" (id),
+    order_id uuid REFERENCES "orders_table" (id),
+    amount INTEGER NOT NULL CHECK (amount >= 0),
+    reason TEXT,
+    refunded_by uuid NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (id)
+);
+
+INSERT INTO "permissions_table" (name) VALUES ('order:refund')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO "role_permissions_table" (role_id, permission_id)
+SELECT r.id, p.id FROM "roles_table" r, "permissions_table" p
+WHERE r.name IN ('support', 'platform-admin') AND p.name = 'order:refund'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,7 @@
+-- Add down migration script here
+DELETE FROM "permissions_table" WHERE name = 'coupon:sitewide';
+
+ALTER TABLE "purchases_table" DROP COLUMN IF EXISTS amount_paid;
+ALTER TABLE "orders_table" DROP COLUMN IF EXISTS coupon_id;
+
+DROP TABLE IF EXISTS "coupons_table";
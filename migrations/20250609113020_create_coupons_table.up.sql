@@ -0,0 +1,31 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS "coupons_table" (
+    id uuid DEFAULT gen_random_uuid(),
+    code VARCHAR(64) NOT NULL UNIQUE,
+    kind VARCHAR(16) NOT NULL CHECK (kind IN ('percent', 'fixed')),
+    value INTEGER NOT NULL CHECK (value > 0 AND (kind = 'fixed' OR value <= 100)),
+    -- null means the coupon applies to every course
+    course_id uuid REFERENCES "course_table" (id) ON DELETE CASCADE,
+    expires_at TIMESTAMPTZ,
+    max_redemptions INTEGER CHECK (max_redemptions > 0),
+    redemptions INTEGER NOT NULL DEFAULT 0,
+    created_by uuid NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (id)
+);
+
+ALTER TABLE "orders_table" ADD COLUMN IF NOT EXISTS coupon_id uuid REFERENCES "coupons_table" (id);
+
+-- what the learner was actually charged, null for purchases made before orders existed
+ALTER TABLE "purchases_table" ADD COLUMN IF NOT EXISTS amount_paid INTEGER;
+
+UPDATE "purchases_table" p SET amount_paid = o.amount
+FROM "orders_table" o WHERE o.id = p.order_id;
+
+INSERT INTO "permissions_table" (name) VALUES ('coupon:sitewide')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO "role_permissions_table" (role_id, permission_id)
+SELECT r.id, p.id FROM "roles_table" r, "permissions_table" p
+WHERE r.name = 'platform-admin' AND p.name = 'coupon:sitewide'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,9 @@
+-- Add down migration script here
+DELETE FROM "orders_table" WHERE bundle_id IS NOT NULL;
+
+ALTER TABLE "orders_table" DROP CONSTRAINT IF EXISTS orders_table_item_check;
+ALTER TABLE "orders_table" DROP COLUMN IF EXISTS bundle_id;
+ALTER TABLE "orders_table" ALTER COLUMN course_id SET NOT NULL;
+
+DROP TABLE IF EXISTS "bundle_courses_table";
+DROP TABLE IF EXISTS "bundles_table";
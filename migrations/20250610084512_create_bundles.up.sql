@@ -0,0 +1,20 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS "bundles_table" (
+    id uuid DEFAULT gen_random_uuid(),
+    title VARCHAR(255) NOT NULL,
+    price INTEGER NOT NULL CHECK (price >= 0),
+    admin_id uuid NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (id)
+);
+
+CREATE TABLE IF NOT EXISTS "bundle_courses_table" (
+    bundle_id uuid NOT NULL REFERENCES "bundles_table" (id) ON DELETE CASCADE,
+    course_id uuid NOT NULL REFERENCES "course_table" (id) ON DELETE CASCADE,
+    PRIMARY KEY (bundle_id, course_id)
+);
+
+-- an order is for either a single course or a bundle
+ALTER TABLE "orders_table" ALTER COLUMN course_id DROP NOT NULL;
+ALTER TABLE "orders_table" ADD COLUMN IF NOT EXISTS bundle_id uuid REFERENCES "bundles_table" (id);
+ALTER TABLE "orders_table" ADD CONSTRAINT orders_table_item_check CHECK ((course_id IS NULL) <> (bundle_id IS NULL));
@@ -0,0 +1,9 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS purchases_table_course_idx;
+DROP INDEX IF EXISTS course_table_admin_idx;
+DROP INDEX IF EXISTS course_table_category_idx;
+DROP INDEX IF EXISTS course_table_price_idx;
+DROP INDEX IF EXISTS course_table_created_at_idx;
+
+ALTER TABLE "course_table" DROP COLUMN IF EXISTS created_at;
+ALTER TABLE "course_table" DROP COLUMN IF EXISTS category;
@@ -0,0 +1,9 @@
+-- Add up migration script here
+ALTER TABLE "course_table" ADD COLUMN IF NOT EXISTS category VARCHAR(64);
+ALTER TABLE "course_table" ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+CREATE INDEX IF NOT EXISTS course_table_created_at_idx ON "course_table" (created_at DESC, id);
+CREATE INDEX IF NOT EXISTS course_table_price_idx ON "course_table" (price);
+CREATE INDEX IF NOT EXISTS course_table_category_idx ON "course_table" (category);
+CREATE INDEX IF NOT EXISTS course_table_admin_idx ON "course_table" (admin_id);
+CREATE INDEX IF NOT EXISTS purchases_table_course_idx ON "purchases_table" (course_id);
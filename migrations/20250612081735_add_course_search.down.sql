@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS course_table_search_vector_idx;
+
+ALTER TABLE "course_table" DROP COLUMN IF EXISTS search_vector;
+ALTER TABLE "course_table" DROP COLUMN IF EXISTS description;
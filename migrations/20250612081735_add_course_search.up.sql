@@ -0,0 +1,9 @@
+-- Add up migration script here
+ALTER TABLE "course_table" ADD COLUMN IF NOT EXISTS description TEXT;
+
+ALTER TABLE "course_table" ADD COLUMN IF NOT EXISTS search_vector TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('english', coalesce(title, '')), 'A') ||
+    setweight(to_tsvector('english', coalesce(description, '')), 'B')
+) STORED;
+
+CREATE INDEX IF NOT EXISTS course_table_search_vector_idx ON "course_table" USING GIN (search_vector);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DELETE FROM "permissions_table" WHERE name = 'course:rate';
+
+DROP TABLE IF EXISTS "course_ratings_table";
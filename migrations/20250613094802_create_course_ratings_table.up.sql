@@ -0,0 +1,18 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS "course_ratings_table" (
+    user_id uuid NOT NULL,
+    course_id uuid NOT NULL REFERENCES "course_table" (id) ON DELETE CASCADE,
+    rating INTEGER NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, course_id)
+);
+
+CREATE INDEX IF NOT EXISTS course_ratings_table_course_idx ON "course_ratings_table" (course_id);
+
+INSERT INTO "permissions_table" (name) VALUES ('course:rate')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO "role_permissions_table" (role_id, permission_id)
+SELECT r.id, p.id FROM "roles_table" r, "permissions_table" p
+WHERE r.name IN ('learner', 'platform-admin') AND p.name = 'course:rate'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS course_table_status_idx;
+
+ALTER TABLE "course_table" DROP COLUMN IF EXISTS status;
@@ -0,0 +1,8 @@
+-- Add up migration script here
+-- courses that already exist were live, only new ones start as drafts
+ALTER TABLE "course_table" ADD COLUMN IF NOT EXISTS status VARCHAR(16) NOT NULL DEFAULT 'published'
+CHECK (status IN ('draft', 'published', 'unlisted', 'archived'));
+
+ALTER TABLE "course_table" ALTER COLUMN status SET DEFAULT 'draft';
+
+CREATE INDEX IF NOT EXISTS course_table_status_idx ON "course_table" (status);
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE "course_table" DROP COLUMN IF EXISTS deleted_at;
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE "course_table" ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
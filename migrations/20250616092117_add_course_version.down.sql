@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE "course_table" DROP COLUMN IF EXISTS updated_at;
+ALTER TABLE "course_table" DROP COLUMN IF EXISTS version;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE "course_table" ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE "course_table" ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
@@ -0,0 +1,49 @@
+-- Add down migration script here
+DO $$
+DECLARE
+    t TEXT;
+BEGIN
+    FOREACH t IN ARRAY ARRAY[
+        'admin_table', 'user_table', 'course_table', 'sections_table', 'lessons_table',
+        'purchases_table', 'orders_table', 'refunds_table', 'coupons_table', 'bundles_table',
+        'course_ratings_table', 'lesson_progress_table', 'sessions_table',
+        'password_reset_tokens_table', 'email_verification_tokens_table',
+        'roles_table', 'permissions_table'
+    ]
+    LOOP
+        EXECUTE format('DROP TRIGGER IF EXISTS %I ON %I', t || '_set_updated_at', t);
+    END LOOP;
+END;
+$$;
+
+DROP FUNCTION IF EXISTS set_updated_at();
+
+-- only drop the timestamp columns this migration introduced
+ALTER TABLE "admin_table" DROP COLUMN IF EXISTS created_at, DROP COLUMN IF EXISTS updated_at;
+ALTER TABLE "user_table" DROP COLUMN IF EXISTS created_at, DROP COLUMN IF EXISTS updated_at;
+ALTER TABLE "sections_table" DROP COLUMN IF EXISTS created_at, DROP COLUMN IF EXISTS updated_at;
+ALTER TABLE "lessons_table" DROP COLUMN IF EXISTS created_at, DROP COLUMN IF EXISTS updated_at;
+ALTER TABLE "purchases_table" DROP COLUMN IF EXISTS created_at, DROP COLUMN IF EXISTS updated_at;
+ALTER TABLE "refunds_table" DROP COLUMN IF EXISTS updated_at;
+ALTER TABLE "coupons_table" DROP COLUMN IF EXISTS updated_at;
+ALTER TABLE "bundles_table" DROP COLUMN IF EXISTS updated_at;
+ALTER TABLE "course_ratings_table" DROP COLUMN IF EXISTS created_at;
+ALTER TABLE "lesson_progress_table" DROP COLUMN IF EXISTS created_at;
+ALTER TABLE "sessions_table" DROP COLUMN IF EXISTS updated_at;
+ALTER TABLE "password_reset_tokens_table" DROP COLUMN IF EXISTS updated_at;
+ALTER TABLE "email_verification_tokens_table" DROP COLUMN IF EXISTS updated_at;
+ALTER TABLE "roles_table" DROP COLUMN IF EXISTS created_at, DROP COLUMN IF EXISTS updated_at;
+ALTER TABLE "permissions_table" DROP COLUMN IF EXISTS created_at, DROP COLUMN IF EXISTS updated_at;
+
+ALTER TABLE "course_ratings_table" DROP CONSTRAINT IF EXISTS course_ratings_table_user_id_fkey;
+ALTER TABLE "lesson_progress_table" DROP CONSTRAINT IF EXISTS lesson_progress_table_user_id_fkey;
+ALTER TABLE "email_verification_tokens_table" DROP CONSTRAINT IF EXISTS email_verification_tokens_table_user_id_fkey;
+ALTER TABLE "bundles_table" DROP CONSTRAINT IF EXISTS bundles_table_admin_id_fkey;
+ALTER TABLE "coupons_table" DROP CONSTRAINT IF EXISTS coupons_table_created_by_fkey;
+ALTER TABLE "refunds_table" DROP CONSTRAINT IF EXISTS refunds_table_refunded_by_fkey;
+ALTER TABLE "orders_table" DROP CONSTRAINT IF EXISTS orders_table_course_id_fkey, DROP CONSTRAINT IF EXISTS orders_table_user_id_fkey;
+
+DROP INDEX IF EXISTS purchases_table_active_idx;
+
+ALTER TABLE "purchases_table" DROP CONSTRAINT IF EXISTS purchases_table_course_id_fkey, DROP CONSTRAINT IF EXISTS purchases_table_user_id_fkey;
+ALTER TABLE "course_table" DROP CONSTRAINT IF EXISTS course_table_admin_id_fkey;
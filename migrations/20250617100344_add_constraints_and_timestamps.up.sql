@@ -0,0 +1,102 @@
+-- Add up migration script here
+-- courses whose admin was removed can't be linked up, their sections and lessons go with them
+DELETE FROM "course_table" c WHERE NOT EXISTS (SELECT 1 FROM "admin_table" a WHERE a.id = c.admin_id);
+
+-- purchases of a removed user or course, along with their refunds
+DELETE FROM "refunds_table" r USING "purchases_table" p
+WHERE r.purchase_id = p.id
+AND (NOT EXISTS (SELECT 1 FROM "user_table" u WHERE u.id = p.user_id)
+OR NOT EXISTS (SELECT 1 FROM "course_table" c WHERE c.id = p.course_id));
+
+DELETE FROM "purchases_table" p
+WHERE NOT EXISTS (SELECT 1 FROM "user_table" u WHERE u.id = p.user_id)
+OR NOT EXISTS (SELECT 1 FROM "course_table" c WHERE c.id = p.course_id);
+
+-- same for orders, the purchases and refunds that point at them just lose the link
+CREATE TEMP TABLE orphan_orders ON COMMIT DROP AS
+SELECT o.id FROM "orders_table" o
+WHERE NOT EXISTS (SELECT 1 FROM "user_table" u WHERE u.id = o.user_id)
+OR (o.course_id IS NOT NULL AND NOT EXISTS (SELECT 1 FROM "course_table" c WHERE c.id = o.course_id));
+
+UPDATE "purchases_table" SET order_id = NULL WHERE order_id IN (SELECT id FROM orphan_orders);
+UPDATE "refunds_table" SET order_id = NULL WHERE order_id IN (SELECT id FROM orphan_orders);
+DELETE FROM "orders_table" WHERE id IN (SELECT id FROM orphan_orders);
+
+-- a user may hold a course twice from before the unique index, the earliest purchase stays
+-- active and the later ones are revoked so they are kept as history
+UPDATE "purchases_table" p SET revoked_at = now()
+FROM (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY user_id, course_id ORDER BY purchased_at, id) AS n
+    FROM "purchases_table"
+    WHERE revoked_at IS NULL
+) d
+WHERE p.id = d.id AND d.n > 1;
+
+ALTER TABLE "course_table"
+ADD CONSTRAINT course_table_admin_id_fkey FOREIGN KEY (admin_id) REFERENCES "admin_table" (id);
+
+ALTER TABLE "purchases_table"
+ADD CONSTRAINT purchases_table_user_id_fkey FOREIGN KEY (user_id) REFERENCES "user_table" (id),
+ADD CONSTRAINT purchases_table_course_id_fkey FOREIGN KEY (course_id) REFERENCES "course_table" (id);
+
+-- one live purchase per user and course, refunded ones stay around as history
+CREATE UNIQUE INDEX IF NOT EXISTS purchases_table_active_idx ON "purchases_table" (user_id, course_id)
+WHERE revoked_at IS NULL;
+
+ALTER TABLE "orders_table"
+ADD CONSTRAINT orders_table_user_id_fkey FOREIGN KEY (user_id) REFERENCES "user_table" (id),
+ADD CONSTRAINT orders_table_course_id_fkey FOREIGN KEY (course_id) REFERENCES "course_table" (id);
+
+ALTER TABLE "refunds_table"
+ADD CONSTRAINT refunds_table_refunded_by_fkey FOREIGN KEY (refunded_by) REFERENCES "admin_table" (id);
+
+ALTER TABLE "coupons_table"
+ADD CONSTRAINT coupons_table_created_by_fkey FOREIGN KEY (created_by) REFERENCES "admin_table" (id);
+
+ALTER TABLE "bundles_table"
+ADD CONSTRAINT bundles_table_admin_id_fkey FOREIGN KEY (admin_id) REFERENCES "admin_table" (id);
+
+-- rows that only belonged to an already removed user are dropped before linking them up
+DELETE FROM "email_verification_tokens_table" t WHERE NOT EXISTS (SELECT 1 FROM "user_table" u WHERE u.id = t.user_id);
+DELETE FROM "lesson_progress_table" t WHERE NOT EXISTS (SELECT 1 FROM "user_table" u WHERE u.id = t.user_id);
+DELETE FROM "course_ratings_table" t WHERE NOT EXISTS (SELECT 1 FROM "user_table" u WHERE u.id = t.user_id);
+
+ALTER TABLE "email_verification_tokens_table"
+ADD CONSTRAINT email_verification_tokens_table_user_id_fkey FOREIGN KEY (user_id) REFERENCES "user_table" (id) ON DELETE CASCADE;
+
+ALTER TABLE "lesson_progress_table"
+ADD CONSTRAINT lesson_progress_table_user_id_fkey FOREIGN KEY (user_id) REFERENCES "user_table" (id) ON DELETE CASCADE;
+
+ALTER TABLE "course_ratings_table"
+ADD CONSTRAINT course_ratings_table_user_id_fkey FOREIGN KEY (user_id) REFERENCES "user_table" (id) ON DELETE CASCADE;
+
+-- created_at / updated_at on every table that holds records, updated_at is kept by a trigger
+CREATE OR REPLACE FUNCTION set_updated_at() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.updated_at = now();
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DO $$
+DECLARE
+    t TEXT;
+BEGIN
+    FOREACH t IN ARRAY ARRAY[
+        'admin_table', 'user_table', 'course_table', 'sections_table', 'lessons_table',
+        'purchases_table', 'orders_table', 'refunds_table', 'coupons_table', 'bundles_table',
+        'course_ratings_table', 'lesson_progress_table', 'sessions_table',
+        'password_reset_tokens_table', 'email_verification_tokens_table',
+        'roles_table', 'permissions_table'
+    ]
+    LOOP
+        EXECUTE format('ALTER TABLE %I ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now()', t);
+        EXECUTE format('ALTER TABLE %I ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT now()', t);
+        EXECUTE format('DROP TRIGGER IF EXISTS %I ON %I', t || '_set_updated_at', t);
+        EXECUTE format('CREATE TRIGGER %I BEFORE UPDATE ON %I FOR EACH ROW EXECUTE FUNCTION set_updated_at()', t || '_set_updated_at', t);
+    END LOOP;
+END;
+$$;
+
+-- purchases already record when they happened
+UPDATE "purchases_table" SET created_at = purchased_at;
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE "orders_table" DROP COLUMN IF EXISTS refund_due;
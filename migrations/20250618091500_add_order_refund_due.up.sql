@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- set when money was taken for an order that could not be delivered, so someone has to pay it back
+ALTER TABLE "orders_table" ADD COLUMN IF NOT EXISTS refund_due BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,461 @@
+use std::{env, fs, path::{Path, PathBuf}, str::FromStr, time::Duration};
+
+use actix_cors::Cors;
+use serde::Deserialize;
+use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
+use tracing_subscriber::EnvFilter;
+
+use crate::utils::{ACCESS_TOKEN_TTL_MINUTES, DEFAULT_REFUND_WINDOW_DAYS, REFRESH_TOKEN_TTL_DAYS};
+
+/// Read when COURSER_CONFIG does not point somewhere else, it's fine for it to be missing.
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// Settings are layered: built in defaults, then the TOML file, then environment variables.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig{
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub auth: AuthConfig,
+    pub cors: CorsConfig,
+    pub log: LogConfig,
+    pub mail: MailConfig,
+    pub payments: PaymentsConfig,
+    pub metrics: MetricsConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig{
+    pub host: String,
+    pub port: u16,
+    /// Defaults to one worker per physical core when not set.
+    pub workers: Option<usize>,
+    /// How long `/readyz` reports draining before the server stops, so load balancers move away first.
+    pub shutdown_drain_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig{
+    pub url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout_secs: u64,
+    /// `/readyz` reports the database down when it can't answer within this.
+    pub health_check_timeout_ms: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig{
+    pub jwt_secret: String,
+    pub access_token_ttl_minutes: i64,
+    pub refresh_token_ttl_days: i64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig{
+    /// Browser origins allowed to call the API, none by default.
+    pub allowed_origins: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig{
+    /// An `EnvFilter` directive, e.g. `info` or `info,sqlx=warn`.
+    pub level: String,
+    pub format: LogFormat,
+    /// Spans are exported over OTLP/HTTP when set, e.g. `http://localhost:4318/v1/traces`.
+    pub otlp_endpoint: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat{
+    Pretty,
+    Json,
+}
+
+impl FromStr for LogFormat{
+    type Err = ();
+
+    fn from_str(format:&str) -> Result<Self, Self::Err> {
+        match format {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MailConfig{
+    /// Mails are appended to this file when set, else only logged.
+    pub outbox_path: Option<PathBuf>,
+    /// Also log mail bodies at debug level. They hold reset and verification tokens, local use only.
+    pub log_body: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaymentsConfig{
+    pub webhook_secret: String,
+    pub refund_window_days: i32,
+    /// ISO 4217 code all prices are in, used to label revenue.
+    pub currency: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig{
+    /// `/metrics` is only served to scrapers sending this as a bearer token, it's off when not set.
+    pub token: Option<String>,
+}
+
+impl Default for ServerConfig{
+    fn default() -> Self {
+        ServerConfig{host: "127.0.0.1".to_string(), port: 8080, workers: None, shutdown_drain_secs: 5}
+    }
+}
+
+impl Default for DatabaseConfig{
+    fn default() -> Self {
+        DatabaseConfig{url: String::new(), max_connections: 5, min_connections: 0, acquire_timeout_secs: 30, health_check_timeout_ms: 1000}
+    }
+}
+
+impl Default for AuthConfig{
+    fn default() -> Self {
+        AuthConfig{
+            jwt_secret: String::new(),
+            access_token_ttl_minutes: ACCESS_TOKEN_TTL_MINUTES,
+            refresh_token_ttl_days: REFRESH_TOKEN_TTL_DAYS,
+        }
+    }
+}
+
+impl Default for LogConfig{
+    fn default() -> Self {
+        LogConfig{level: "info".to_string(), format: LogFormat::Pretty, otlp_endpoint: None}
+    }
+}
+
+impl Default for PaymentsConfig{
+    fn default() -> Self {
+        PaymentsConfig{webhook_secret: String::new(), refund_window_days: DEFAULT_REFUND_WINDOW_DAYS, currency: "USD".to_string()}
+    }
+}
+
+impl AppConfig{
+
+    /// Loads the file named by COURSER_CONFIG (or `config.toml`), applies the environment on top
+    /// and validates the result. Every problem found is returned, not just the first one.
+    pub fn load() -> Result<AppConfig, Vec<String>> {
+        AppConfig::load_with(|_config| {})
+    }
+
+    /// Like `load`, `overrides` runs last so the test harness can pin its own settings.
+    pub fn load_with(overrides:impl FnOnce(&mut AppConfig)) -> Result<AppConfig, Vec<String>> {
+
+        let path = env::var("COURSER_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
+
+        // an explicitly configured file has to exist, the default one is optional
+        let required = env::var("COURSER_CONFIG").is_ok();
+
+        let (mut config, mut errors) = AppConfig::from_layers(Path::new(&path), required, |key| env::var(key).ok());
+
+        overrides(&mut config);
+        errors.extend(config.validate());
+
+        if !errors.is_empty(){
+            return Err(errors);
+        }
+
+        Ok(config)
+    }
+
+    /// The file with the environment on top. A broken file is reported along with
+    /// everything else, the environment is then checked against the defaults.
+    fn from_layers(path:&Path, required:bool, var:impl Fn(&str) -> Option<String>) -> (AppConfig, Vec<String>) {
+
+        let (mut config, mut errors) = match AppConfig::from_file(path, required) {
+            Ok(config) => (config, Vec::new()),
+            Err(e) => (AppConfig::default(), vec![e]),
+        };
+
+        errors.extend(config.apply_env(var));
+
+        (config, errors)
+    }
+
+    fn from_file(path:&Path, required:bool) -> Result<AppConfig, String> {
+
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(_) if !required => return Ok(AppConfig::default()),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+
+        toml::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e.message()))
+    }
+
+    /// Environment variables win over the file. The unprefixed names are kept so existing
+    /// `.env` files keep working.
+    fn apply_env(&mut self, var:impl Fn(&str) -> Option<String>) -> Vec<String> {
+
+        let mut errors = Vec::new();
+
+        set_string(&var, "COURSER_HOST", &mut self.server.host);
+        set_parsed(&var, "COURSER_PORT", &mut self.server.port, &mut errors);
+
+        let mut workers = 0;
+        if set_parsed(&var, "COURSER_WORKERS", &mut workers, &mut errors){
+            self.server.workers = Some(workers);
+        }
+
+        set_parsed(&var, "COURSER_SHUTDOWN_DRAIN_SECS", &mut self.server.shutdown_drain_secs, &mut errors);
+
+        set_string(&var, "DATABASE_URL", &mut self.database.url);
+        set_parsed(&var, "DATABASE_MAX_CONNECTIONS", &mut self.database.max_connections, &mut errors);
+        set_parsed(&var, "DATABASE_MIN_CONNECTIONS", &mut self.database.min_connections, &mut errors);
+        set_parsed(&var, "DATABASE_ACQUIRE_TIMEOUT_SECS", &mut self.database.acquire_timeout_secs, &mut errors);
+        set_parsed(&var, "DATABASE_HEALTH_CHECK_TIMEOUT_MS", &mut self.database.health_check_timeout_ms, &mut errors);
+
+        set_string(&var, "JWT_SECRET", &mut self.auth.jwt_secret);
+        set_parsed(&var, "ACCESS_TOKEN_TTL_MINUTES", &mut self.auth.access_token_ttl_minutes, &mut errors);
+        set_parsed(&var, "REFRESH_TOKEN_TTL_DAYS", &mut self.auth.refresh_token_ttl_days, &mut errors);
+
+        if let Some(origins) = var("CORS_ALLOWED_ORIGINS"){
+            self.cors.allowed_origins = origins.split(',')
+            .map(|origin| origin.trim().to_string())
+            .filter(|origin| !origin.is_empty())
+            .collect();
+        }
+
+        set_string(&var, "LOG_LEVEL", &mut self.log.level);
+        set_parsed(&var, "LOG_FORMAT", &mut self.log.format, &mut errors);
+
+        if let Some(endpoint) = var("OTEL_EXPORTER_OTLP_ENDPOINT"){
+            self.log.otlp_endpoint = Some(endpoint);
+        }
+
+        if let Some(path) = var("MAIL_OUTBOX_PATH"){
+            self.mail.outbox_path = Some(PathBuf::from(path));
+        }
+
+        set_parsed(&var, "MAIL_LOG_BODY", &mut self.mail.log_body, &mut errors);
+
+        set_string(&var, "PAYMENT_WEBHOOK_SECRET", &mut self.payments.webhook_secret);
+        set_parsed(&var, "REFUND_WINDOW_DAYS", &mut self.payments.refund_window_days, &mut errors);
+        set_string(&var, "PAYMENT_CURRENCY", &mut self.payments.currency);
+
+        if let Some(token) = var("METRICS_TOKEN"){
+            self.metrics.token = Some(token);
+        }
+
+        errors
+    }
+
+    pub fn validate(&self) -> Vec<String> {
+
+        let mut errors = Vec::new();
+
+        if self.server.host.trim().is_empty(){
+            errors.push("server.host must be set".to_string());
+        }
+
+        if self.server.workers == Some(0){
+            errors.push("server.workers must be at least 1".to_string());
+        }
+
+        if self.database.url.trim().is_empty(){
+            errors.push("database.url must be set (DATABASE_URL)".to_string());
+        }
+
+        if self.database.max_connections == 0{
+            errors.push("database.max_connections must be at least 1".to_string());
+        }
+
+        if self.database.min_connections > self.database.max_connections{
+            errors.push("database.min_connections can't be more than database.max_connections".to_string());
+        }
+
+        if self.database.acquire_timeout_secs == 0{
+            errors.push("database.acquire_timeout_secs must be at least 1".to_string());
+        }
+
+        if self.database.health_check_timeout_ms == 0{
+            errors.push("database.health_check_timeout_ms must be at least 1".to_string());
+        }
+
+        if self.auth.jwt_secret.is_empty(){
+            errors.push("auth.jwt_secret must be set (JWT_SECRET)".to_string());
+        }
+
+        if self.auth.access_token_ttl_minutes < 1{
+            errors.push("auth.access_token_ttl_minutes must be at least 1".to_string());
+        }
+
+        if self.auth.refresh_token_ttl_days < 1{
+            errors.push("auth.refresh_token_ttl_days must be at least 1".to_string());
+        }
+
+        for origin in &self.cors.allowed_origins {
+            if !(origin.starts_with("http://") || origin.starts_with("https://")){
+                errors.push(format!("cors.allowed_origins: {} is not an http(s) origin", origin));
+            }
+        }
+
+        if EnvFilter::try_new(&self.log.level).is_err(){
+            errors.push(format!("log.level: {} is not a valid filter", self.log.level));
+        }
+
+        if self.log.otlp_endpoint.as_ref().is_some_and(|endpoint| !(endpoint.starts_with("http://") || endpoint.starts_with("https://"))){
+            errors.push("log.otlp_endpoint must be an http(s) url".to_string());
+        }
+
+        if self.payments.webhook_secret.is_empty(){
+            errors.push("payments.webhook_secret must be set (PAYMENT_WEBHOOK_SECRET)".to_string());
+        }
+
+        if self.payments.refund_window_days < 0{
+            errors.push("payments.refund_window_days can't be negative".to_string());
+        }
+
+        if !(self.payments.currency.len() == 3 && self.payments.currency.chars().all(|c| c.is_ascii_uppercase())){
+            errors.push(format!("payments.currency: {} is not an ISO 4217 code", self.payments.currency));
+        }
+
+        if self.metrics.token.as_ref().is_some_and(|token| token.len() < 16){
+            errors.push("metrics.token must be at least 16 characters".to_string());
+        }
+
+        errors
+    }
+}
+
+impl ServerConfig{
+    pub fn address(&self) -> (String, u16) {
+        (self.host.clone(), self.port)
+    }
+}
+
+impl CorsConfig{
+    /// Only the configured origins may call the API from a browser.
+    pub fn middleware(&self) -> Cors {
+        self.allowed_origins.iter().fold(
+            Cors::default().allow_any_method().allow_any_header().max_age(3600),
+            |cors, origin| cors.allowed_origin(origin)
+        )
+    }
+}
+
+impl DatabaseConfig{
+    pub async fn connect(&self) -> Result<Pool<Postgres>, sqlx::Error> {
+        PgPoolOptions::new()
+        .max_connections(self.max_connections)
+        .min_connections(self.min_connections)
+        .acquire_timeout(Duration::from_secs(self.acquire_timeout_secs))
+        .connect(&self.url)
+        .await
+    }
+}
+
+fn set_string(var:&impl Fn(&str) -> Option<String>, key:&str, target:&mut String){
+    if let Some(value) = var(key){
+        *target = value;
+    }
+}
+
+/// Returns true when the variable was set and parsed.
+fn set_parsed<T:FromStr>(var:&impl Fn(&str) -> Option<String>, key:&str, target:&mut T, errors:&mut Vec<String>) -> bool{
+
+    let Some(value) = var(key) else {
+        return false;
+    };
+
+    match value.trim().parse() {
+        Ok(parsed) => {
+            *target = parsed;
+            true
+        },
+        Err(_) => {
+            errors.push(format!("{}: {} is not a valid value", key, value));
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_config_layers_and_validation() {
+
+        let file = r#"
+            [server]
+            port = 9000
+            workers = 4
+
+            [database]
+            url = "postgresql://localhost/courser"
+            max_connections = 10
+
+            [cors]
+            allowed_origins = ["https://courser.dev"]
+        "#;
+
+        let mut config: AppConfig = toml::from_str(file).unwrap();
+        assert_eq!(config.server.host, "127.0.0.1");
+        assert_eq!(config.auth.access_token_ttl_minutes, ACCESS_TOKEN_TTL_MINUTES);
+
+        // the environment wins over the file
+        let env = HashMap::from([
+            ("COURSER_PORT", "9100"),
+            ("JWT_SECRET", "secret"),
+            ("PAYMENT_WEBHOOK_SECRET", "webhook"),
+        ]);
+
+        let errors = config.apply_env(|key| env.get(key).map(|value| value.to_string()));
+        assert!(errors.is_empty());
+        assert_eq!(config.server.port, 9100);
+        assert_eq!(config.database.max_connections, 10);
+        assert!(config.validate().is_empty());
+
+        // every problem is reported at once
+        let env = HashMap::from([
+            ("COURSER_WORKERS", "many"),
+            ("DATABASE_MAX_CONNECTIONS", "0"),
+            ("CORS_ALLOWED_ORIGINS", "courser.dev"),
+            ("JWT_SECRET", ""),
+            ("LOG_FORMAT", "xml"),
+        ]);
+
+        let mut errors = config.apply_env(|key| env.get(key).map(|value| value.to_string()));
+        errors.extend(config.validate());
+
+        assert_eq!(errors.len(), 5);
+        assert!(errors[0].starts_with("COURSER_WORKERS"));
+
+        assert!(toml::from_str::<AppConfig>("[server]\nprot = 1").is_err());
+
+        // a file that doesn't parse doesn't hide the environment's problems
+        let path = env::temp_dir().join(format!("courser-config-{}.toml", std::process::id()));
+        fs::write(&path, "[server]\nprot = 1").unwrap();
+
+        let env = HashMap::from([("COURSER_PORT", "eighty")]);
+        let (config, errors) = AppConfig::from_layers(&path, true, |key| env.get(key).map(|value| value.to_string()));
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with(&path.display().to_string()));
+        assert!(errors[1].starts_with("COURSER_PORT"));
+        assert_eq!(config.server.host, "127.0.0.1");
+    }
+}
@@ -1,8 +1,11 @@
-use actix_web::{http::StatusCode, ResponseError};
+use actix_web::{error::{JsonPayloadError, PathError, QueryPayloadError}, http::StatusCode, web, HttpRequest, HttpResponse, ResponseError};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use validator::ValidationErrors;
 use derive_more::derive::{Display, Error as DeriveMoreError};
 
+use crate::middlewares::request_id;
+
 #[derive(Debug, Error)]
 pub enum AppError{
     #[error("Cant bind to the Socket")]
@@ -11,8 +14,8 @@ pub enum AppError{
     DbConnect,
     #[error("Cant start the server")]
     ServerStart,
-    #[error("Internal Server Error")]
-    InternalError
+    #[error("Invalid configuration: {}", .0.join(", "))]
+    Config(Vec<String>),
 }
 
 #[derive(Debug, Display, DeriveMoreError, Serialize, Deserialize)]
@@ -21,19 +24,231 @@ pub struct CustomError{
     pub error:String
 }
 
-impl ResponseError for CustomError{}
+/// Database constraint violations the models recognise, so callers can tell
+/// "already bought" apart from "that course doesn't exist" without parsing strings.
+#[derive(Debug, Error, PartialEq)]
+pub enum DbError{
+    #[error("Already Purchased")]
+    AlreadyPurchased,
+    #[error("Course not found")]
+    CourseNotFound,
+    #[error("User not found")]
+    UserNotFound,
+    #[error("Admin not found")]
+    AdminNotFound,
+    #[error("User exists already with this email")]
+    EmailTaken,
+    #[error("{0}")]
+    Other(String),
+}
+
+impl DbError{
+    /// Maps a failed query onto the constraint it broke, anything else becomes `Other(fallback)`.
+    pub fn from_sqlx(e:&sqlx::Error, fallback:&str) -> DbError {
+
+        let constraint = e.as_database_error().and_then(|db_error| db_error.constraint());
+
+        match constraint {
+            Some("purchases_table_active_idx") => DbError::AlreadyPurchased,
+            Some("purchases_table_course_id_fkey" | "orders_table_course_id_fkey" | "course_ratings_table_course_id_fkey" | "bundle_courses_table_course_id_fkey") => DbError::CourseNotFound,
+            Some("purchases_table_user_id_fkey" | "orders_table_user_id_fkey" | "course_ratings_table_user_id_fkey") => DbError::UserNotFound,
+            Some("course_table_admin_id_fkey" | "bundles_table_admin_id_fkey" | "coupons_table_created_by_fkey") => DbError::AdminNotFound,
+            Some("user_table_email_key" | "admin_table_email_key") => DbError::EmailTaken,
+            _ => DbError::Other(fallback.to_string()),
+        }
+    }
+}
+
+impl From<DbError> for CustomError{
+    fn from(e:DbError) -> Self {
+        CustomError{error:e.to_string()}
+    }
+}
+
+/// One invalid field of a request body or query.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldError{
+    pub field: String,
+    pub message: String,
+}
+
+/// Every error a handler or middleware sends back. `code` is stable and meant for
+/// clients to branch on, the message is for humans and may change.
+#[derive(Debug, Error)]
+pub enum ApiError{
+    #[error("{message}")]
+    BadRequest{code:&'static str, message:String},
+    #[error("{message}")]
+    Unauthorized{code:&'static str, message:String},
+    #[error("{message}")]
+    Forbidden{code:&'static str, message:String},
+    #[error("{message}")]
+    NotFound{code:&'static str, message:String},
+    #[error("{message}")]
+    Conflict{code:&'static str, message:String},
+    #[error("{message}")]
+    PreconditionFailed{code:&'static str, message:String},
+    #[error("{message}")]
+    PreconditionRequired{code:&'static str, message:String},
+    #[error("{message}")]
+    Validation{message:String, details:Vec<FieldError>},
+    #[error("{0}")]
+    Internal(String),
+}
+
+/// JSON body of every error response. `error` keeps the old `CustomError` shape readable.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ErrorBody{
+    pub error: String,
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<FieldError>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl ApiError{
+    pub fn bad_request(code:&'static str, message:impl Into<String>) -> ApiError {
+        ApiError::BadRequest{code, message:message.into()}
+    }
+
+    pub fn unauthorized(code:&'static str, message:impl Into<String>) -> ApiError {
+        ApiError::Unauthorized{code, message:message.into()}
+    }
+
+    pub fn forbidden(code:&'static str, message:impl Into<String>) -> ApiError {
+        ApiError::Forbidden{code, message:message.into()}
+    }
+
+    pub fn not_found(code:&'static str, message:impl Into<String>) -> ApiError {
+        ApiError::NotFound{code, message:message.into()}
+    }
+
+    pub fn conflict(code:&'static str, message:impl Into<String>) -> ApiError {
+        ApiError::Conflict{code, message:message.into()}
+    }
+
+    pub fn precondition_failed(code:&'static str, message:impl Into<String>) -> ApiError {
+        ApiError::PreconditionFailed{code, message:message.into()}
+    }
+
+    pub fn precondition_required(code:&'static str, message:impl Into<String>) -> ApiError {
+        ApiError::PreconditionRequired{code, message:message.into()}
+    }
+
+    /// A single invalid field, the message doubles as the field's detail.
+    pub fn validation(field:&str, message:impl Into<String>) -> ApiError {
+        let message = message.into();
+        ApiError::Validation{
+            message:message.clone(),
+            details:vec![FieldError{field:field.to_string(), message}],
+        }
+    }
+
+    pub fn internal(message:impl Into<String>) -> ApiError {
+        ApiError::Internal(message.into())
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest{code, ..}
+            | ApiError::Unauthorized{code, ..}
+            | ApiError::Forbidden{code, ..}
+            | ApiError::NotFound{code, ..}
+            | ApiError::Conflict{code, ..}
+            | ApiError::PreconditionFailed{code, ..}
+            | ApiError::PreconditionRequired{code, ..} => code,
+            ApiError::Validation{..} => "validation_failed",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+}
+
+impl ResponseError for ApiError{
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest{..} => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized{..} => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden{..} => StatusCode::FORBIDDEN,
+            ApiError::NotFound{..} => StatusCode::NOT_FOUND,
+            ApiError::Conflict{..} => StatusCode::CONFLICT,
+            ApiError::PreconditionFailed{..} => StatusCode::PRECONDITION_FAILED,
+            ApiError::PreconditionRequired{..} => StatusCode::PRECONDITION_REQUIRED,
+            ApiError::Validation{..} => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let details = match self {
+            ApiError::Validation{details, ..} => details.clone(),
+            _ => vec![],
+        };
 
-impl ResponseError for AppError {
-    fn error_response(&self) -> actix_web::HttpResponse<actix_web::body::BoxBody> {
-        actix_web::HttpResponse::build(self.status_code()).body(self.to_string())
+        HttpResponse::build(self.status_code()).json(ErrorBody{
+            error:self.to_string(),
+            code:self.code().to_string(),
+            details,
+            request_id:request_id::current(),
+        })
     }
+}
+
+/// Field errors come back sorted by field so responses are stable.
+impl From<ValidationErrors> for ApiError{
+    fn from(e:ValidationErrors) -> Self {
+        let mut details: Vec<FieldError> = e.field_errors()
+        .into_iter()
+        .flat_map(|(field, errors)| errors.iter().map(move |error| FieldError{
+            // rules spanning several fields, `#[validate(schema(...))]`, are filed under "__all__"
+            field:if field == "__all__" { "body".to_string() } else { field.to_string() },
+            message:error.message.as_deref().unwrap_or(&error.code).to_string(),
+        }))
+        .collect();
 
-    fn status_code(&self) -> actix_web::http::StatusCode {
-        match *self {
-            AppError::DbConnect => StatusCode::INTERNAL_SERVER_ERROR,
-            AppError::ServerStart => StatusCode::INTERNAL_SERVER_ERROR,
-            AppError::SocketBind => StatusCode::INTERNAL_SERVER_ERROR,
-            AppError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+        details.sort_by(|a, b| a.field.cmp(&b.field));
+
+        ApiError::Validation{message:"Request body is invalid".to_string(), details}
+    }
+}
+
+/// Model errors that aren't a recognised constraint are failures on our side.
+impl From<CustomError> for ApiError{
+    fn from(e:CustomError) -> Self {
+        ApiError::Internal(e.error)
+    }
+}
+
+impl From<DbError> for ApiError{
+    fn from(e:DbError) -> Self {
+        let message = e.to_string();
+        match e {
+            DbError::AlreadyPurchased => ApiError::conflict("already_purchased", message),
+            DbError::CourseNotFound => ApiError::not_found("course_not_found", message),
+            DbError::UserNotFound => ApiError::not_found("user_not_found", message),
+            DbError::AdminNotFound => ApiError::not_found("admin_not_found", message),
+            DbError::EmailTaken => ApiError::conflict("email_taken", message),
+            DbError::Other(message) => ApiError::Internal(message),
         }
     }
-}
\ No newline at end of file
+}
+fn json_error(err:JsonPayloadError, _req:&HttpRequest) -> actix_web::Error {
+    ApiError::bad_request("invalid_body", err.to_string()).into()
+}
+
+fn query_error(err:QueryPayloadError, _req:&HttpRequest) -> actix_web::Error {
+    ApiError::bad_request("invalid_query", err.to_string()).into()
+}
+
+fn path_error(err:PathError, _req:&HttpRequest) -> actix_web::Error {
+    ApiError::bad_request("invalid_path", err.to_string()).into()
+}
+
+/// Register on the app so rejected bodies, queries and paths use the `ApiError` shape too.
+pub fn extractor_configs() -> (web::JsonConfig, web::QueryConfig, web::PathConfig) {
+    (
+        web::JsonConfig::default().error_handler(json_error),
+        web::QueryConfig::default().error_handler(query_error),
+        web::PathConfig::default().error_handler(path_error),
+    )
+}
@@ -0,0 +1,2 @@
+pub mod validated_json;
+pub mod principal;
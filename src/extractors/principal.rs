@@ -0,0 +1,63 @@
+use std::future::{ready, Ready};
+
+use actix_web::{dev::Payload, FromRequest, HttpMessage, HttpRequest};
+use sqlx::types::Uuid;
+
+use crate::errors::ApiError;
+
+/// A signed in learner, put on the request by the auth guard.
+/// Take it as a handler argument on routes wrapped with `auth::require` or `auth::authenticated`.
+// the whole principal is carried even where a handler only needs the id
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct AuthUser{
+    pub id: Uuid,
+    pub email: String,
+    pub roles: Vec<String>,
+    pub session_id: Uuid,
+}
+
+/// A signed in admin, see `AuthUser`.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct AuthAdmin{
+    pub id: Uuid,
+    pub email: String,
+    pub roles: Vec<String>,
+    pub session_id: Uuid,
+}
+
+/// The guard stores exactly one of the two, so finding the other one means
+/// the token belongs to the wrong kind of account for this route.
+fn principal<T: Clone + 'static, Other: 'static>(req:&HttpRequest, expected:&str) -> Result<T, ApiError> {
+
+    let extensions = req.extensions();
+
+    if let Some(principal) = extensions.get::<T>() {
+        return Ok(principal.clone());
+    }
+
+    if extensions.get::<Other>().is_some() {
+        return Err(ApiError::forbidden("wrong_account_kind", format!("Sign in as {} to use this route", expected)));
+    }
+
+    Err(ApiError::unauthorized("token_missing", "token missing"))
+}
+
+impl FromRequest for AuthUser{
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req:&HttpRequest, _payload:&mut Payload) -> Self::Future {
+        ready(principal::<AuthUser, AuthAdmin>(req, "a user").map_err(Into::into))
+    }
+}
+
+impl FromRequest for AuthAdmin{
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req:&HttpRequest, _payload:&mut Payload) -> Self::Future {
+        ready(principal::<AuthAdmin, AuthUser>(req, "an admin").map_err(Into::into))
+    }
+}
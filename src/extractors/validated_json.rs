@@ -0,0 +1,44 @@
+use std::ops::Deref;
+
+use actix_web::{dev::Payload, web::Json, FromRequest, HttpRequest};
+use futures_util::future::LocalBoxFuture;
+use serde::de::DeserializeOwned;
+use validator::Validate;
+
+use crate::errors::ApiError;
+
+/// Like `web::Json`, but runs the schema's `#[validate]` rules before the handler sees it.
+/// Every broken rule is reported at once in a 422.
+pub struct ValidatedJson<T>(pub T);
+
+impl<T> ValidatedJson<T>{
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for ValidatedJson<T>{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidatedJson<T>{
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req:&HttpRequest, payload:&mut Payload) -> Self::Future {
+
+        let json = Json::<T>::from_request(req, payload);
+
+        Box::pin(async move {
+            let value = json.await?.into_inner();
+
+            value.validate().map_err(ApiError::from)?;
+
+            Ok(ValidatedJson(value))
+        })
+    }
+}
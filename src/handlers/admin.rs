@@ -1,29 +1,28 @@
 use std::str::FromStr;
 
-use actix_web::{get, post, put, web::{self, Json}, HttpMessage, HttpRequest, HttpResponse, Responder};
+use actix_web::{delete, get, http::header::{self, EntityTag}, patch, post, put, web, HttpRequest, HttpResponse, Responder, ResponseError};
 use chrono::{Duration, Utc};
-use jsonwebtoken::{encode, EncodingKey, Header};
 use sqlx::types::Uuid;
 
-use crate::{errors::CustomError, models::{admin::{check_admin_exists, create_admin, get_admin_id_by_email, retrieve_admin_password}, course::{self, create_course}}, schema::{admin::{CourseResponse, CreateAdmin, CreateCourse, CreateCourseWithoutAdminId, UpdateCourse}, EmailAndPassword, JWTClaims, SigninResponse, SignupResponse}, utils::{hash_password, verify_password}, GlobalState};
+use crate::{errors::{ApiError, DbError}, extractors::{principal::AuthAdmin, validated_json::ValidatedJson}, handlers::content::owned_course, mailer::Mail, models::{admin::{check_admin_exists, create_admin, get_admin_by_email, get_admin_email_by_id, get_admin_id_by_email}, course::{self, create_course, CourseDeletion}, password_reset, role::{assign_role, get_principal_roles, roles, unassign_role}, session::{self, ADMIN_SESSION, USER_SESSION}}, schema::{admin::{CourseResponse, CreateAdmin, CreateCourse, CreateCourseWithoutAdminId, PatchCourse, UpdateCourse}, EmailAndPassword, EmailRequest, MessageResponse, RefreshTokenRequest, ResetPasswordRequest, RoleAssignment, SigninResponse, SignupResponse}, utils::{create_access_token, generate_token, hash_password, hash_token, verify_password, PASSWORD_RESET_TOKEN_TTL_MINUTES}, GlobalState};
 
 #[post("/signup")]
-async fn signup_admin(data:web::Data<GlobalState>, admin:Json<CreateAdmin>) -> impl Responder{
+async fn signup_admin(data:web::Data<GlobalState>, admin:ValidatedJson<CreateAdmin>) -> impl Responder{
     let pool = &data.pool;
     let admin_exists = check_admin_exists(&data.pool, &admin.email).await;
 
     if let Err(e) = admin_exists{
-        return HttpResponse::InternalServerError().json(e);
+        return ApiError::from(e).error_response();
     }
 
-    if admin_exists.unwrap() == true{
-        return HttpResponse::BadRequest().json(CustomError{error:"User exists already with this email".to_string()});
+    if admin_exists.unwrap(){
+        return ApiError::conflict("email_taken", "User exists already with this email").error_response();
     }
 
     let password_hash = hash_password(&admin.password);
 
     if let Err(_e) = password_hash{
-        return HttpResponse::InternalServerError().json(CustomError{error:"Something went wrong !".to_string()});
+        return ApiError::internal("Something went wrong !").error_response();
     }
 
     let admin_meta = CreateAdmin{
@@ -34,230 +33,447 @@ async fn signup_admin(data:web::Data<GlobalState>, admin:Json<CreateAdmin>) -> i
 
     let signup_result = create_admin(pool, admin_meta).await;
 
-    match signup_result {
-        Ok(res) => HttpResponse::Ok().json(SignupResponse{message:String::from("Signed up successfully"),id: res}),
-        Err(e) => HttpResponse::BadGateway().json(e),
+    if let Err(e) = signup_result{
+        return ApiError::from(e).error_response();
+    }
+
+    let admin_id = signup_result.unwrap();
+    data.metrics.record_signup(ADMIN_SESSION);
+
+    let admin_uuid = Uuid::from_str(&admin_id);
+
+    if admin_uuid.is_err(){
+        return ApiError::internal("Internal Error").error_response();
+    }
+
+    // every admin signup is an instructor, staff roles are granted separately
+    match assign_role(pool, admin_uuid.unwrap(), ADMIN_SESSION, roles::INSTRUCTOR).await {
+        Ok(_) => HttpResponse::Ok().json(SignupResponse{message:String::from("Signed up successfully"),id: admin_id}),
+        Err(e) => ApiError::from(e).error_response(),
     }
 }
 
 #[post("/signin")]
-async fn signin_admin(data:web::Data<GlobalState>, admin_data:web::Json<EmailAndPassword>) -> impl Responder {
+async fn signin_admin(data:web::Data<GlobalState>, admin_data:ValidatedJson<EmailAndPassword>) -> impl Responder {
 
     let user_exists = check_admin_exists(&data.pool, &admin_data.email).await;
 
     if let Err(e) = user_exists{
-        return HttpResponse::InternalServerError().json(e);
+        return ApiError::from(e).error_response();
     }
 
     // throw when user not found
-    if user_exists.unwrap() == false {
-        return HttpResponse::BadRequest().json(CustomError{error:"Signup first".to_string()});
+    if !user_exists.unwrap() {
+        data.metrics.record_signin(ADMIN_SESSION, false);
+        return ApiError::unauthorized("invalid_credentials", "Signup first").error_response();
     }
 
     let pool = &data.pool;
-    let password_hash_res = retrieve_admin_password(pool, &admin_data.email).await;
+    let admin_res = get_admin_by_email(pool, &admin_data.email).await;
+
+    if let Err(e) = admin_res{
+        return ApiError::from(e).error_response();
+    }
+
+    let admin = admin_res.unwrap();
+
+    if verify_password(&admin_data.password, &admin.password).is_err(){
+        data.metrics.record_signin(ADMIN_SESSION, false);
+        return ApiError::unauthorized("invalid_credentials", "Enter Valid Password").error_response();
+    }
+
+    let admin_uuid = Uuid::from_str(&admin.id);
+
+    if admin_uuid.is_err(){
+        return ApiError::internal("Internal Error").error_response();
+    }
 
-    if let Err(e) = password_hash_res{
-        return HttpResponse::InternalServerError().json(e);
+    let admin_uuid = admin_uuid.unwrap();
+
+    let admin_roles = get_principal_roles(pool, admin_uuid, ADMIN_SESSION).await;
+
+    if let Err(e) = admin_roles{
+        return ApiError::from(e).error_response();
+    }
+
+    let refresh_token = generate_token();
+    let refresh_expiry = Utc::now() + Duration::days(data.auth.refresh_token_ttl_days);
+
+    let session_id = session::create_session(pool, admin_uuid, ADMIN_SESSION, &hash_token(&refresh_token), refresh_expiry).await;
+
+    if let Err(e) = session_id{
+        return ApiError::from(e).error_response();
+    }
+
+    let token = create_access_token(&data.auth, admin_uuid, &admin.email, &session_id.unwrap(), ADMIN_SESSION, admin_roles.unwrap());
+
+    match token {
+        Ok(token) => {
+            data.metrics.record_signin(ADMIN_SESSION, true);
+            HttpResponse::Ok().json(SigninResponse{message:String::from("Signined in Successfully"), token, refresh_token})
+        },
+        Err(_) => ApiError::internal("Internal Error").error_response(),
     }
 
-    let hash = password_hash_res.unwrap();
-    let is_valid = verify_password(&admin_data.password, &hash);
+}
+
+#[post("/refresh")]
+async fn refresh_admin_token(data:web::Data<GlobalState>, body:ValidatedJson<RefreshTokenRequest>) -> impl Responder {
 
-    let jwt_secret = std::env::var("ADMIN_JWT_PASSWORD").unwrap();
+    let pool = &data.pool;
+
+    let refresh_token = generate_token();
+    let refresh_expiry = Utc::now() + Duration::days(data.auth.refresh_token_ttl_days);
 
-    let tomorrow = Utc::now() + Duration::days(1);
+    let rotated = session::rotate_refresh_token(pool, ADMIN_SESSION, &hash_token(&body.refresh_token), &hash_token(&refresh_token), refresh_expiry).await;
+
+    if let Err(e) = rotated{
+        return ApiError::from(e).error_response();
+    }
 
-    let claims = JWTClaims{
-        sub: admin_data.email.clone(),
-        exp: tomorrow.timestamp() as usize
+    let session = match rotated.unwrap() {
+        Some(session) => session,
+        None => return ApiError::unauthorized("invalid_refresh_token", "Invalid refresh token").error_response(),
     };
 
-    let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(jwt_secret.as_bytes()));
+    let email = get_admin_email_by_id(pool, session.owner_id).await;
 
-    if let Err(_) = token {
-        return HttpResponse::InternalServerError().json(CustomError{error:"Internal Error".to_string()});
+    if let Err(e) = email{
+        return ApiError::from(e).error_response();
     }
 
-    match is_valid {
-        Ok(()) => HttpResponse::Ok().json(SigninResponse{message:String::from("Signined in Successfully"), token:token.unwrap()}),
-        Err(_) => HttpResponse::BadRequest().json(CustomError{error:"Enter Valid Password".to_string()}) 
+    let admin_roles = get_principal_roles(pool, session.owner_id, ADMIN_SESSION).await;
+
+    if let Err(e) = admin_roles{
+        return ApiError::from(e).error_response();
     }
 
+    let token = create_access_token(&data.auth, session.owner_id, &email.unwrap(), &session.id, ADMIN_SESSION, admin_roles.unwrap());
+
+    match token {
+        Ok(token) => HttpResponse::Ok().json(SigninResponse{message:String::from("Token refreshed"), token, refresh_token}),
+        Err(_) => ApiError::internal("Internal Error").error_response(),
+    }
 }
 
 #[post("")]
-async fn create_course_handler(data:web::Data<GlobalState>, course:Json<CreateCourseWithoutAdminId>, req:HttpRequest) -> impl Responder{
-    let pool = &data.pool;
+async fn logout_admin(data:web::Data<GlobalState>, admin:AuthAdmin) -> impl Responder {
+
+    match session::revoke_session(&data.pool, admin.session_id).await {
+        Ok(()) => HttpResponse::Ok().json(MessageResponse{message:"Logged out successfully".to_string()}),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+/// Kills every session of the account, `kind` being the table it lives in.
+async fn force_logout(data:&GlobalState, id:&str, kind:&str) -> HttpResponse {
 
-    let admin_email = req.extensions().get::<String>().cloned();
+    let principal_uuid = Uuid::from_str(id);
 
-    if admin_email.is_none(){
-        return HttpResponse::Forbidden().json(CustomError{error:"email missing".to_string()});
+    if principal_uuid.is_err(){
+        return ApiError::bad_request("invalid_id", format!("Invalid {} id", kind)).error_response();
     }
 
-    let admin_email = admin_email.unwrap();
+    match session::revoke_all_sessions(&data.pool, principal_uuid.unwrap(), kind).await {
+        Ok(count) => HttpResponse::Ok().json(MessageResponse{message:format!("Revoked {} session(s)", count)}),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+// lets support kill every session of a compromised learner account
+#[post("/{user_id}/logout")]
+async fn force_logout_user(data:web::Data<GlobalState>, path:web::Path<String>) -> impl Responder {
+    force_logout(&data, &path.into_inner(), USER_SESSION).await
+}
+
+// same for a compromised admin account, which can do far more damage
+#[post("/{admin_id}/logout")]
+async fn force_logout_admin(data:web::Data<GlobalState>, path:web::Path<String>) -> impl Responder {
+    force_logout(&data, &path.into_inner(), ADMIN_SESSION).await
+}
+
+#[post("/password/forgot")]
+async fn forgot_admin_password(data:web::Data<GlobalState>, body:ValidatedJson<EmailRequest>) -> impl Responder {
+
+    let pool = &data.pool;
+
+    // same answer whether the account exists or not, so emails can't be enumerated
+    let response = MessageResponse{message:"If the account exists, a reset token has been sent".to_string()};
 
-    let admin_exists = get_admin_id_by_email(pool, &admin_email).await;
+    let admin_exists = check_admin_exists(pool, &body.email).await;
 
     if let Err(e) = admin_exists{
-        return HttpResponse::InternalServerError().json(e);
+        return ApiError::from(e).error_response();
+    }
+
+    if !admin_exists.unwrap(){
+        return HttpResponse::Ok().json(response);
     }
 
-    let admin_uuid = Uuid::from_str(&admin_exists.unwrap());
+    let admin_id = get_admin_id_by_email(pool, &body.email).await;
+
+    if let Err(e) = admin_id{
+        return ApiError::from(e).error_response();
+    }
+
+    let admin_uuid = Uuid::from_str(&admin_id.unwrap());
 
     if admin_uuid.is_err(){
-        return HttpResponse::InternalServerError().json(CustomError{error:"Internal Error".to_string()});
+        return ApiError::internal("Internal Error").error_response();
     }
 
-    let course = CreateCourse{
-        title: course.title.clone(),
-        image_url: course.image_url.clone(),
-        price: course.price,
-        admin_id: admin_uuid.unwrap(),
-    };
+    let token = generate_token();
+    let expiry = Utc::now() + Duration::minutes(PASSWORD_RESET_TOKEN_TTL_MINUTES);
 
-    let course_res = create_course(pool, course).await;
+    let created = password_reset::create_reset_token(pool, admin_uuid.unwrap(), ADMIN_SESSION, &hash_token(&token), expiry).await;
 
-    match course_res {
-        Ok(res) => {
-            let parsed_course = CourseResponse{
-                id: res.id,
-                admin_id: res.admin_id.to_string(),
-                title: res.title,
-                image_url: res.image_url,
-                price: res.price,
-            };
-
-            HttpResponse::Ok().json(parsed_course)
-        },
-        Err(e) => HttpResponse::BadGateway().json(e),
+    if let Err(e) = created{
+        return ApiError::from(e).error_response();
     }
 
+    let mail = Mail{
+        to: body.email.clone(),
+        subject: "Reset your password".to_string(),
+        body: format!("Your password reset token expires in {} minutes: {}", PASSWORD_RESET_TOKEN_TTL_MINUTES, token),
+    };
+
+    match data.mailer.send(&mail) {
+        Ok(()) => HttpResponse::Ok().json(response),
+        Err(e) => ApiError::from(e).error_response(),
+    }
 }
 
-#[put("/{id}")]
-async fn update_course_handler(data:web::Data<GlobalState>, course:Json<UpdateCourse>, req:HttpRequest, path:web::Path<String>) -> impl Responder {
-    
-    let pool = &data.pool;
+#[post("/password/reset")]
+async fn reset_admin_password(data:web::Data<GlobalState>, body:ValidatedJson<ResetPasswordRequest>) -> impl Responder {
 
-    let course_id = path.into_inner();
-    let course_uuid = Uuid::from_str(&course_id);
+    let password_hash = hash_password(&body.password);
 
-    if course_uuid.is_err(){
-        return HttpResponse::InternalServerError().json(CustomError{error:"Internal Error".to_string()});
+    if let Err(_e) = password_hash{
+        return ApiError::internal("Something went wrong !").error_response();
     }
 
-    let admin_email = req.extensions().get::<String>().cloned();
+    let reset = password_reset::reset_password_with_token(&data.pool, ADMIN_SESSION, &hash_token(&body.token), &password_hash.unwrap()).await;
 
-    if admin_email.is_none(){
-        return HttpResponse::Forbidden().json(CustomError{error:"email missing".to_string()});
+    match reset {
+        Ok(true) => HttpResponse::Ok().json(MessageResponse{message:"Password reset successfully".to_string()}),
+        Ok(false) => ApiError::bad_request("invalid_reset_token", "Invalid or expired reset token").error_response(),
+        Err(e) => ApiError::from(e).error_response(),
     }
+}
 
-    let admin_email = admin_email.unwrap();
+#[post("/assign")]
+async fn assign_role_handler(data:web::Data<GlobalState>, body:ValidatedJson<RoleAssignment>) -> impl Responder {
 
-    let admin_exists = get_admin_id_by_email(pool, &admin_email).await;
+    let principal_uuid = Uuid::from_str(&body.principal_id);
 
-    if let Err(e) = admin_exists{
-        return HttpResponse::InternalServerError().json(e);
+    if principal_uuid.is_err(){
+        return ApiError::bad_request("invalid_id", "Invalid principal id").error_response();
     }
 
-    let admin_uuid = Uuid::from_str(&admin_exists.unwrap());
+    // new roles land in the token on the next signin or refresh
+    match assign_role(&data.pool, principal_uuid.unwrap(), &body.principal_kind, &body.role).await {
+        Ok(true) => HttpResponse::Ok().json(MessageResponse{message:"Role assigned".to_string()}),
+        Ok(false) => ApiError::not_found("role_not_found", "Role not found").error_response(),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
 
-    if admin_uuid.is_err(){
-        return HttpResponse::InternalServerError().json(CustomError{error:"Internal Error".to_string()});
+#[post("/unassign")]
+async fn unassign_role_handler(data:web::Data<GlobalState>, body:ValidatedJson<RoleAssignment>) -> impl Responder {
+
+    let principal_uuid = Uuid::from_str(&body.principal_id);
+
+    if principal_uuid.is_err(){
+        return ApiError::bad_request("invalid_id", "Invalid principal id").error_response();
+    }
+
+    match unassign_role(&data.pool, principal_uuid.unwrap(), &body.principal_kind, &body.role).await {
+        Ok(()) => HttpResponse::Ok().json(MessageResponse{message:"Role removed".to_string()}),
+        Err(e) => ApiError::from(e).error_response(),
     }
+}
 
-    let existing_course = course::get_course_by_id(pool, course_uuid.unwrap()).await;
+/// The course version the client last saw, taken from `If-Match: "<version>"`.
+/// Edits without the header are refused so nobody overwrites a change they never saw,
+/// `*` is the explicit way to skip the check and gives None.
+fn if_match_version(req:&HttpRequest) -> Result<Option<i32>, ApiError>{
 
-    if let Err(e) = existing_course{
-        return HttpResponse::InternalServerError().json(e);
+    let Some(value) = req.headers().get(header::IF_MATCH) else {
+        return Err(ApiError::precondition_required("if_match_required", "Send the course's ETag in If-Match, or * to skip the check"));
+    };
+
+    let value = value.to_str().unwrap_or_default().trim();
+
+    if value == "*" {
+        return Ok(None);
     }
 
-    let existing_course = existing_course.unwrap();
+    value.trim_start_matches("W/").trim_matches('"').parse::<i32>()
+    .map(Some)
+    .map_err(|_e| ApiError::bad_request("invalid_if_match", "Invalid If-Match header"))
+}
+
+fn course_with_etag(course:course::Course) -> HttpResponse{
+    HttpResponse::Ok()
+    .insert_header(header::ETag(EntityTag::new_strong(course.version.to_string())))
+    .json(CourseResponse::from(course))
+}
+
+fn course_changed() -> HttpResponse{
+    ApiError::precondition_failed("version_mismatch", "Course was changed by someone else, reload it and try again").error_response()
+}
+
+#[post("")]
+async fn create_course_handler(data:web::Data<GlobalState>, course:ValidatedJson<CreateCourseWithoutAdminId>, admin:AuthAdmin) -> impl Responder{
+    let pool = &data.pool;
+
+    let course = CreateCourse{
+        title: course.title.clone(),
+        image_url: course.image_url.clone(),
+        price: course.price,
+        category: course.category.clone(),
+        description: course.description.clone(),
+        admin_id: admin.id,
+    };
+
+    let course_res = create_course(pool, course).await;
 
-    if existing_course.admin_id != admin_uuid.unwrap(){
-        return HttpResponse::Forbidden().json(CustomError{error:"Unauthorized".to_string()});
+    match course_res {
+        Ok(res) => course_with_etag(res),
+        Err(e @ DbError::AdminNotFound) => ApiError::forbidden("admin_not_found", e.to_string()).error_response(),
+        Err(e) => ApiError::from(e).error_response(),
     }
 
+}
+
+#[put("/{id}")]
+async fn update_course_handler(data:web::Data<GlobalState>, course:ValidatedJson<UpdateCourse>, admin:AuthAdmin, req:HttpRequest, path:web::Path<String>) -> impl Responder {
+    
+    let pool = &data.pool;
+
+    let existing_course = match owned_course(pool, &admin, &path.into_inner()).await {
+        Ok(existing_course) => existing_course,
+        Err(res) => return res,
+    };
+
+    let expected_version = match if_match_version(&req) {
+        Ok(expected_version) => expected_version,
+        Err(e) => return e.error_response(),
+    };
+
+    let course_uuid = match Uuid::from_str(&existing_course.id) {
+        Ok(course_uuid) => course_uuid,
+        Err(_) => return ApiError::internal("Internal Error").error_response(),
+    };
+
     let course = UpdateCourse{
         title: course.title.clone(),
         image_url: course.image_url.clone(),
         price: course.price,
+        category: course.category.clone(),
+        description: course.description.clone(),
     };
 
-    let course_res = course::update_course(pool, course).await;
+    let course_res = course::update_course(pool, course_uuid, course, expected_version).await;
 
     match course_res {
-        Ok(res) => {
-            let parsed_course = CourseResponse{
-                id: res.id,
-                admin_id: res.admin_id.to_string(),
-                title: res.title,
-                image_url: res.image_url,
-                price: res.price,
-            };
-
-            HttpResponse::Ok().json(parsed_course)
-        },
-        Err(e) => HttpResponse::BadGateway().json(e),
+        Ok(Some(res)) => course_with_etag(res),
+        Ok(None) => course_changed(),
+        Err(e) => ApiError::from(e).error_response(),
     }
 
 }
 
-#[get("/courses")]
-async fn get_all_courses_handler(data:web::Data<GlobalState>, req:HttpRequest) -> impl Responder {
+/// Updates only the fields present in the body. Needs the course's ETag in `If-Match`
+/// to make sure nobody else changed it in the meantime.
+#[patch("/{id}")]
+async fn patch_course_handler(data:web::Data<GlobalState>, patch:ValidatedJson<PatchCourse>, admin:AuthAdmin, req:HttpRequest, path:web::Path<String>) -> impl Responder {
+
     let pool = &data.pool;
 
-    let admin_email = req.extensions().get::<String>().cloned();
+    let existing_course = match owned_course(pool, &admin, &path.into_inner()).await {
+        Ok(existing_course) => existing_course,
+        Err(res) => return res,
+    };
 
-    if admin_email.is_none(){
-        return HttpResponse::Forbidden().json(CustomError{error:"email missing".to_string()});
-    }
+    let expected_version = match if_match_version(&req) {
+        Ok(expected_version) => expected_version,
+        Err(e) => return e.error_response(),
+    };
 
-    let admin_email = admin_email.unwrap();
+    let patch = patch.into_inner();
 
-    let admin_exists = get_admin_id_by_email(pool, &admin_email).await;
+    if patch.title.is_none() && patch.image_url.is_none() && patch.price.is_none() && patch.category.is_none() && patch.description.is_none(){
+        return ApiError::bad_request("empty_update", "Nothing to update").error_response();
+    }
 
-    if let Err(e) = admin_exists{
-        return HttpResponse::InternalServerError().json(e);
+    let course_uuid = match Uuid::from_str(&existing_course.id) {
+        Ok(course_uuid) => course_uuid,
+        Err(_) => return ApiError::internal("Internal Error").error_response(),
+    };
+
+    match course::patch_course(pool, course_uuid, patch, expected_version).await {
+        Ok(Some(res)) => course_with_etag(res),
+        Ok(None) => course_changed(),
+        Err(e) => ApiError::from(e).error_response(),
     }
+}
 
-    let admin_uuid = Uuid::from_str(&admin_exists.unwrap());
+/// Courses nobody bought are removed for good, the rest are archived and hidden
+/// from the admin's list while their buyers keep access. Needs `If-Match` like the edits.
+#[delete("/{id}")]
+async fn delete_course_handler(data:web::Data<GlobalState>, admin:AuthAdmin, req:HttpRequest, path:web::Path<String>) -> impl Responder {
 
-    if admin_uuid.is_err(){
-        return HttpResponse::InternalServerError().json(CustomError{error:"Internal Error".to_string()});
+    let pool = &data.pool;
+
+    let existing_course = match owned_course(pool, &admin, &path.into_inner()).await {
+        Ok(existing_course) => existing_course,
+        Err(res) => return res,
+    };
+
+    let expected_version = match if_match_version(&req) {
+        Ok(expected_version) => expected_version,
+        Err(e) => return e.error_response(),
+    };
+
+    let course_uuid = match Uuid::from_str(&existing_course.id) {
+        Ok(course_uuid) => course_uuid,
+        Err(_) => return ApiError::internal("Internal Error").error_response(),
+    };
+
+    match course::delete_course(pool, course_uuid, expected_version).await {
+        Ok(None) => course_changed(),
+        Ok(Some(CourseDeletion::Deleted)) => HttpResponse::Ok().json(MessageResponse{message:"Course deleted".to_string()}),
+        Ok(Some(CourseDeletion::SoftDeleted)) => HttpResponse::Ok().json(MessageResponse{message:"Course archived, existing buyers keep access".to_string()}),
+        Err(e) => ApiError::from(e).error_response(),
     }
+}
+
+#[get("/courses")]
+async fn get_all_courses_handler(data:web::Data<GlobalState>, admin:AuthAdmin) -> impl Responder {
+    let pool = &data.pool;
 
-    let courses = course::get_all_admin_courses(pool, admin_uuid.unwrap()).await;
+    let courses = course::get_all_admin_courses(pool, admin.id).await;
 
     match courses {
         Ok(courses) => {
-            let parsed_courses = courses.into_iter().map(|course|{
-                CourseResponse{
-                    id: course.id,
-                    admin_id: course.admin_id.to_string(),
-                    title: course.title,
-                    image_url: course.image_url,
-                    price: course.price,
-                }
-            }).collect::<Vec<CourseResponse>>();
+            let parsed_courses = courses.into_iter().map(CourseResponse::from).collect::<Vec<CourseResponse>>();
 
             HttpResponse::Ok().json(parsed_courses)
         },
-        Err(e) => HttpResponse::BadGateway().json(e),
+        Err(e) => ApiError::from(e).error_response(),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{schema::admin::CreateCourseWithoutAdminId, test_init_app::init};
+    use crate::{errors::ErrorBody, models::{bundle::{create_bundle, get_bundle}, order::{create_order, fail_order, OrderItem}, purchase::{has_purchased, purchase_course}}, schema::{admin::{CourseStatusInput, CreateCourseWithoutAdminId}, user::CreateUser}, test_init_app::{init, last_mail_to, token_from_mail}};
     use actix_web::test;
     use super::*;
 
     #[actix_web::test]
     async fn test_admin_signup_and_signin() {
-        let (app, pool) = init(signup_admin).await;
+        let (app, pool) = init().await;
 
         let admin = CreateAdmin {
             email: String::from("admin@test.com"),
@@ -301,7 +517,7 @@ mod tests {
 
     #[actix_web::test]
     async fn test_admin_invalid_credentials() {
-        let (app, pool) = init(signup_admin).await;
+        let (app, pool) = init().await;
 
         let admin = CreateAdmin {
             email: String::from("admin2@test.com"),
@@ -328,7 +544,7 @@ mod tests {
             .send_request(&app)
             .await;
 
-        let res_body: CustomError = test::read_body_json(res).await;
+        let res_body: ErrorBody = test::read_body_json(res).await;
         assert_eq!(res_body.error, "Enter Valid Password".to_string());
 
         sqlx::query("DELETE FROM admin_table WHERE email = $1")
@@ -340,7 +556,7 @@ mod tests {
 
     #[actix_web::test]
     async fn test_create_course() {
-        let (app, pool) = init(signup_admin).await;
+        let (app, pool) = init().await;
 
         // First signup and signin to get token
         let admin = CreateAdmin {
@@ -374,11 +590,13 @@ mod tests {
             title: "Test Course".to_string(),
             image_url: Some("https://test.com/image.jpg".to_string()),
             price: 5000,
+            category: None,
+            description: None,
         };
 
         let res = test::TestRequest::post()
             .set_json(course)
-            .append_header(("Authorization", token))
+            .append_header(("Authorization", token.clone()))
             .uri("/api/v1/admin/course")
             .send_request(&app)
             .await;
@@ -388,6 +606,29 @@ mod tests {
         let course_res: CourseResponse = test::read_body_json(res).await;
         assert_eq!(course_res.title, "Test Course");
 
+        // Negative prices and junk image urls never reach the DB
+        let course = CreateCourseWithoutAdminId {
+            title: "Broken Course".to_string(),
+            image_url: Some("not a url".to_string()),
+            price: -10,
+            category: None,
+            description: None,
+        };
+
+        let res = test::TestRequest::post()
+            .set_json(course)
+            .append_header(("Authorization", token))
+            .uri("/api/v1/admin/course")
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 422);
+
+        let res_body: ErrorBody = test::read_body_json(res).await;
+        assert_eq!(res_body.details.len(), 2);
+        assert_eq!(res_body.details[0].field, "image_url");
+        assert_eq!(res_body.details[1].field, "price");
+
         // Cleanup
         sqlx::query("DELETE FROM course_table WHERE title = $1")
             .bind("Test Course")
@@ -404,12 +645,14 @@ mod tests {
 
     #[actix_web::test]
     async fn test_create_course_without_auth() {
-        let (app, _) = init(create_course_handler).await;
+        let (app, _) = init().await;
 
         let course = CreateCourseWithoutAdminId {
             title: "Test Course".to_string(),
             image_url: Some("https://test.com/image.jpg".to_string()),
             price: 7888,
+            category: None,
+            description: None,
         };
 
         let req = test::TestRequest::post()
@@ -417,20 +660,16 @@ mod tests {
             .uri("/api/v1/admin/course")
             .to_request();
 
-        let res = test::try_call_service(&app, req).await;
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status().as_u16(), 401);
 
-        match res {
-            Ok(_) => panic!("Expected error"),
-            Err(e) => {
-                let e= e.as_error::<CustomError>().unwrap();
-                assert_eq!(e.error, "token missing");
-            },
-        }
+        let error_body: ErrorBody = test::read_body_json(res).await;
+        assert_eq!(error_body.code, "token_missing");
     }
 
     #[actix_web::test]
     async fn test_update_course() {
-        let (app, pool) = init(signup_admin).await;
+        let (app, pool) = init().await;
 
         // First create an admin and get token
         let admin = CreateAdmin {
@@ -464,6 +703,8 @@ mod tests {
             title: "Original Course".to_string(),
             image_url: Some("https://test.com/old.jpg".to_string()),
             price: 5000,
+            category: None,
+            description: None,
         };
 
         let create_res = test::TestRequest::post()
@@ -480,11 +721,14 @@ mod tests {
             title: "Updated Course".to_string(),
             image_url: Some("https://test.com/new.jpg".to_string()),
             price: 6000,
+            category: None,
+            description: None,
         };
 
         let update_res = test::TestRequest::put()
             .set_json(update_course)
             .append_header(("Authorization", token))
+            .append_header(("If-Match", "\"1\""))
             .uri(&format!("/api/v1/admin/course/{}", created_course.id))
             .send_request(&app)
             .await;
@@ -513,7 +757,7 @@ mod tests {
 
     #[actix_web::test]
     async fn test_get_all_courses() {
-        let (app, pool) = init(signup_admin).await;
+        let (app, pool) = init().await;
 
         // Create admin and get token
         let admin = CreateAdmin {
@@ -549,11 +793,15 @@ mod tests {
                 title: "Course 1".to_string(),
                 image_url: Some("https://test.com/1.jpg".to_string()),
                 price: 5000,
+                category: None,
+                description: None,
             },
             CreateCourseWithoutAdminId {
                 title: "Course 2".to_string(),
                 image_url: Some("https://test.com/2.jpg".to_string()),
                 price: 6000,
+                category: None,
+                description: None,
             },
         ];
 
@@ -603,4 +851,720 @@ mod tests {
             .unwrap();
     }
 
+    #[actix_web::test]
+    async fn test_patch_course_with_etag() {
+        let (app, pool) = init().await;
+
+        let _ = test::TestRequest::post()
+            .set_json(CreateAdmin {
+                email: String::from("admin_patch@test.com"),
+                name: String::from("Test Admin"),
+                password: String::from("adminpass123")
+            })
+            .uri("/api/v1/admin/signup")
+            .send_request(&app)
+            .await;
+
+        let signin_res = test::TestRequest::post()
+            .set_json(EmailAndPassword {
+                email: "admin_patch@test.com".to_string(),
+                password: "adminpass123".to_string(),
+            })
+            .uri("/api/v1/admin/signin")
+            .send_request(&app)
+            .await;
+
+        let token = test::read_body_json::<SigninResponse, _>(signin_res).await.token;
+
+        let mut created_courses = Vec::new();
+
+        for title in ["Patched Course", "Untouched Course"] {
+            let create_res = test::TestRequest::post()
+                .set_json(CreateCourseWithoutAdminId {
+                    title: title.to_string(),
+                    image_url: None,
+                    price: 1000,
+                    category: Some("patching".to_string()),
+                    description: None,
+                })
+                .append_header(("Authorization", token.clone()))
+                .uri("/api/v1/admin/course")
+                .send_request(&app)
+                .await;
+
+            assert_eq!(create_res.headers().get(header::ETAG).unwrap(), "\"1\"");
+
+            let course: CourseResponse = test::read_body_json(create_res).await;
+            created_courses.push(course.id);
+        }
+
+        let patch_uri = format!("/api/v1/admin/course/{}", created_courses[0]);
+
+        // Only the supplied field changes, and the version moves on
+        let res = test::TestRequest::patch()
+            .set_json(PatchCourse { price: Some(2500), ..Default::default() })
+            .append_header(("Authorization", token.clone()))
+            .append_header(("If-Match", "\"1\""))
+            .uri(&patch_uri)
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+        assert_eq!(res.headers().get(header::ETAG).unwrap(), "\"2\"");
+
+        let patched: CourseResponse = test::read_body_json(res).await;
+        assert_eq!(patched.price, 2500);
+        assert_eq!(patched.title, "Patched Course");
+        assert_eq!(patched.category.as_deref(), Some("patching"));
+        assert_eq!(patched.version, 2);
+
+        // A second editor still holding version 1 is turned away
+        let res = test::TestRequest::patch()
+            .set_json(PatchCourse { title: Some("Stale Edit".to_string()), ..Default::default() })
+            .append_header(("Authorization", token.clone()))
+            .append_header(("If-Match", "\"1\""))
+            .uri(&patch_uri)
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 412);
+
+        // Leaving If-Match out isn't a way around the check
+        let res = test::TestRequest::patch()
+            .set_json(PatchCourse { title: Some("Blind Edit".to_string()), ..Default::default() })
+            .append_header(("Authorization", token.clone()))
+            .uri(&patch_uri)
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 428);
+
+        let error_body: ErrorBody = test::read_body_json(res).await;
+        assert_eq!(error_body.code, "if_match_required");
+
+        let res = test::TestRequest::patch()
+            .set_json(PatchCourse::default())
+            .append_header(("Authorization", token.clone()))
+            .append_header(("If-Match", "\"2\""))
+            .uri(&patch_uri)
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 400);
+
+        // A full update only touches the course in the path
+        let res = test::TestRequest::put()
+            .set_json(UpdateCourse {
+                title: "Replaced Course".to_string(),
+                image_url: None,
+                price: 3000,
+                category: None,
+                description: None,
+            })
+            .append_header(("Authorization", token.clone()))
+            .append_header(("If-Match", "\"2\""))
+            .uri(&patch_uri)
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.headers().get(header::ETAG).unwrap(), "\"3\"");
+
+        // A nullable field sent as null is cleared, a missing one is left alone
+        let res = test::TestRequest::patch()
+            .set_json(PatchCourse { category: Some(Some("patching".to_string())), description: Some(Some("About the course".to_string())), ..Default::default() })
+            .append_header(("Authorization", token.clone()))
+            .append_header(("If-Match", "*"))
+            .uri(&patch_uri)
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.headers().get(header::ETAG).unwrap(), "\"4\"");
+
+        let res = test::TestRequest::patch()
+            .set_json(PatchCourse { category: Some(None), ..Default::default() })
+            .append_header(("Authorization", token.clone()))
+            .append_header(("If-Match", "\"4\""))
+            .uri(&patch_uri)
+            .send_request(&app)
+            .await;
+
+        let patched: CourseResponse = test::read_body_json(res).await;
+        assert_eq!(patched.category, None);
+        assert_eq!(patched.description.as_deref(), Some("About the course"));
+        assert_eq!(patched.version, 5);
+
+        // A status change is an edit too, so it invalidates the ETag
+        let res = test::TestRequest::put()
+            .set_json(CourseStatusInput { status: "archived".to_string() })
+            .append_header(("Authorization", token.clone()))
+            .uri(&format!("{}/status", patch_uri))
+            .send_request(&app)
+            .await;
+
+        let archived: CourseResponse = test::read_body_json(res).await;
+        assert_eq!(archived.version, 6);
+
+        let res = test::TestRequest::patch()
+            .set_json(PatchCourse { title: Some("Stale Edit".to_string()), ..Default::default() })
+            .append_header(("Authorization", token.clone()))
+            .append_header(("If-Match", "\"5\""))
+            .uri(&patch_uri)
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 412);
+
+        let res = test::TestRequest::get()
+            .append_header(("Authorization", token))
+            .uri("/api/v1/admin/course/courses")
+            .send_request(&app)
+            .await;
+
+        let courses: Vec<CourseResponse> = test::read_body_json(res).await;
+        let untouched = courses.iter().find(|c| c.id == created_courses[1]).unwrap();
+        assert_eq!(untouched.title, "Untouched Course");
+        assert_eq!(untouched.version, 1);
+
+        // Cleanup
+        sqlx::query("DELETE FROM course_table WHERE admin_id = (SELECT id FROM admin_table WHERE email = $1)")
+            .bind("admin_patch@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM admin_table WHERE email = $1")
+            .bind("admin_patch@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_delete_course() {
+        let (app, pool) = init().await;
+
+        let signup_res = test::TestRequest::post()
+            .set_json(CreateAdmin {
+                email: String::from("admin_delete@test.com"),
+                name: String::from("Test Admin"),
+                password: String::from("adminpass123")
+            })
+            .uri("/api/v1/admin/signup")
+            .send_request(&app)
+            .await;
+
+        let admin_uuid = Uuid::from_str(&test::read_body_json::<SignupResponse, _>(signup_res).await.id).unwrap();
+
+        let signin_res = test::TestRequest::post()
+            .set_json(EmailAndPassword {
+                email: "admin_delete@test.com".to_string(),
+                password: "adminpass123".to_string(),
+            })
+            .uri("/api/v1/admin/signin")
+            .send_request(&app)
+            .await;
+
+        let token = test::read_body_json::<SigninResponse, _>(signin_res).await.token;
+
+        let mut created_courses = Vec::new();
+
+        for title in ["Unsold Course", "Sold Course", "Abandoned Course", "Bundled Course"] {
+            let create_res = test::TestRequest::post()
+                .set_json(CreateCourseWithoutAdminId {
+                    title: title.to_string(),
+                    image_url: None,
+                    price: 1000,
+                    category: None,
+                    description: None,
+                })
+                .append_header(("Authorization", token.clone()))
+                .uri("/api/v1/admin/course")
+                .send_request(&app)
+                .await;
+
+            let course: CourseResponse = test::read_body_json(create_res).await;
+            created_courses.push(Uuid::from_str(&course.id).unwrap());
+        }
+
+        let (unsold, sold, abandoned, bundled) = (created_courses[0], created_courses[1], created_courses[2], created_courses[3]);
+
+        let signup_res = test::TestRequest::post()
+            .set_json(CreateUser {
+                email: String::from("learner_delete@test.com"),
+                name: String::from("Test User"),
+                password: String::from("userpass123")
+            })
+            .uri("/api/v1/user/signup")
+            .send_request(&app)
+            .await;
+
+        let user_id = test::read_body_json::<SignupResponse, _>(signup_res).await.id;
+        let user_uuid = Uuid::from_str(&user_id).unwrap();
+
+        purchase_course(&mut pool.acquire().await.unwrap(), sold, user_uuid, None, None).await.unwrap();
+
+        let failed_order = create_order(&pool, user_uuid, OrderItem::Course(abandoned), 1000, None, "mock").await.unwrap().unwrap();
+        fail_order(&pool, Uuid::from_str(&failed_order.id).unwrap()).await.unwrap();
+
+        let bundle = create_bundle(&pool, admin_uuid, "Delete Bundle", 1500, &[sold, bundled]).await.unwrap();
+
+        // A stale version doesn't delete anything
+        let res = test::TestRequest::delete()
+            .append_header(("Authorization", token.clone()))
+            .append_header(("If-Match", "\"2\""))
+            .uri(&format!("/api/v1/admin/course/{}", unsold))
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 412);
+
+        // A course nobody bought is gone for good
+        let res = test::TestRequest::delete()
+            .append_header(("Authorization", token.clone()))
+            .append_header(("If-Match", "\"1\""))
+            .uri(&format!("/api/v1/admin/course/{}", unsold))
+            .send_request(&app)
+            .await;
+
+        let body: MessageResponse = test::read_body_json(res).await;
+        assert_eq!(body.message, "Course deleted");
+        assert!(course::get_course_by_id(&pool, unsold).await.unwrap().is_none());
+
+        // A sold one is archived and its buyer keeps access
+        let res = test::TestRequest::delete()
+            .append_header(("Authorization", token.clone()))
+            .append_header(("If-Match", "\"1\""))
+            .uri(&format!("/api/v1/admin/course/{}", sold))
+            .send_request(&app)
+            .await;
+
+        let body: MessageResponse = test::read_body_json(res).await;
+        assert_eq!(body.message, "Course archived, existing buyers keep access");
+
+        let sold_course = course::get_course_by_id(&pool, sold).await.unwrap().unwrap();
+        assert_eq!(sold_course.status, "archived");
+        assert!(sold_course.deleted_at.is_some());
+        assert!(has_purchased(&pool, user_uuid, sold).await.unwrap());
+
+        // Even a failed order keeps the course around as order history
+        let res = test::TestRequest::delete()
+            .append_header(("Authorization", token.clone()))
+            .append_header(("If-Match", "\"1\""))
+            .uri(&format!("/api/v1/admin/course/{}", abandoned))
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+
+        let abandoned_course = course::get_course_by_id(&pool, abandoned).await.unwrap().unwrap();
+        assert!(abandoned_course.deleted_at.is_some());
+
+        // A bundled one too, the bundle it is in stops selling
+        let res = test::TestRequest::delete()
+            .append_header(("Authorization", token.clone()))
+            .append_header(("If-Match", "\"1\""))
+            .uri(&format!("/api/v1/admin/course/{}", bundled))
+            .send_request(&app)
+            .await;
+
+        let body: MessageResponse = test::read_body_json(res).await;
+        assert_eq!(body.message, "Course archived, existing buyers keep access");
+
+        let bundle = get_bundle(&pool, Uuid::from_str(&bundle.id).unwrap()).await.unwrap().unwrap();
+        assert!(!bundle.available);
+
+        let res = test::TestRequest::get()
+            .append_header(("Authorization", token.clone()))
+            .uri("/api/v1/admin/course/courses")
+            .send_request(&app)
+            .await;
+
+        let courses: Vec<CourseResponse> = test::read_body_json(res).await;
+        assert!(courses.is_empty());
+
+        let res = test::TestRequest::delete()
+            .append_header(("Authorization", token))
+            .append_header(("If-Match", "*"))
+            .uri(&format!("/api/v1/admin/course/{}", sold))
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 404);
+
+        // Cleanup
+        sqlx::query("DELETE FROM purchases_table WHERE course_id = $1")
+            .bind(sold)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM orders_table WHERE user_id = $1")
+            .bind(user_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM bundles_table WHERE id = $1")
+            .bind(Uuid::from_str(&bundle.id).unwrap())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM course_table WHERE id = ANY($1)")
+            .bind(vec![sold, abandoned, bundled])
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM user_table WHERE id = $1")
+            .bind(user_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM admin_table WHERE email = $1")
+            .bind("admin_delete@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_force_logout_user() {
+        let (app, pool) = init().await;
+
+        let admin = CreateAdmin {
+            email: String::from("admin_support@test.com"),
+            name: String::from("Test Admin"),
+            password: String::from("adminpass123")
+        };
+
+        let signup_res = test::TestRequest::post()
+            .set_json(admin)
+            .uri("/api/v1/admin/signup")
+            .send_request(&app)
+            .await;
+
+        let admin_id = test::read_body_json::<SignupResponse, _>(signup_res).await.id;
+
+        // only support staff may force a logout
+        assign_role(&pool, Uuid::from_str(&admin_id).unwrap(), ADMIN_SESSION, roles::SUPPORT).await.unwrap();
+
+        let signin_res = test::TestRequest::post()
+            .set_json(EmailAndPassword {
+                email: "admin_support@test.com".to_string(),
+                password: "adminpass123".to_string(),
+            })
+            .uri("/api/v1/admin/signin")
+            .send_request(&app)
+            .await;
+
+        let admin_token = test::read_body_json::<SigninResponse, _>(signin_res).await.token;
+
+        let user = CreateUser {
+            email: String::from("compromised@test.com"),
+            name: String::from("Test User"),
+            password: String::from("userpass123")
+        };
+
+        let signup_res = test::TestRequest::post()
+            .set_json(user)
+            .uri("/api/v1/user/signup")
+            .send_request(&app)
+            .await;
+
+        let user_id = test::read_body_json::<SignupResponse, _>(signup_res).await.id;
+
+        let signin_res = test::TestRequest::post()
+            .set_json(EmailAndPassword {
+                email: "compromised@test.com".to_string(),
+                password: "userpass123".to_string(),
+            })
+            .uri("/api/v1/user/signin")
+            .send_request(&app)
+            .await;
+
+        let user_token = test::read_body_json::<SigninResponse, _>(signin_res).await.token;
+
+        // an admin token doesn't pass as a learner on learner routes
+        let res = test::TestRequest::post()
+            .append_header(("Authorization", admin_token.clone()))
+            .uri("/api/v1/user/logout")
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 403);
+
+        let res_body: ErrorBody = test::read_body_json(res).await;
+        assert_eq!(res_body.code, "wrong_account_kind");
+
+        let res = test::TestRequest::post()
+            .append_header(("Authorization", admin_token.clone()))
+            .uri(&format!("/api/v1/admin/users/{}/logout", user_id))
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+
+        let res_body: MessageResponse = test::read_body_json(res).await;
+        assert_eq!(res_body.message, "Revoked 1 session(s)");
+
+        let req = test::TestRequest::get()
+            .append_header(("Authorization", user_token))
+            .uri("/api/v1/user/purchases")
+            .to_request();
+
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status().as_u16(), 401);
+
+        let error_body: ErrorBody = test::read_body_json(res).await;
+        assert_eq!(error_body.code, "session_revoked");
+
+        // a compromised admin account can be locked out the same way
+        let signup_res = test::TestRequest::post()
+            .set_json(CreateAdmin {
+                email: String::from("admin_compromised@test.com"),
+                name: String::from("Test Admin"),
+                password: String::from("adminpass123")
+            })
+            .uri("/api/v1/admin/signup")
+            .send_request(&app)
+            .await;
+
+        let compromised_admin_id = test::read_body_json::<SignupResponse, _>(signup_res).await.id;
+
+        let signin_res = test::TestRequest::post()
+            .set_json(EmailAndPassword {
+                email: "admin_compromised@test.com".to_string(),
+                password: "adminpass123".to_string(),
+            })
+            .uri("/api/v1/admin/signin")
+            .send_request(&app)
+            .await;
+
+        let compromised_token = test::read_body_json::<SigninResponse, _>(signin_res).await.token;
+
+        let res = test::TestRequest::post()
+            .append_header(("Authorization", admin_token))
+            .uri(&format!("/api/v1/admin/admins/{}/logout", compromised_admin_id))
+            .send_request(&app)
+            .await;
+
+        let res_body: MessageResponse = test::read_body_json(res).await;
+        assert_eq!(res_body.message, "Revoked 1 session(s)");
+
+        let res = test::TestRequest::get()
+            .append_header(("Authorization", compromised_token))
+            .uri("/api/v1/admin/course/courses")
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 401);
+
+        let error_body: ErrorBody = test::read_body_json(res).await;
+        assert_eq!(error_body.code, "session_revoked");
+
+        // Cleanup
+        let user_uuid = Uuid::from_str(&user_id).unwrap();
+
+        sqlx::query("DELETE FROM sessions_table WHERE owner_id = $1 OR owner_id IN (SELECT id FROM admin_table WHERE email = ANY($2))")
+            .bind(user_uuid)
+            .bind(["admin_support@test.com", "admin_compromised@test.com"])
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM principal_roles_table WHERE principal_id = $1 OR principal_id IN (SELECT id FROM admin_table WHERE email = ANY($2))")
+            .bind(user_uuid)
+            .bind(["admin_support@test.com", "admin_compromised@test.com"])
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM user_table WHERE id = $1")
+            .bind(user_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM admin_table WHERE email = ANY($1)")
+            .bind(["admin_support@test.com", "admin_compromised@test.com"])
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_admin_password_reset() {
+        let (app, pool) = init().await;
+
+        let admin = CreateAdmin {
+            email: String::from("admin_forgetful@test.com"),
+            name: String::from("Test Admin"),
+            password: String::from("adminpass123")
+        };
+
+        let _ = test::TestRequest::post()
+            .set_json(admin)
+            .uri("/api/v1/admin/signup")
+            .send_request(&app)
+            .await;
+
+        let res = test::TestRequest::post()
+            .set_json(EmailRequest { email: "admin_forgetful@test.com".to_string() })
+            .uri("/api/v1/admin/password/forgot")
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+
+        let mail = last_mail_to("admin_forgetful@test.com").expect("reset mail not sent");
+
+        let res = test::TestRequest::post()
+            .set_json(ResetPasswordRequest { token: token_from_mail(&mail), password: "newadminpass".to_string() })
+            .uri("/api/v1/admin/password/reset")
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+
+        let res = test::TestRequest::post()
+            .set_json(EmailAndPassword {
+                email: "admin_forgetful@test.com".to_string(),
+                password: "newadminpass".to_string(),
+            })
+            .uri("/api/v1/admin/signin")
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+
+        // Cleanup
+        sqlx::query("DELETE FROM password_reset_tokens_table WHERE owner_id = (SELECT id FROM admin_table WHERE email = $1)")
+            .bind("admin_forgetful@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM sessions_table WHERE owner_id = (SELECT id FROM admin_table WHERE email = $1)")
+            .bind("admin_forgetful@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM admin_table WHERE email = $1")
+            .bind("admin_forgetful@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_role_management_requires_permission() {
+        let (app, pool) = init().await;
+
+        let mut tokens = Vec::new();
+        let mut admin_ids = Vec::new();
+
+        for email in ["instructor_roles@test.com", "platform_roles@test.com"] {
+            let signup_res = test::TestRequest::post()
+                .set_json(CreateAdmin {
+                    email: email.to_string(),
+                    name: String::from("Test Admin"),
+                    password: String::from("adminpass123")
+                })
+                .uri("/api/v1/admin/signup")
+                .send_request(&app)
+                .await;
+
+            let admin_id = test::read_body_json::<SignupResponse, _>(signup_res).await.id;
+
+            if email == "platform_roles@test.com" {
+                assign_role(&pool, Uuid::from_str(&admin_id).unwrap(), ADMIN_SESSION, roles::PLATFORM_ADMIN).await.unwrap();
+            }
+
+            let signin_res = test::TestRequest::post()
+                .set_json(EmailAndPassword {
+                    email: email.to_string(),
+                    password: "adminpass123".to_string(),
+                })
+                .uri("/api/v1/admin/signin")
+                .send_request(&app)
+                .await;
+
+            tokens.push(test::read_body_json::<SigninResponse, _>(signin_res).await.token);
+            admin_ids.push(admin_id);
+        }
+
+        let assignment = RoleAssignment {
+            principal_id: admin_ids[0].clone(),
+            principal_kind: ADMIN_SESSION.to_string(),
+            role: roles::SUPPORT.to_string(),
+        };
+
+        // only users and admins hold roles, on either end
+        for uri in ["/api/v1/admin/roles/assign", "/api/v1/admin/roles/unassign"] {
+            let res = test::TestRequest::post()
+                .set_json(RoleAssignment { principal_kind: "course".to_string(), ..assignment.clone() })
+                .append_header(("Authorization", tokens[1].clone()))
+                .uri(uri)
+                .send_request(&app)
+                .await;
+
+            assert_eq!(res.status().as_u16(), 422);
+
+            let error_body: ErrorBody = test::read_body_json(res).await;
+            assert_eq!(error_body.details[0].field, "principal_kind");
+        }
+
+        // a plain instructor can't hand out roles
+        let req = test::TestRequest::post()
+            .set_json(&assignment)
+            .append_header(("Authorization", tokens[0].clone()))
+            .uri("/api/v1/admin/roles/assign")
+            .to_request();
+
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status().as_u16(), 403);
+
+        let error_body: ErrorBody = test::read_body_json(res).await;
+        assert_eq!(error_body.code, "missing_permission");
+        assert_eq!(error_body.error, "Missing permission: roles:manage");
+
+        let res = test::TestRequest::post()
+            .set_json(&assignment)
+            .append_header(("Authorization", tokens[1].clone()))
+            .uri("/api/v1/admin/roles/assign")
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+
+        let instructor_roles = get_principal_roles(&pool, Uuid::from_str(&admin_ids[0]).unwrap(), ADMIN_SESSION).await.unwrap();
+        assert_eq!(instructor_roles, vec![roles::INSTRUCTOR.to_string(), roles::SUPPORT.to_string()]);
+
+        // Cleanup
+        for admin_id in admin_ids {
+            let admin_uuid = Uuid::from_str(&admin_id).unwrap();
+
+            sqlx::query("DELETE FROM principal_roles_table WHERE principal_id = $1")
+                .bind(admin_uuid)
+                .execute(&pool)
+                .await
+                .unwrap();
+
+            sqlx::query("DELETE FROM sessions_table WHERE owner_id = $1")
+                .bind(admin_uuid)
+                .execute(&pool)
+                .await
+                .unwrap();
+
+            sqlx::query("DELETE FROM admin_table WHERE id = $1")
+                .bind(admin_uuid)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,386 @@
+use std::str::FromStr;
+
+use actix_web::{get, post, web, HttpResponse, Responder, ResponseError};
+use sqlx::types::Uuid;
+
+use crate::{errors::ApiError, extractors::{principal::{AuthAdmin, AuthUser}, validated_json::ValidatedJson}, handlers::order::{checkout_order, verified_buyer}, models::{bundle::{self, get_unowned_bundle_courses}, course::{self, CourseStatus}, order::OrderItem}, schema::admin::BundleInput, GlobalState};
+
+// a bundle may only contain courses of the instructor selling it
+#[post("")]
+async fn create_bundle_handler(data:web::Data<GlobalState>, admin:AuthAdmin, body:ValidatedJson<BundleInput>) -> impl Responder{
+
+    let pool = &data.pool;
+
+    let course_uuids = body.course_ids.iter().map(|id| Uuid::from_str(id)).collect::<Result<Vec<Uuid>, _>>();
+
+    if course_uuids.is_err(){
+        return ApiError::bad_request("invalid_id", "Invalid id").error_response();
+    }
+
+    let mut course_uuids = course_uuids.unwrap();
+    course_uuids.sort();
+    course_uuids.dedup();
+
+    for course_uuid in &course_uuids {
+
+        let existing_course = match course::get_course_by_id(pool, *course_uuid).await {
+            Ok(Some(existing_course)) => existing_course,
+            Ok(None) => return ApiError::not_found("course_not_found", "Course not found").error_response(),
+            Err(e) => return ApiError::from(e).error_response(),
+        };
+
+        if existing_course.admin_id != admin.id{
+            return ApiError::forbidden("not_owner", "Unauthorized").error_response();
+        }
+
+        if existing_course.status != CourseStatus::Published.as_str() || existing_course.deleted_at.is_some(){
+            return ApiError::bad_request("course_unavailable", "Only published courses can be bundled").error_response();
+        }
+    }
+
+    match bundle::create_bundle(pool, admin.id, body.title.trim(), body.price, &course_uuids).await {
+        Ok(res) => HttpResponse::Ok().json(res),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[get("")]
+async fn get_admin_bundles_handler(data:web::Data<GlobalState>, admin:AuthAdmin) -> impl Responder{
+
+    let pool = &data.pool;
+
+    match bundle::get_admin_bundles(pool, admin.id).await {
+        Ok(bundles) => HttpResponse::Ok().json(bundles),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[get("")]
+async fn get_all_bundles_handler(data:web::Data<GlobalState>) -> impl Responder{
+
+    match bundle::get_all_bundles(&data.pool).await {
+        Ok(bundles) => HttpResponse::Ok().json(bundles),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+// the full bundle price is charged, courses the learner already owns are simply not granted twice
+#[post("/{bundle_id}")]
+async fn purchase_bundle_handler(data:web::Data<GlobalState>, path:web::Path<String>, user:AuthUser) -> impl Responder{
+
+    let pool = &data.pool;
+
+    let user_uuid = match verified_buyer(pool, &user).await {
+        Ok(user_uuid) => user_uuid,
+        Err(res) => return res,
+    };
+
+    let bundle_uuid = Uuid::from_str(&path.into_inner());
+
+    if bundle_uuid.is_err(){
+        return ApiError::bad_request("invalid_id", "Invalid id").error_response();
+    }
+
+    let bundle_uuid = bundle_uuid.unwrap();
+
+    let existing_bundle = bundle::get_bundle(pool, bundle_uuid).await;
+
+    if let Err(e) = existing_bundle{
+        return ApiError::from(e).error_response();
+    }
+
+    let existing_bundle = existing_bundle.unwrap();
+
+    if existing_bundle.is_none(){
+        return ApiError::not_found("bundle_not_found", "Bundle not found").error_response();
+    }
+
+    let existing_bundle = existing_bundle.unwrap();
+
+    if !existing_bundle.available{
+        return ApiError::bad_request("bundle_unavailable", "Bundle is not available for purchase").error_response();
+    }
+
+    let conn = pool.acquire().await;
+
+    if conn.is_err(){
+        return ApiError::internal("Internal Error").error_response();
+    }
+
+    let unowned_courses = get_unowned_bundle_courses(&mut conn.unwrap(), bundle_uuid, user_uuid).await;
+
+    if let Err(e) = unowned_courses{
+        return ApiError::from(e).error_response();
+    }
+
+    if unowned_courses.unwrap().is_empty(){
+        return ApiError::conflict("already_purchased", "Already Purchased").error_response();
+    }
+
+    checkout_order(&data, user_uuid, OrderItem::Bundle(bundle_uuid), existing_bundle.price, None).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{errors::ErrorBody, models::{bundle::Bundle, order::Order, progress::PurchaseWithProgress, purchase::purchase_course}, payments::{PaymentEvent, PaymentOutcome}, schema::{admin::{CourseResponse, CreateAdmin, CreateCourseWithoutAdminId}, user::CreateUser, CheckoutResponse, EmailAndPassword, MessageResponse, SigninResponse, SignupResponse}, test_init_app::{init, publish_course, sign_webhook}};
+    use actix_web::test;
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_bundle_purchase() {
+        let (app, pool) = init().await;
+
+        // 1. An instructor with three courses
+        let _ = test::TestRequest::post()
+            .set_json(CreateAdmin {
+                email: String::from("admin_bundle@test.com"),
+                name: String::from("Test Admin"),
+                password: String::from("adminpass123")
+            })
+            .uri("/api/v1/admin/signup")
+            .send_request(&app)
+            .await;
+
+        let signin_res = test::TestRequest::post()
+            .set_json(EmailAndPassword {
+                email: "admin_bundle@test.com".to_string(),
+                password: "adminpass123".to_string(),
+            })
+            .uri("/api/v1/admin/signin")
+            .send_request(&app)
+            .await;
+
+        let admin_token = test::read_body_json::<SigninResponse, _>(signin_res).await.token;
+
+        let mut course_ids = Vec::new();
+
+        for (title, price) in [("Bundle Course A", 3000), ("Bundle Course B", 1000), ("Bundle Course C", 2000)] {
+            let res = test::TestRequest::post()
+                .set_json(CreateCourseWithoutAdminId {
+                    title: title.to_string(),
+                    image_url: None,
+                    price,
+                    category: None,
+                    description: None,
+                })
+                .append_header(("Authorization", admin_token.clone()))
+                .uri("/api/v1/admin/course")
+                .send_request(&app)
+                .await;
+
+            course_ids.push(test::read_body_json::<CourseResponse, _>(res).await.id);
+        }
+
+        // 2. Bundle them once they are on sale, a single course is not a bundle
+        let res = test::TestRequest::post()
+            .set_json(BundleInput {
+                title: "Drafts".to_string(),
+                price: 3000,
+                course_ids: course_ids.clone(),
+            })
+            .append_header(("Authorization", admin_token.clone()))
+            .uri("/api/v1/admin/bundle")
+            .send_request(&app)
+            .await;
+
+        let error_body: ErrorBody = test::read_body_json(res).await;
+        assert_eq!(error_body.code, "course_unavailable");
+
+        for course_id in &course_ids {
+            publish_course(&pool, course_id).await;
+        }
+
+        let res = test::TestRequest::post()
+            .set_json(BundleInput {
+                title: "Too Small".to_string(),
+                price: 500,
+                course_ids: vec![course_ids[0].clone(), course_ids[0].clone()],
+            })
+            .append_header(("Authorization", admin_token.clone()))
+            .uri("/api/v1/admin/bundle")
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 422);
+
+        let res = test::TestRequest::post()
+            .set_json(BundleInput {
+                title: "Everything".to_string(),
+                price: 3000,
+                course_ids: course_ids.clone(),
+            })
+            .append_header(("Authorization", admin_token.clone()))
+            .uri("/api/v1/admin/bundle")
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+
+        let bundle: Bundle = test::read_body_json(res).await;
+        assert_eq!(bundle.course_ids.len(), 3);
+
+        let res = test::TestRequest::get()
+            .uri("/api/v1/bundles")
+            .send_request(&app)
+            .await;
+
+        let bundles: Vec<Bundle> = test::read_body_json(res).await;
+        assert!(bundles.iter().any(|listed| listed.id == bundle.id));
+
+        // 3. A verified learner who already owns course B
+        let signup_res = test::TestRequest::post()
+            .set_json(CreateUser {
+                email: String::from("learner_bundle@test.com"),
+                name: String::from("Test User"),
+                password: String::from("userpass123")
+            })
+            .uri("/api/v1/user/signup")
+            .send_request(&app)
+            .await;
+
+        let user_uuid = Uuid::from_str(&test::read_body_json::<SignupResponse, _>(signup_res).await.id).unwrap();
+
+        sqlx::query("UPDATE user_table SET email_verified_at = now() WHERE id = $1")
+            .bind(user_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let owned_course_uuid = Uuid::from_str(&course_ids[1]).unwrap();
+        purchase_course(&mut pool.acquire().await.unwrap(), owned_course_uuid, user_uuid, None, None).await.unwrap();
+
+        let signin_res = test::TestRequest::post()
+            .set_json(EmailAndPassword {
+                email: "learner_bundle@test.com".to_string(),
+                password: "userpass123".to_string(),
+            })
+            .uri("/api/v1/user/signin")
+            .send_request(&app)
+            .await;
+
+        let user_token = test::read_body_json::<SigninResponse, _>(signin_res).await.token;
+
+        // 4. Buy the bundle and confirm the payment
+        let purchase_uri = format!("/api/v1/bundles/purchase/{}", bundle.id);
+
+        let res = test::TestRequest::post()
+            .append_header(("Authorization", user_token.clone()))
+            .uri(&purchase_uri)
+            .send_request(&app)
+            .await;
+
+        let checkout: CheckoutResponse = test::read_body_json(res).await;
+        assert_eq!(checkout.amount, 3000);
+
+        let res = test::TestRequest::get()
+            .append_header(("Authorization", user_token.clone()))
+            .uri("/api/v1/user/orders")
+            .send_request(&app)
+            .await;
+
+        let orders: Vec<Order> = test::read_body_json(res).await;
+        assert_eq!(orders[0].bundle_id, Some(bundle.id.clone()));
+
+        let payload = serde_json::to_vec(&PaymentEvent{
+            provider_ref: orders[0].provider_ref.clone().unwrap(),
+            outcome: PaymentOutcome::Paid,
+        }).unwrap();
+
+        let res = test::TestRequest::post()
+            .append_header(("X-Signature", sign_webhook(&payload)))
+            .set_payload(payload)
+            .uri("/api/v1/payments/webhook")
+            .send_request(&app)
+            .await;
+
+        let webhook_body: MessageResponse = test::read_body_json(res).await;
+        assert_eq!(webhook_body.message, "Order fulfilled");
+
+        // only the two missing courses were granted, sharing the bundle price
+        let res = test::TestRequest::get()
+            .append_header(("Authorization", user_token.clone()))
+            .uri("/api/v1/user/purchases")
+            .send_request(&app)
+            .await;
+
+        let purchases: Vec<PurchaseWithProgress> = test::read_body_json(res).await;
+        assert_eq!(purchases.len(), 3);
+
+        let paid_for_a = purchases.iter().find(|purchase| purchase.course_id == course_ids[0]).unwrap().amount_paid;
+        let paid_for_c = purchases.iter().find(|purchase| purchase.course_id == course_ids[2]).unwrap().amount_paid;
+        assert_eq!(paid_for_a, Some(1800));
+        assert_eq!(paid_for_c, Some(1200));
+
+        let res = test::TestRequest::post()
+            .append_header(("Authorization", user_token.clone()))
+            .uri(&purchase_uri)
+            .send_request(&app)
+            .await;
+
+        let error_body: ErrorBody = test::read_body_json(res).await;
+        assert_eq!(error_body.error, "Already Purchased");
+
+        // 5. Archiving one of its courses takes the bundle off sale
+        sqlx::query("UPDATE course_table SET status = 'archived' WHERE id = $1")
+            .bind(Uuid::from_str(&course_ids[2]).unwrap())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let res = test::TestRequest::get()
+            .uri("/api/v1/bundles")
+            .send_request(&app)
+            .await;
+
+        let bundles: Vec<Bundle> = test::read_body_json(res).await;
+        assert!(!bundles.iter().any(|listed| listed.id == bundle.id));
+
+        let res = test::TestRequest::post()
+            .append_header(("Authorization", user_token))
+            .uri(&purchase_uri)
+            .send_request(&app)
+            .await;
+
+        let error_body: ErrorBody = test::read_body_json(res).await;
+        assert_eq!(error_body.code, "bundle_unavailable");
+
+        // Cleanup
+        sqlx::query("DELETE FROM purchases_table WHERE user_id = $1")
+            .bind(user_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM orders_table WHERE user_id = $1")
+            .bind(user_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM bundles_table WHERE id = $1")
+            .bind(Uuid::from_str(&bundle.id).unwrap())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        for course_id in &course_ids {
+            sqlx::query("DELETE FROM course_table WHERE id = $1")
+                .bind(Uuid::from_str(course_id).unwrap())
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        sqlx::query("DELETE FROM user_table WHERE id = $1")
+            .bind(user_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM admin_table WHERE email = $1")
+            .bind("admin_bundle@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
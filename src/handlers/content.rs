@@ -0,0 +1,778 @@
+use std::str::FromStr;
+
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse, Responder, ResponseError};
+use sqlx::{types::Uuid, Pool, Postgres};
+
+use crate::{errors::{ApiError, CustomError}, extractors::{principal::AuthAdmin, validated_json::ValidatedJson}, middlewares::auth::optional_claims, models::{course::{self, Course, CourseStatus}, lesson::{self, Lesson}, purchase::has_purchased, section::{self, Section}, session::{ADMIN_SESSION, USER_SESSION}}, schema::{admin::{CourseResponse, CourseStatusInput}, content::{CourseContentResponse, LessonInput, LessonResponse, ReorderRequest, SectionInput, SectionResponse}, MessageResponse}, GlobalState};
+
+fn parse_uuid(id:&str) -> Result<Uuid, ApiError>{
+    Uuid::from_str(id).map_err(|_e| ApiError::bad_request("invalid_id", "Invalid id"))
+}
+
+fn parse_uuids(ids:&[String]) -> Result<Vec<Uuid>, ApiError>{
+    ids.iter().map(|id| parse_uuid(id)).collect()
+}
+
+/// Resolves the course in the path and makes sure the signed in admin owns it.
+pub async fn owned_course(pool:&Pool<Postgres>, admin:&AuthAdmin, course_id:&str) -> Result<Course, HttpResponse>{
+
+    let course_uuid = parse_uuid(course_id).map_err(|e| e.error_response())?;
+
+    let existing_course = match course::get_course_by_id(pool, course_uuid).await {
+        Ok(Some(existing_course)) => existing_course,
+        Ok(None) => return Err(ApiError::not_found("course_not_found", "Course not found").error_response()),
+        Err(e) => return Err(ApiError::from(e).error_response()),
+    };
+
+    if existing_course.admin_id != admin.id{
+        return Err(ApiError::forbidden("not_owner", "Unauthorized").error_response());
+    }
+
+    if existing_course.deleted_at.is_some(){
+        return Err(ApiError::not_found("course_not_found", "Course not found").error_response());
+    }
+
+    Ok(existing_course)
+}
+
+/// Resolves a section of an owned course.
+async fn owned_section(pool:&Pool<Postgres>, admin:&AuthAdmin, course_id:&str, section_id:&str) -> Result<Section, HttpResponse>{
+
+    let existing_course = owned_course(pool, admin, course_id).await?;
+    let section_uuid = parse_uuid(section_id).map_err(|e| e.error_response())?;
+    let course_uuid = parse_uuid(&existing_course.id).map_err(|e| e.error_response())?;
+
+    let existing_section = section::get_section(pool, section_uuid, course_uuid).await
+    .map_err(|e| ApiError::from(e).error_response())?;
+
+    existing_section.ok_or_else(|| ApiError::not_found("section_not_found", "Section not found").error_response())
+}
+
+fn lesson_response(lesson:Lesson, full_access:bool) -> LessonResponse{
+
+    // previews are open to everyone, the rest only to owners of the course
+    let locked = !full_access && !lesson.is_preview;
+
+    LessonResponse{
+        id: lesson.id,
+        section_id: lesson.section_id.to_string(),
+        title: lesson.title,
+        body: if locked { None } else { lesson.body },
+        video_url: if locked { None } else { lesson.video_url },
+        duration_seconds: lesson.duration_seconds,
+        is_preview: lesson.is_preview,
+        position: lesson.position,
+        locked,
+    }
+}
+
+fn section_response(section:Section) -> SectionResponse{
+    SectionResponse{
+        id: section.id,
+        course_id: section.course_id.to_string(),
+        title: section.title,
+        position: section.position,
+        lessons: Vec::new(),
+    }
+}
+
+/// Nests the lessons under their sections, both are expected in display order.
+fn build_sections(sections:Vec<Section>, lessons:Vec<Lesson>, full_access:bool) -> Vec<SectionResponse>{
+
+    let mut parsed_sections = sections.into_iter().map(section_response).collect::<Vec<SectionResponse>>();
+
+    for lesson in lessons {
+        let section_id = lesson.section_id.to_string();
+
+        if let Some(section) = parsed_sections.iter_mut().find(|section| section.id == section_id) {
+            section.lessons.push(lesson_response(lesson, full_access));
+        }
+    }
+
+    parsed_sections
+}
+
+async fn course_sections(pool:&Pool<Postgres>, course_uuid:Uuid, full_access:bool) -> Result<Vec<SectionResponse>, CustomError>{
+
+    let sections = section::get_course_sections(pool, course_uuid).await?;
+    let lessons = lesson::get_course_lessons(pool, course_uuid).await?;
+
+    Ok(build_sections(sections, lessons, full_access))
+}
+
+#[post("/{course_id}/sections")]
+async fn create_section_handler(data:web::Data<GlobalState>, body:ValidatedJson<SectionInput>, admin:AuthAdmin, path:web::Path<String>) -> impl Responder {
+
+    let pool = &data.pool;
+
+    let existing_course = match owned_course(pool, &admin, &path.into_inner()).await {
+        Ok(existing_course) => existing_course,
+        Err(res) => return res,
+    };
+
+    let course_uuid = match parse_uuid(&existing_course.id) {
+        Ok(course_uuid) => course_uuid,
+        Err(e) => return e.error_response(),
+    };
+
+    match section::create_section(pool, course_uuid, &body.title).await {
+        Ok(res) => HttpResponse::Ok().json(section_response(res)),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[get("/{course_id}/sections")]
+async fn get_sections_handler(data:web::Data<GlobalState>, admin:AuthAdmin, path:web::Path<String>) -> impl Responder {
+
+    let pool = &data.pool;
+
+    let existing_course = match owned_course(pool, &admin, &path.into_inner()).await {
+        Ok(existing_course) => existing_course,
+        Err(res) => return res,
+    };
+
+    let course_uuid = match parse_uuid(&existing_course.id) {
+        Ok(course_uuid) => course_uuid,
+        Err(e) => return e.error_response(),
+    };
+
+    match course_sections(pool, course_uuid, true).await {
+        Ok(sections) => HttpResponse::Ok().json(sections),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+// register before update_section_handler, else "order" is matched as a section id
+#[put("/{course_id}/sections/order")]
+async fn reorder_sections_handler(data:web::Data<GlobalState>, body:ValidatedJson<ReorderRequest>, admin:AuthAdmin, path:web::Path<String>) -> impl Responder {
+
+    let pool = &data.pool;
+
+    let existing_course = match owned_course(pool, &admin, &path.into_inner()).await {
+        Ok(existing_course) => existing_course,
+        Err(res) => return res,
+    };
+
+    let course_uuid = match parse_uuid(&existing_course.id) {
+        Ok(course_uuid) => course_uuid,
+        Err(e) => return e.error_response(),
+    };
+
+    let ids = match parse_uuids(&body.ids) {
+        Ok(ids) => ids,
+        Err(e) => return e.error_response(),
+    };
+
+    let reordered = section::reorder_sections(pool, course_uuid, &ids).await;
+
+    if let Err(e) = reordered{
+        return ApiError::from(e).error_response();
+    }
+
+    if !reordered.unwrap(){
+        return ApiError::validation("ids", "ids must list every section of the course exactly once").error_response();
+    }
+
+    match course_sections(pool, course_uuid, true).await {
+        Ok(sections) => HttpResponse::Ok().json(sections),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[put("/{course_id}/sections/{section_id}")]
+async fn update_section_handler(data:web::Data<GlobalState>, body:ValidatedJson<SectionInput>, admin:AuthAdmin, path:web::Path<(String, String)>) -> impl Responder {
+
+    let pool = &data.pool;
+    let (course_id, section_id) = path.into_inner();
+
+    let existing_section = match owned_section(pool, &admin, &course_id, &section_id).await {
+        Ok(existing_section) => existing_section,
+        Err(res) => return res,
+    };
+
+    let section_uuid = match parse_uuid(&existing_section.id) {
+        Ok(section_uuid) => section_uuid,
+        Err(e) => return e.error_response(),
+    };
+
+    match section::update_section(pool, section_uuid, &body.title).await {
+        Ok(res) => HttpResponse::Ok().json(section_response(res)),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[delete("/{course_id}/sections/{section_id}")]
+async fn delete_section_handler(data:web::Data<GlobalState>, admin:AuthAdmin, path:web::Path<(String, String)>) -> impl Responder {
+
+    let pool = &data.pool;
+    let (course_id, section_id) = path.into_inner();
+
+    let existing_section = match owned_section(pool, &admin, &course_id, &section_id).await {
+        Ok(existing_section) => existing_section,
+        Err(res) => return res,
+    };
+
+    let section_uuid = match parse_uuid(&existing_section.id) {
+        Ok(section_uuid) => section_uuid,
+        Err(e) => return e.error_response(),
+    };
+
+    // lessons of the section are removed by the cascade
+    match section::delete_section(pool, section_uuid).await {
+        Ok(()) => HttpResponse::Ok().json(MessageResponse{message:"Section deleted".to_string()}),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[post("/{course_id}/sections/{section_id}/lessons")]
+async fn create_lesson_handler(data:web::Data<GlobalState>, body:ValidatedJson<LessonInput>, admin:AuthAdmin, path:web::Path<(String, String)>) -> impl Responder {
+
+    let pool = &data.pool;
+    let (course_id, section_id) = path.into_inner();
+
+    let existing_section = match owned_section(pool, &admin, &course_id, &section_id).await {
+        Ok(existing_section) => existing_section,
+        Err(res) => return res,
+    };
+
+    let section_uuid = match parse_uuid(&existing_section.id) {
+        Ok(section_uuid) => section_uuid,
+        Err(e) => return e.error_response(),
+    };
+
+    match lesson::create_lesson(pool, section_uuid, &body).await {
+        Ok(res) => HttpResponse::Ok().json(lesson_response(res, true)),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+// register before update_lesson_handler, else "order" is matched as a lesson id
+#[put("/{course_id}/sections/{section_id}/lessons/order")]
+async fn reorder_lessons_handler(data:web::Data<GlobalState>, body:ValidatedJson<ReorderRequest>, admin:AuthAdmin, path:web::Path<(String, String)>) -> impl Responder {
+
+    let pool = &data.pool;
+    let (course_id, section_id) = path.into_inner();
+
+    let existing_section = match owned_section(pool, &admin, &course_id, &section_id).await {
+        Ok(existing_section) => existing_section,
+        Err(res) => return res,
+    };
+
+    let section_uuid = match parse_uuid(&existing_section.id) {
+        Ok(section_uuid) => section_uuid,
+        Err(e) => return e.error_response(),
+    };
+
+    let ids = match parse_uuids(&body.ids) {
+        Ok(ids) => ids,
+        Err(e) => return e.error_response(),
+    };
+
+    let reordered = lesson::reorder_lessons(pool, section_uuid, &ids).await;
+
+    if let Err(e) = reordered{
+        return ApiError::from(e).error_response();
+    }
+
+    if !reordered.unwrap(){
+        return ApiError::validation("ids", "ids must list every lesson of the section exactly once").error_response();
+    }
+
+    match course_sections(pool, existing_section.course_id, true).await {
+        Ok(sections) => HttpResponse::Ok().json(sections),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[put("/{course_id}/sections/{section_id}/lessons/{lesson_id}")]
+async fn update_lesson_handler(data:web::Data<GlobalState>, body:ValidatedJson<LessonInput>, admin:AuthAdmin, path:web::Path<(String, String, String)>) -> impl Responder {
+
+    let pool = &data.pool;
+    let (course_id, section_id, lesson_id) = path.into_inner();
+
+    let existing_section = match owned_section(pool, &admin, &course_id, &section_id).await {
+        Ok(existing_section) => existing_section,
+        Err(res) => return res,
+    };
+
+    let (section_uuid, lesson_uuid) = match (parse_uuid(&existing_section.id), parse_uuid(&lesson_id)) {
+        (Ok(section_uuid), Ok(lesson_uuid)) => (section_uuid, lesson_uuid),
+        (Err(e), _) | (_, Err(e)) => return e.error_response(),
+    };
+
+    match lesson::get_lesson(pool, lesson_uuid, section_uuid).await {
+        Ok(Some(_)) => {},
+        Ok(None) => return ApiError::not_found("lesson_not_found", "Lesson not found").error_response(),
+        Err(e) => return ApiError::from(e).error_response(),
+    }
+
+    match lesson::update_lesson(pool, lesson_uuid, &body).await {
+        Ok(res) => HttpResponse::Ok().json(lesson_response(res, true)),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[delete("/{course_id}/sections/{section_id}/lessons/{lesson_id}")]
+async fn delete_lesson_handler(data:web::Data<GlobalState>, admin:AuthAdmin, path:web::Path<(String, String, String)>) -> impl Responder {
+
+    let pool = &data.pool;
+    let (course_id, section_id, lesson_id) = path.into_inner();
+
+    let existing_section = match owned_section(pool, &admin, &course_id, &section_id).await {
+        Ok(existing_section) => existing_section,
+        Err(res) => return res,
+    };
+
+    let (section_uuid, lesson_uuid) = match (parse_uuid(&existing_section.id), parse_uuid(&lesson_id)) {
+        (Ok(section_uuid), Ok(lesson_uuid)) => (section_uuid, lesson_uuid),
+        (Err(e), _) | (_, Err(e)) => return e.error_response(),
+    };
+
+    match lesson::get_lesson(pool, lesson_uuid, section_uuid).await {
+        Ok(Some(_)) => {},
+        Ok(None) => return ApiError::not_found("lesson_not_found", "Lesson not found").error_response(),
+        Err(e) => return ApiError::from(e).error_response(),
+    }
+
+    match lesson::delete_lesson(pool, lesson_uuid).await {
+        Ok(()) => HttpResponse::Ok().json(MessageResponse{message:"Lesson deleted".to_string()}),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+/// Moves an owned course through draft, published, unlisted and archived.
+/// Going live needs at least one lesson, prices can't be negative to begin with.
+#[put("/{course_id}/status")]
+async fn update_course_status_handler(data:web::Data<GlobalState>, admin:AuthAdmin, path:web::Path<String>, body:ValidatedJson<CourseStatusInput>) -> impl Responder {
+
+    let pool = &data.pool;
+
+    let existing_course = match owned_course(pool, &admin, &path.into_inner()).await {
+        Ok(existing_course) => existing_course,
+        Err(res) => return res,
+    };
+
+    let Some(status) = CourseStatus::parse(&body.status) else {
+        return ApiError::validation("status", "Status must be one of draft, published, unlisted, archived").error_response();
+    };
+
+    let current = CourseStatus::parse(&existing_course.status).unwrap_or(CourseStatus::Draft);
+
+    if !status.allowed_from().contains(&current){
+        return ApiError::conflict("invalid_status_transition", format!("Cannot move a {} course to {}", current.as_str(), status.as_str())).error_response();
+    }
+
+    let course_uuid = match parse_uuid(&existing_course.id) {
+        Ok(course_uuid) => course_uuid,
+        Err(e) => return e.error_response(),
+    };
+
+    if status.is_live(){
+        match course::count_course_lessons(pool, course_uuid).await {
+            Ok(0) => return ApiError::bad_request("not_publishable", "Add at least one lesson before publishing").error_response(),
+            Ok(_) => {},
+            Err(e) => return ApiError::from(e).error_response(),
+        }
+    }
+
+    match course::set_course_status(pool, course_uuid, status).await {
+        Ok(Some(res)) => HttpResponse::Ok().json(CourseResponse::from(res)),
+        Ok(None) => ApiError::conflict("status_changed", "Course status changed, try again").error_response(),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+/// Public curriculum of a course. Buyers and the owning admin get every lesson,
+/// everyone else only gets the preview lessons in full.
+#[get("/{course_id}/content")]
+async fn get_course_content_handler(data:web::Data<GlobalState>, req:HttpRequest, path:web::Path<String>) -> impl Responder {
+
+    let pool = &data.pool;
+
+    let course_uuid = match parse_uuid(&path.into_inner()) {
+        Ok(course_uuid) => course_uuid,
+        Err(e) => return e.error_response(),
+    };
+
+    let existing_course = match course::get_course_by_id(pool, course_uuid).await {
+        Ok(Some(existing_course)) => existing_course,
+        Ok(None) => return ApiError::not_found("course_not_found", "Course not found").error_response(),
+        Err(e) => return ApiError::from(e).error_response(),
+    };
+
+    let mut has_access = false;
+
+    if let Some(claims) = optional_claims(&req, &data).await {
+        if claims.kind == USER_SESSION {
+            if let Ok(user_uuid) = Uuid::from_str(&claims.uid) {
+                has_access = has_purchased(pool, user_uuid, course_uuid).await.unwrap_or(false);
+            }
+        } else if claims.kind == ADMIN_SESSION {
+            has_access = claims.uid == existing_course.admin_id.to_string();
+        }
+    }
+
+    // drafts stay with their admin and archived courses with their buyers
+    let is_live = CourseStatus::parse(&existing_course.status).is_some_and(|status| status.is_live());
+
+    if !is_live && !has_access{
+        return ApiError::not_found("course_not_found", "Course not found").error_response();
+    }
+
+    match course_sections(pool, course_uuid, has_access).await {
+        Ok(sections) => HttpResponse::Ok().json(CourseContentResponse{course_id: existing_course.id, has_access, sections}),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{errors::ErrorBody, models::purchase::purchase_course, schema::{admin::{CreateAdmin, CreateCourseWithoutAdminId}, user::CreateUser, EmailAndPassword, SigninResponse, SignupResponse}, test_init_app::init};
+    use actix_web::test;
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_sections_lessons_and_access() {
+        let (app, pool) = init().await;
+
+        let admin = CreateAdmin {
+            email: String::from("admin_content@test.com"),
+            name: String::from("Test Admin"),
+            password: String::from("adminpass123")
+        };
+
+        let _ = test::TestRequest::post()
+            .set_json(admin)
+            .uri("/api/v1/admin/signup")
+            .send_request(&app)
+            .await;
+
+        let signin_res = test::TestRequest::post()
+            .set_json(EmailAndPassword {
+                email: "admin_content@test.com".to_string(),
+                password: "adminpass123".to_string(),
+            })
+            .uri("/api/v1/admin/signin")
+            .send_request(&app)
+            .await;
+
+        let admin_token = test::read_body_json::<SigninResponse, _>(signin_res).await.token;
+
+        let create_course_res = test::TestRequest::post()
+            .set_json(CreateCourseWithoutAdminId {
+                title: "Content Course".to_string(),
+                image_url: None,
+                price: 1500,
+                category: None,
+                description: None,
+            })
+            .append_header(("Authorization", admin_token.clone()))
+            .uri("/api/v1/admin/course")
+            .send_request(&app)
+            .await;
+
+        let course_res: CourseResponse = test::read_body_json(create_course_res).await;
+        let sections_uri = format!("/api/v1/admin/course/{}/sections", course_res.id);
+
+        // A fresh course is a draft, hidden from the public and not publishable while empty
+        assert_eq!(course_res.status, "draft");
+
+        let res = test::TestRequest::get()
+            .uri(&format!("/api/v1/courses/{}/content", course_res.id))
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 404);
+
+        let res = test::TestRequest::put()
+            .set_json(CourseStatusInput { status: "published".to_string() })
+            .append_header(("Authorization", admin_token.clone()))
+            .uri(&format!("/api/v1/admin/course/{}/status", course_res.id))
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 400);
+
+        // 1. Two sections, the second one gets created last
+        let mut section_ids = Vec::new();
+
+        for title in ["Basics", "Advanced"] {
+            let res = test::TestRequest::post()
+                .set_json(SectionInput { title: title.to_string() })
+                .append_header(("Authorization", admin_token.clone()))
+                .uri(&sections_uri)
+                .send_request(&app)
+                .await;
+
+            assert!(res.status().is_success());
+
+            let section: SectionResponse = test::read_body_json(res).await;
+            section_ids.push(section.id);
+        }
+
+        // 2. A preview lesson and a paid one in the first section
+        for (title, is_preview) in [("Intro", true), ("Deep dive", false)] {
+            let res = test::TestRequest::post()
+                .set_json(LessonInput {
+                    title: title.to_string(),
+                    body: Some(format!("# {}", title)),
+                    video_url: Some("https://test.com/video.mp4".to_string()),
+                    duration_seconds: 300,
+                    is_preview,
+                })
+                .append_header(("Authorization", admin_token.clone()))
+                .uri(&format!("{}/{}/lessons", sections_uri, section_ids[0]))
+                .send_request(&app)
+                .await;
+
+            assert!(res.status().is_success());
+        }
+
+        let res = test::TestRequest::post()
+            .set_json(LessonInput {
+                title: "x".repeat(256),
+                body: None,
+                video_url: Some("not a url".to_string()),
+                duration_seconds: -1,
+                is_preview: false,
+            })
+            .append_header(("Authorization", admin_token.clone()))
+            .uri(&format!("{}/{}/lessons", sections_uri, section_ids[0]))
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 422);
+
+        let res_body: ErrorBody = test::read_body_json(res).await;
+        let fields: Vec<&str> = res_body.details.iter().map(|detail| detail.field.as_str()).collect();
+        assert_eq!(fields, ["duration_seconds", "title", "video_url"]);
+
+        // 3. Publishing needs lessons, which the course has now, and a live course can't go back to draft
+        let status_uri = format!("/api/v1/admin/course/{}/status", course_res.id);
+
+        let res = test::TestRequest::put()
+            .set_json(CourseStatusInput { status: "published".to_string() })
+            .append_header(("Authorization", admin_token.clone()))
+            .uri(&status_uri)
+            .send_request(&app)
+            .await;
+
+        let published: CourseResponse = test::read_body_json(res).await;
+        assert_eq!(published.status, "published");
+
+        let res = test::TestRequest::put()
+            .set_json(CourseStatusInput { status: "draft".to_string() })
+            .append_header(("Authorization", admin_token.clone()))
+            .uri(&status_uri)
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 409);
+
+        // Move "Advanced" before "Basics"
+        let res = test::TestRequest::put()
+            .set_json(ReorderRequest { ids: vec![section_ids[1].clone(), section_ids[0].clone()] })
+            .append_header(("Authorization", admin_token.clone()))
+            .uri(&format!("{}/order", sections_uri))
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+
+        let sections: Vec<SectionResponse> = test::read_body_json(res).await;
+        assert_eq!(sections[0].title, "Advanced");
+        assert_eq!(sections[1].title, "Basics");
+        assert_eq!(sections[1].lessons.len(), 2);
+
+        // a partial order is rejected
+        let res = test::TestRequest::put()
+            .set_json(ReorderRequest { ids: vec![section_ids[0].clone()] })
+            .append_header(("Authorization", admin_token.clone()))
+            .uri(&format!("{}/order", sections_uri))
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 422);
+
+        // 4. Anonymous callers only see the preview lesson in full
+        let content_uri = format!("/api/v1/courses/{}/content", course_res.id);
+
+        let res = test::TestRequest::get()
+            .uri(&content_uri)
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+
+        let content: CourseContentResponse = test::read_body_json(res).await;
+        assert!(!content.has_access);
+
+        let lessons = &content.sections[1].lessons;
+        assert_eq!(lessons[0].title, "Intro");
+        assert!(!lessons[0].locked);
+        assert_eq!(lessons[0].body.as_deref(), Some("# Intro"));
+        assert!(lessons[1].locked);
+        assert!(lessons[1].body.is_none());
+        assert!(lessons[1].video_url.is_none());
+
+        // 5. A buyer sees everything
+        let signup_res = test::TestRequest::post()
+            .set_json(CreateUser {
+                email: String::from("learner_content@test.com"),
+                name: String::from("Test User"),
+                password: String::from("userpass123")
+            })
+            .uri("/api/v1/user/signup")
+            .send_request(&app)
+            .await;
+
+        let user_id = test::read_body_json::<SignupResponse, _>(signup_res).await.id;
+        let user_uuid = Uuid::from_str(&user_id).unwrap();
+        let course_uuid = Uuid::from_str(&course_res.id).unwrap();
+
+        let signin_res = test::TestRequest::post()
+            .set_json(EmailAndPassword {
+                email: "learner_content@test.com".to_string(),
+                password: "userpass123".to_string(),
+            })
+            .uri("/api/v1/user/signin")
+            .send_request(&app)
+            .await;
+
+        let user_token = test::read_body_json::<SigninResponse, _>(signin_res).await.token;
+
+        purchase_course(&mut pool.acquire().await.unwrap(), course_uuid, user_uuid, None, None).await.unwrap();
+
+        let res = test::TestRequest::get()
+            .append_header(("Authorization", user_token.clone()))
+            .uri(&content_uri)
+            .send_request(&app)
+            .await;
+
+        let content: CourseContentResponse = test::read_body_json(res).await;
+        assert!(content.has_access);
+        assert!(content.sections[1].lessons.iter().all(|lesson| !lesson.locked));
+        assert_eq!(content.sections[1].lessons[1].body.as_deref(), Some("# Deep dive"));
+
+        // 6. Archiving hides the course from everyone but its buyers
+        let res = test::TestRequest::put()
+            .set_json(CourseStatusInput { status: "archived".to_string() })
+            .append_header(("Authorization", admin_token.clone()))
+            .uri(&status_uri)
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+
+        let res = test::TestRequest::get()
+            .uri(&content_uri)
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 404);
+
+        let res = test::TestRequest::get()
+            .append_header(("Authorization", user_token))
+            .uri(&content_uri)
+            .send_request(&app)
+            .await;
+
+        let content: CourseContentResponse = test::read_body_json(res).await;
+        assert!(content.has_access);
+
+        // Cleanup
+        sqlx::query("DELETE FROM purchases_table WHERE course_id = $1")
+            .bind(course_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM course_table WHERE id = $1")
+            .bind(course_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM user_table WHERE id = $1")
+            .bind(user_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM admin_table WHERE email = $1")
+            .bind("admin_content@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_sections_require_course_owner() {
+        let (app, pool) = init().await;
+
+        let mut tokens = Vec::new();
+
+        for email in ["owner_content@test.com", "intruder_content@test.com"] {
+            let _ = test::TestRequest::post()
+                .set_json(CreateAdmin {
+                    email: email.to_string(),
+                    name: String::from("Test Admin"),
+                    password: String::from("adminpass123")
+                })
+                .uri("/api/v1/admin/signup")
+                .send_request(&app)
+                .await;
+
+            let signin_res = test::TestRequest::post()
+                .set_json(EmailAndPassword {
+                    email: email.to_string(),
+                    password: "adminpass123".to_string(),
+                })
+                .uri("/api/v1/admin/signin")
+                .send_request(&app)
+                .await;
+
+            tokens.push(test::read_body_json::<SigninResponse, _>(signin_res).await.token);
+        }
+
+        let create_course_res = test::TestRequest::post()
+            .set_json(CreateCourseWithoutAdminId {
+                title: "Owned Course".to_string(),
+                image_url: None,
+                price: 1500,
+                category: None,
+                description: None,
+            })
+            .append_header(("Authorization", tokens[0].clone()))
+            .uri("/api/v1/admin/course")
+            .send_request(&app)
+            .await;
+
+        let course_res: CourseResponse = test::read_body_json(create_course_res).await;
+
+        let res = test::TestRequest::post()
+            .set_json(SectionInput { title: "Hijacked".to_string() })
+            .append_header(("Authorization", tokens[1].clone()))
+            .uri(&format!("/api/v1/admin/course/{}/sections", course_res.id))
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 403);
+
+        let res_body: ErrorBody = test::read_body_json(res).await;
+        assert_eq!(res_body.error, "Unauthorized");
+
+        // Cleanup
+        sqlx::query("DELETE FROM course_table WHERE id = $1")
+            .bind(Uuid::from_str(&course_res.id).unwrap())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM admin_table WHERE email = ANY($1)")
+            .bind(vec!["owner_content@test.com", "intruder_content@test.com"])
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
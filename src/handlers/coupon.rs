@@ -0,0 +1,328 @@
+use std::str::FromStr;
+
+use actix_web::{get, post, web, HttpResponse, Responder, ResponseError};
+use sqlx::types::Uuid;
+
+use crate::{errors::ApiError, extractors::{principal::AuthAdmin, validated_json::ValidatedJson}, models::{coupon::{self, NewCoupon}, course, role::{permissions, roles_have_permission}}, schema::admin::CouponInput, GlobalState};
+
+// instructors discount their own courses, only platform admins may create site-wide coupons
+#[post("")]
+async fn create_coupon_handler(data:web::Data<GlobalState>, admin:AuthAdmin, body:ValidatedJson<CouponInput>) -> impl Responder{
+
+    let pool = &data.pool;
+
+    // codes are matched case-insensitively, so they are stored upper-cased
+    let code = body.code.trim().to_uppercase();
+
+    let course_uuid = match &body.course_id {
+        Some(course_id) => {
+
+            let course_uuid = Uuid::from_str(course_id);
+
+            if course_uuid.is_err(){
+                return ApiError::bad_request("invalid_id", "Invalid id").error_response();
+            }
+
+            let course_uuid = course_uuid.unwrap();
+
+            let existing_course = match course::get_course_by_id(pool, course_uuid).await {
+                Ok(Some(existing_course)) => existing_course,
+                Ok(None) => return ApiError::not_found("course_not_found", "Course not found").error_response(),
+                Err(e) => return ApiError::from(e).error_response(),
+            };
+
+            if existing_course.admin_id != admin.id{
+                return ApiError::forbidden("not_owner", "Unauthorized").error_response();
+            }
+
+            Some(course_uuid)
+        },
+        None => {
+
+            let allowed = roles_have_permission(pool, &admin.roles, permissions::COUPON_SITEWIDE).await;
+
+            if let Err(e) = allowed{
+                return ApiError::from(e).error_response();
+            }
+
+            if !allowed.unwrap(){
+                return ApiError::forbidden("missing_permission", format!("Missing permission: {}", permissions::COUPON_SITEWIDE)).error_response();
+            }
+
+            None
+        }
+    };
+
+    let coupon_res = coupon::create_coupon(pool, NewCoupon{
+        code: &code,
+        kind: &body.kind,
+        value: body.value,
+        course_id: course_uuid,
+        expires_at: body.expires_at,
+        max_redemptions: body.max_redemptions,
+        created_by: admin.id,
+    }).await;
+
+    match coupon_res {
+        Ok(Some(res)) => HttpResponse::Ok().json(res),
+        Ok(None) => ApiError::conflict("coupon_code_taken", "Coupon code already exists").error_response(),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[get("")]
+async fn get_coupons_handler(data:web::Data<GlobalState>, admin:AuthAdmin) -> impl Responder{
+
+    let pool = &data.pool;
+
+    match coupon::get_admin_coupons(pool, admin.id).await {
+        Ok(coupons) => HttpResponse::Ok().json(coupons),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{errors::ErrorBody, models::{coupon::{Coupon, FIXED_COUPON, PERCENT_COUPON}, progress::PurchaseWithProgress}, schema::{admin::{CourseResponse, CreateAdmin, CreateCourseWithoutAdminId}, user::CreateUser, CheckoutResponse, EmailAndPassword, SigninResponse}, test_init_app::{init, publish_course}};
+    use actix_web::test;
+    use super::*;
+
+    fn coupon_input(code:&str, kind:&str, value:i32, course_id:Option<String>) -> CouponInput {
+        CouponInput {
+            code: code.to_string(),
+            kind: kind.to_string(),
+            value,
+            course_id,
+            expires_at: None,
+            max_redemptions: Some(1),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_coupons_at_purchase() {
+        let (app, pool) = init().await;
+
+        // 1. An instructor with a course
+        let _ = test::TestRequest::post()
+            .set_json(CreateAdmin {
+                email: String::from("admin_coupon@test.com"),
+                name: String::from("Test Admin"),
+                password: String::from("adminpass123")
+            })
+            .uri("/api/v1/admin/signup")
+            .send_request(&app)
+            .await;
+
+        let signin_res = test::TestRequest::post()
+            .set_json(EmailAndPassword {
+                email: "admin_coupon@test.com".to_string(),
+                password: "adminpass123".to_string(),
+            })
+            .uri("/api/v1/admin/signin")
+            .send_request(&app)
+            .await;
+
+        let admin_token = test::read_body_json::<SigninResponse, _>(signin_res).await.token;
+
+        let create_course_res = test::TestRequest::post()
+            .set_json(CreateCourseWithoutAdminId {
+                title: "Coupon Course".to_string(),
+                image_url: None,
+                price: 4000,
+                category: None,
+                description: None,
+            })
+            .append_header(("Authorization", admin_token.clone()))
+            .uri("/api/v1/admin/course")
+            .send_request(&app)
+            .await;
+
+        let course_res: CourseResponse = test::read_body_json(create_course_res).await;
+        publish_course(&pool, &course_res.id).await;
+
+        // 2. Coupons for the course, site-wide ones need a platform admin
+        let res = test::TestRequest::post()
+            .set_json(coupon_input("launch25", PERCENT_COUPON, 25, Some(course_res.id.clone())))
+            .append_header(("Authorization", admin_token.clone()))
+            .uri("/api/v1/admin/coupons")
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+
+        let launch_coupon: Coupon = test::read_body_json(res).await;
+        assert_eq!(launch_coupon.code, "LAUNCH25");
+
+        let res = test::TestRequest::post()
+            .set_json(coupon_input("launch25", FIXED_COUPON, 500, Some(course_res.id.clone())))
+            .append_header(("Authorization", admin_token.clone()))
+            .uri("/api/v1/admin/coupons")
+            .send_request(&app)
+            .await;
+
+        let error_body: ErrorBody = test::read_body_json(res).await;
+        assert_eq!(error_body.error, "Coupon code already exists");
+
+        let res = test::TestRequest::post()
+            .set_json(coupon_input("half_off_plus", PERCENT_COUPON, 150, Some(course_res.id.clone())))
+            .append_header(("Authorization", admin_token.clone()))
+            .uri("/api/v1/admin/coupons")
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 422);
+
+        let error_body: ErrorBody = test::read_body_json(res).await;
+        let fields: Vec<&str> = error_body.details.iter().map(|detail| detail.field.as_str()).collect();
+        assert_eq!(fields, ["body"]);
+
+        let res = test::TestRequest::post()
+            .set_json(coupon_input("everything10", PERCENT_COUPON, 10, None))
+            .append_header(("Authorization", admin_token.clone()))
+            .uri("/api/v1/admin/coupons")
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 403);
+
+        let res = test::TestRequest::post()
+            .set_json(coupon_input("freebie_coupon", FIXED_COUPON, 10000, Some(course_res.id.clone())))
+            .append_header(("Authorization", admin_token.clone()))
+            .uri("/api/v1/admin/coupons")
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+
+        // 3. A verified learner
+        let _ = test::TestRequest::post()
+            .set_json(CreateUser {
+                email: String::from("learner_coupon@test.com"),
+                name: String::from("Test User"),
+                password: String::from("userpass123")
+            })
+            .uri("/api/v1/user/signup")
+            .send_request(&app)
+            .await;
+
+        sqlx::query("UPDATE user_table SET email_verified_at = now() WHERE email = $1")
+            .bind("learner_coupon@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let signin_res = test::TestRequest::post()
+            .set_json(EmailAndPassword {
+                email: "learner_coupon@test.com".to_string(),
+                password: "userpass123".to_string(),
+            })
+            .uri("/api/v1/user/signin")
+            .send_request(&app)
+            .await;
+
+        let user_token = test::read_body_json::<SigninResponse, _>(signin_res).await.token;
+
+        let purchase_uri = format!("/api/v1/courses/purchase/{}", course_res.id);
+
+        // 4. The coupon is applied to the order and takes a redemption
+        let res = test::TestRequest::post()
+            .append_header(("Authorization", user_token.clone()))
+            .uri(&format!("{}?coupon=launch25", purchase_uri))
+            .send_request(&app)
+            .await;
+
+        let checkout: CheckoutResponse = test::read_body_json(res).await;
+        assert_eq!(checkout.amount, 3000);
+        assert_eq!(checkout.status, "pending");
+
+        let redemptions = sqlx::query_scalar::<_, i32>("SELECT redemptions FROM coupons_table WHERE code = $1")
+            .bind("LAUNCH25")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(redemptions, 1);
+
+        let res = test::TestRequest::post()
+            .append_header(("Authorization", user_token.clone()))
+            .uri(&format!("{}?coupon=nope", purchase_uri))
+            .send_request(&app)
+            .await;
+
+        let error_body: ErrorBody = test::read_body_json(res).await;
+        assert_eq!(error_body.error, "Invalid coupon");
+
+        // 5. Checking out without it drops the discounted order and frees the redemption
+        let res = test::TestRequest::post()
+            .append_header(("Authorization", user_token.clone()))
+            .uri(&purchase_uri)
+            .send_request(&app)
+            .await;
+
+        let full_price_checkout: CheckoutResponse = test::read_body_json(res).await;
+        assert_eq!(full_price_checkout.amount, 4000);
+        assert_ne!(full_price_checkout.order_id, checkout.order_id);
+
+        let redemptions = sqlx::query_scalar::<_, i32>("SELECT redemptions FROM coupons_table WHERE code = $1")
+            .bind("LAUNCH25")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(redemptions, 0);
+
+        // 6. A coupon covering the whole price grants the course straight away
+        let res = test::TestRequest::post()
+            .append_header(("Authorization", user_token.clone()))
+            .uri(&format!("{}?coupon=FREEBIE_COUPON", purchase_uri))
+            .send_request(&app)
+            .await;
+
+        let free_checkout: CheckoutResponse = test::read_body_json(res).await;
+        assert_eq!(free_checkout.status, "fulfilled");
+        assert!(free_checkout.checkout_url.is_none());
+
+        let res = test::TestRequest::get()
+            .append_header(("Authorization", user_token))
+            .uri("/api/v1/user/purchases")
+            .send_request(&app)
+            .await;
+
+        let purchases: Vec<PurchaseWithProgress> = test::read_body_json(res).await;
+        assert_eq!(purchases.len(), 1);
+        assert_eq!(purchases[0].amount_paid, Some(0));
+
+        // Cleanup
+        let course_uuid = Uuid::from_str(&course_res.id).unwrap();
+
+        sqlx::query("DELETE FROM purchases_table WHERE course_id = $1")
+            .bind(course_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM orders_table WHERE course_id = $1")
+            .bind(course_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM course_table WHERE id = $1")
+            .bind(course_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM user_table WHERE email = $1")
+            .bind("learner_coupon@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM admin_table WHERE email = $1")
+            .bind("admin_coupon@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
@@ -1,46 +1,31 @@
 use std::str::FromStr;
 
-use actix_web::{get, post, web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use actix_web::{get, post, put, web, HttpRequest, HttpResponse, Responder, ResponseError};
 use sqlx::types::{uuid, Uuid};
 
-use crate::{errors::CustomError,models::{course, purchase::{self, get_user_purchases}, user::get_user_id_by_email}, schema::{admin::CourseResponse, PurchaseResponse, StructWithEmail}, GlobalState};
+use crate::{errors::ApiError, extractors::{principal::AuthUser, validated_json::ValidatedJson}, handlers::order::{checkout_order, verified_buyer}, middlewares::auth::optional_claims, models::{coupon::get_coupon_by_code, course::{self, CatalogFilter, CatalogSort, CourseStatus}, order::OrderItem, purchase::{get_user_purchases, has_purchased}, rating::rate_course, session::USER_SESSION}, schema::{admin::CourseResponse, user::{CatalogQuery, CatalogResponse, CourseDetailResponse, PurchaseQuery, RatingInput, SearchQuery, SearchResponse, SearchResult}, MessageResponse}, GlobalState};
 
 #[post("/{course_id}")]
-async fn purchase_course_handler(data:web::Data<GlobalState>, path:web::Path<String>, req:HttpRequest) -> impl Responder {
+async fn purchase_course_handler(data:web::Data<GlobalState>, path:web::Path<String>, query:web::Query<PurchaseQuery>, user:AuthUser) -> impl Responder {
 
     let pool = &data.pool;
 
-    let user_email = req.extensions().get::<StructWithEmail>().cloned();
-
-    if user_email.is_none(){
-        return HttpResponse::Forbidden().json(CustomError{error:"email missing".to_string()});
-    }
-
-    let user_email = user_email.unwrap().email;
-
-    let user_exists = get_user_id_by_email(pool, &user_email).await;
-
-    if let Err(e) = user_exists{
-        return HttpResponse::Forbidden().json(e);
-    }
-
-    let user_uuid = Uuid::from_str(&user_exists.unwrap());
-
-    if user_uuid.is_err(){
-        return HttpResponse::InternalServerError().json(CustomError{error:"Internal Error".to_string()});
-    }
+    let user_uuid = match verified_buyer(pool, &user).await {
+        Ok(user_uuid) => user_uuid,
+        Err(res) => return res,
+    };
 
     let course_id = path.into_inner();
     let course_uuid = uuid::Uuid::from_str(&course_id);
 
     if course_uuid.is_err(){
-        return HttpResponse::InternalServerError().json(CustomError{error:"Internal Error".to_string()});
+        return ApiError::bad_request("invalid_id", "Invalid id").error_response();
     }
 
-    let existing_purchases = get_user_purchases(pool, user_uuid.clone().unwrap()).await;
+    let existing_purchases = get_user_purchases(pool, user_uuid).await;
 
     if let Err(e) = existing_purchases{
-        return HttpResponse::InternalServerError().json(e);
+        return ApiError::from(e).error_response();
     }
 
     let existing_purchases = existing_purchases.unwrap();
@@ -53,52 +38,210 @@ async fn purchase_course_handler(data:web::Data<GlobalState>, path:web::Path<Str
     });
 
     if existing_purchase.is_some(){
-        return HttpResponse::BadRequest().json(CustomError{error:"Already Purchased".to_string()});
+        return ApiError::conflict("already_purchased", "Already Purchased").error_response();
     }
 
-    let purchase_res = purchase::purchase_course(pool, course_uuid.unwrap(), user_uuid.unwrap()).await;
+    let course_uuid = course_uuid.unwrap();
 
-    match purchase_res {
-        Ok(res) => HttpResponse::Ok().json(PurchaseResponse{id:res.id, message:"Purchased Successfully".to_string()}),
-        Err(e) => HttpResponse::BadGateway().json(e),
+    let course = match course::get_course_by_id(pool, course_uuid).await {
+        Ok(Some(course)) => course,
+        Ok(None) => return ApiError::not_found("course_not_found", "Course not found").error_response(),
+        Err(e) => return ApiError::from(e).error_response(),
+    };
+
+    if !CourseStatus::parse(&course.status).is_some_and(|status| status.is_live()){
+        return ApiError::bad_request("course_unavailable", "Course is not available for purchase").error_response();
     }
 
-    
+    let mut amount = course.price;
+    let mut coupon_uuid = None;
+
+    if let Some(code) = &query.coupon {
+
+        let coupon = get_coupon_by_code(pool, &code.trim().to_uppercase()).await;
+
+        if let Err(e) = coupon{
+            return ApiError::from(e).error_response();
+        }
+
+        let coupon = coupon.unwrap();
+
+        if coupon.is_none(){
+            return ApiError::bad_request("invalid_coupon", "Invalid coupon").error_response();
+        }
+
+        let coupon = coupon.unwrap();
+
+        if let Err(e) = coupon.check(&course.id){
+            return ApiError::bad_request("invalid_coupon", e.error).error_response();
+        }
+
+        let parsed_coupon_uuid = Uuid::from_str(&coupon.id);
+
+        if parsed_coupon_uuid.is_err(){
+            return ApiError::internal("Internal Error").error_response();
+        }
+
+        amount = coupon.apply(course.price);
+        coupon_uuid = Some(parsed_coupon_uuid.unwrap());
+    }
+
+    checkout_order(&data, user_uuid, OrderItem::Course(course_uuid), amount, coupon_uuid).await
 }
 
+const DEFAULT_PER_PAGE: i64 = 20;
+const MAX_PER_PAGE: i64 = 100;
+
 #[get("")]
-pub async fn get_all_courses_handler(data:web::Data<GlobalState>) -> impl Responder {
+pub async fn get_all_courses_handler(data:web::Data<GlobalState>, query:web::Query<CatalogQuery>) -> impl Responder {
     let pool = &data.pool;
+    let query = query.into_inner();
 
-    let courses = course::get_all_courses(pool).await;
-
-    match courses {
-        Ok(courses) => {
-            let parsed_courses = courses.into_iter().map(|course|{
-                CourseResponse{
-                    id: course.id,
-                    admin_id: course.admin_id.to_string(),
-                    title: course.title,
-                    image_url: course.image_url,
-                    price: course.price,
-                }
-            }).collect::<Vec<CourseResponse>>();
-
-            HttpResponse::Ok().json(parsed_courses)
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+
+    let sort = match query.sort.as_deref() {
+        None => CatalogSort::Newest,
+        Some(sort) => match CatalogSort::parse(sort) {
+            Some(sort) => sort,
+            None => return ApiError::validation("sort", "Sort must be one of newest, price_asc, price_desc, popular").error_response(),
         },
-        Err(e) => HttpResponse::BadGateway().json(e),
+    };
+
+    let instructor = match query.instructor.as_deref().map(Uuid::from_str) {
+        None => None,
+        Some(Ok(instructor)) => Some(instructor),
+        Some(Err(_)) => return ApiError::validation("instructor", "Invalid instructor id").error_response(),
+    };
+
+    if let (Some(min_price), Some(max_price)) = (query.min_price, query.max_price) {
+        if min_price > max_price {
+            return ApiError::validation("min_price", "min_price can't be greater than max_price").error_response();
+        }
+    }
+
+    let filter = CatalogFilter{
+        min_price: query.min_price,
+        max_price: query.max_price,
+        instructor,
+        category: query.category,
+        free_only: query.free.unwrap_or(false),
+        sort,
+        limit: per_page,
+        offset: (page - 1).saturating_mul(per_page),
+    };
+
+    match course::get_catalog(pool, &filter).await {
+        Ok((courses, total)) => HttpResponse::Ok().json(CatalogResponse{
+            courses: courses.into_iter().map(CourseResponse::from).collect(),
+            page,
+            per_page,
+            total,
+            total_pages: (total + per_page - 1) / per_page,
+        }),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[get("/search")]
+pub async fn search_courses_handler(data:web::Data<GlobalState>, query:web::Query<SearchQuery>) -> impl Responder {
+    let pool = &data.pool;
+
+    let search = match course::search_query(&query.q) {
+        Some(search) => search,
+        None => return ApiError::validation("q", "Search query can't be empty").error_response(),
+    };
+
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+
+    match course::search_courses(pool, &search, per_page, (page - 1).saturating_mul(per_page)).await {
+        Ok((hits, total)) => HttpResponse::Ok().json(SearchResponse{
+            results: hits.into_iter().map(SearchResult::from).collect(),
+            page,
+            per_page,
+            total,
+            total_pages: (total + per_page - 1) / per_page,
+        }),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+/// Public course page. A signed in user also learns whether they already own the course.
+#[get("/{course_id}")]
+pub async fn get_course_detail_handler(data:web::Data<GlobalState>, req:HttpRequest, path:web::Path<String>) -> impl Responder {
+    let pool = &data.pool;
+
+    let course_uuid = match Uuid::from_str(&path.into_inner()) {
+        Ok(course_uuid) => course_uuid,
+        Err(_) => return ApiError::bad_request("invalid_id", "Invalid id").error_response(),
+    };
+
+    let detail = match course::get_course_detail(pool, course_uuid).await {
+        Ok(Some(detail)) => detail,
+        Ok(None) => return ApiError::not_found("course_not_found", "Course not found").error_response(),
+        Err(e) => return ApiError::from(e).error_response(),
+    };
+
+    let mut owned = false;
+
+    if let Some(claims) = optional_claims(&req, &data).await {
+        if claims.kind == USER_SESSION {
+            if let Ok(user_uuid) = Uuid::from_str(&claims.uid) {
+                owned = has_purchased(pool, user_uuid, course_uuid).await.unwrap_or(false);
+            }
+        }
+    }
+
+    let is_live = CourseStatus::parse(&detail.course.status).is_some_and(|status| status.is_live());
+
+    if !is_live && !owned{
+        return ApiError::not_found("course_not_found", "Course not found").error_response();
+    }
+
+    HttpResponse::Ok().json(CourseDetailResponse{
+        course: CourseResponse::from(detail.course),
+        instructor_name: detail.instructor_name,
+        lesson_count: detail.lesson_count,
+        total_duration_seconds: detail.total_duration_seconds,
+        purchase_count: detail.purchase_count,
+        average_rating: detail.average_rating,
+        rating_count: detail.rating_count,
+        owned,
+    })
+}
+
+/// Buyers rate a course from 1 to 5, rating again replaces the earlier rating.
+#[put("/{course_id}")]
+pub async fn rate_course_handler(data:web::Data<GlobalState>, user:AuthUser, path:web::Path<String>, rating:ValidatedJson<RatingInput>) -> impl Responder {
+    let pool = &data.pool;
+
+    let course_uuid = match Uuid::from_str(&path.into_inner()) {
+        Ok(course_uuid) => course_uuid,
+        Err(_) => return ApiError::bad_request("invalid_id", "Invalid id").error_response(),
+    };
+
+    match has_purchased(pool, user.id, course_uuid).await {
+        Ok(true) => {},
+        Ok(false) => return ApiError::forbidden("purchase_required", "Purchase the course first").error_response(),
+        Err(e) => return ApiError::from(e).error_response(),
+    }
+
+    match rate_course(pool, user.id, course_uuid, rating.rating).await {
+        Ok(()) => HttpResponse::Ok().json(MessageResponse{message:"Rating saved".to_string()}),
+        Err(e) => ApiError::from(e).error_response(),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{models::purchase::Purchase, schema::{admin::{CreateAdmin, CreateCourseWithoutAdminId}, user::CreateUser, EmailAndPassword, SigninResponse}, test_init_app::init};
+    use crate::{errors::{DbError, ErrorBody}, models::{order::Order, purchase::{purchase_course, Purchase}}, payments::{PaymentEvent, PaymentOutcome}, schema::{admin::{CourseStatusInput, CreateAdmin, CreateCourseWithoutAdminId}, content::{LessonInput, SectionInput, SectionResponse}, user::CreateUser, CheckoutResponse, EmailAndPassword, SigninResponse, SignupResponse}, test_init_app::{init, last_mail_to, publish_course, sign_webhook, token_from_mail}};
     use actix_web::test;
     use super::*;
 
     #[actix_web::test]
     async fn test_purchase_course() {
-        let (app, pool) = init(get_all_courses_handler).await;
+        let (app, pool) = init().await;
 
         // 1. Create an admin
         let admin = CreateAdmin {
@@ -133,6 +276,8 @@ mod tests {
             title: "Test Purchase Course".to_string(),
             image_url: Some("https://test.com/image.jpg".to_string()),
             price: 5000,
+            category: None,
+            description: None,
         };
 
         let create_course_res = test::TestRequest::post()
@@ -143,6 +288,7 @@ mod tests {
             .await;
 
         let course_res: CourseResponse = test::read_body_json(create_course_res).await;
+        publish_course(&pool, &course_res.id).await;
 
         // 3. Create a user
         let user = CreateUser {
@@ -176,7 +322,28 @@ mod tests {
         let uri = format!("/api/v1/courses/purchase/{}", course_res.id);
         println!("uri : {}", uri);
 
-        // 4. Purchase the course
+        // 4. Purchasing is blocked until the email is verified
+        let unverified_res = test::TestRequest::post()
+            .append_header(("Authorization", user_token.clone()))
+            .uri(&uri)
+            .send_request(&app)
+            .await;
+
+        assert_eq!(unverified_res.status().as_u16(), 403);
+
+        let error_body: ErrorBody = test::read_body_json(unverified_res).await;
+        assert_eq!(error_body.error, "Verify your email before purchasing");
+
+        let mail = last_mail_to("user_purchase@test.com").expect("verification mail not sent");
+
+        let verify_res = test::TestRequest::get()
+            .uri(&format!("/api/v1/user/verify?token={}", token_from_mail(&mail)))
+            .send_request(&app)
+            .await;
+
+        assert!(verify_res.status().is_success());
+
+        // 5. Buying opens a pending order, asking again hands back the same checkout
         let purchase_res = test::TestRequest::post()
             .append_header(("Authorization", user_token.clone()))
             .uri(&uri)
@@ -185,10 +352,73 @@ mod tests {
 
         assert!(purchase_res.status().is_success());
 
-        let purchase_body: PurchaseResponse = test::read_body_json(purchase_res).await;
-        assert_eq!(purchase_body.message, "Purchased Successfully");
+        let checkout: CheckoutResponse = test::read_body_json(purchase_res).await;
+        assert_eq!(checkout.status, "pending");
+        assert_eq!(checkout.amount, 5000);
+
+        let repeat_checkout_res = test::TestRequest::post()
+            .append_header(("Authorization", user_token.clone()))
+            .uri(&uri)
+            .send_request(&app)
+            .await;
+
+        let repeat_checkout: CheckoutResponse = test::read_body_json(repeat_checkout_res).await;
+        assert_eq!(repeat_checkout.order_id, checkout.order_id);
+
+        let orders_res = test::TestRequest::get()
+            .append_header(("Authorization", user_token.clone()))
+            .uri("/api/v1/user/orders")
+            .send_request(&app)
+            .await;
+
+        let orders: Vec<Order> = test::read_body_json(orders_res).await;
+        assert_eq!(orders.len(), 1);
+
+        let provider_ref = orders[0].provider_ref.clone().expect("checkout reference missing");
+
+        // Nothing is granted until the provider confirms the payment
+        let purchases_res = test::TestRequest::get()
+            .append_header(("Authorization", user_token.clone()))
+            .uri("/api/v1/user/purchases")
+            .send_request(&app)
+            .await;
 
-        // 5. Verify purchase exists in user's purchases
+        let purchases: Vec<Purchase> = test::read_body_json(purchases_res).await;
+        assert!(purchases.is_empty());
+
+        let payload = serde_json::to_vec(&PaymentEvent{provider_ref, outcome:PaymentOutcome::Paid}).unwrap();
+
+        let forged_res = test::TestRequest::post()
+            .append_header(("X-Signature", "deadbeef"))
+            .set_payload(payload.clone())
+            .uri("/api/v1/payments/webhook")
+            .send_request(&app)
+            .await;
+
+        assert_eq!(forged_res.status().as_u16(), 401);
+
+        let webhook_res = test::TestRequest::post()
+            .append_header(("X-Signature", sign_webhook(&payload)))
+            .set_payload(payload.clone())
+            .uri("/api/v1/payments/webhook")
+            .send_request(&app)
+            .await;
+
+        let webhook_body: MessageResponse = test::read_body_json(webhook_res).await;
+        assert_eq!(webhook_body.message, "Order fulfilled");
+
+        // a replayed webhook must not grant the course twice
+        let replay_res = test::TestRequest::post()
+            .append_header(("X-Signature", sign_webhook(&payload)))
+            .set_payload(payload)
+            .uri("/api/v1/payments/webhook")
+            .send_request(&app)
+            .await;
+
+        let replay_body: MessageResponse = test::read_body_json(replay_res).await;
+        assert_eq!(replay_body.message, "Order already fulfilled");
+
+        // 6. Verify purchase exists in user's purchases
         let user_purchases_res = test::TestRequest::get()
             .append_header(("Authorization", user_token.clone()))
             .uri("/api/v1/user/purchases")
@@ -199,14 +429,14 @@ mod tests {
         assert_eq!(purchases.len(), 1);
         assert_eq!(purchases[0].course_id.to_string(), course_res.id);
 
-        // 6. Try purchasing same course again (should fail)
+        // 7. Try purchasing same course again (should fail)
         let repeat_purchase_res = test::TestRequest::post()
             .append_header(("Authorization", user_token.clone()))
             .uri(&uri)
             .send_request(&app)
             .await;
 
-        let error_body: CustomError = test::read_body_json(repeat_purchase_res).await;
+        let error_body: ErrorBody = test::read_body_json(repeat_purchase_res).await;
         assert_eq!(error_body.error, "Already Purchased");
 
         // Cleanup
@@ -218,12 +448,24 @@ mod tests {
             .await
             .unwrap();
 
+        sqlx::query("DELETE FROM orders_table WHERE course_id = $1")
+            .bind(course_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
         sqlx::query("DELETE FROM course_table WHERE id = $1")
             .bind(course_uuid)
             .execute(&pool)
             .await
             .unwrap();
 
+        sqlx::query("DELETE FROM email_verification_tokens_table WHERE user_id = (SELECT id FROM user_table WHERE email = $1)")
+            .bind("user_purchase@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+
         sqlx::query("DELETE FROM user_table WHERE email = $1")
             .bind("user_purchase@test.com")
             .execute(&pool)
@@ -236,4 +478,401 @@ mod tests {
             .await
             .unwrap();
     }
-}
\ No newline at end of file
+
+    #[actix_web::test]
+    async fn test_course_catalog() {
+        let (app, pool) = init().await;
+
+        let admin = CreateAdmin {
+            email: String::from("admin_catalog@test.com"),
+            name: String::from("Catalog Admin"),
+            password: String::from("adminpass123")
+        };
+
+        let _ = test::TestRequest::post()
+            .set_json(admin)
+            .uri("/api/v1/admin/signup")
+            .send_request(&app)
+            .await;
+
+        let signin_res = test::TestRequest::post()
+            .set_json(EmailAndPassword {
+                email: "admin_catalog@test.com".to_string(),
+                password: "adminpass123".to_string(),
+            })
+            .uri("/api/v1/admin/signin")
+            .send_request(&app)
+            .await;
+
+        let admin_token = test::read_body_json::<SigninResponse, _>(signin_res).await.token;
+
+        // Three courses of one instructor, created oldest to newest
+        let mut admin_id = String::new();
+
+        for (title, price) in [("Catalog Free", 0), ("Catalog Pricey", 3000), ("Catalog Cheap", 1000)] {
+            let course = CreateCourseWithoutAdminId {
+                title: title.to_string(),
+                image_url: None,
+                price,
+                category: Some("catalog-test".to_string()),
+                description: None,
+            };
+
+            let res = test::TestRequest::post()
+                .set_json(course)
+                .append_header(("Authorization", admin_token.clone()))
+                .uri("/api/v1/admin/course")
+                .send_request(&app)
+                .await;
+
+            let course_res: CourseResponse = test::read_body_json(res).await;
+            publish_course(&pool, &course_res.id).await;
+            admin_id = course_res.admin_id;
+        }
+
+        let catalog = |query:&str| test::TestRequest::get()
+            .uri(&format!("/api/v1/courses?instructor={}&{}", admin_id, query))
+            .to_request();
+
+        // Default sort is newest first
+        let res: CatalogResponse = test::call_and_read_body_json(&app, catalog("")).await;
+        assert_eq!(res.total, 3);
+        assert_eq!(res.courses[0].title, "Catalog Cheap");
+
+        // Paginated by price
+        let res: CatalogResponse = test::call_and_read_body_json(&app, catalog("sort=price_asc&per_page=2")).await;
+        assert_eq!(res.total, 3);
+        assert_eq!(res.total_pages, 2);
+        assert_eq!(res.courses.iter().map(|c| c.price).collect::<Vec<_>>(), vec![0, 1000]);
+
+        let res: CatalogResponse = test::call_and_read_body_json(&app, catalog("sort=price_asc&per_page=2&page=2")).await;
+        assert_eq!(res.courses.iter().map(|c| c.price).collect::<Vec<_>>(), vec![3000]);
+
+        // Filters
+        let res: CatalogResponse = test::call_and_read_body_json(&app, catalog("free=true")).await;
+        assert_eq!(res.total, 1);
+        assert_eq!(res.courses[0].price, 0);
+
+        let res: CatalogResponse = test::call_and_read_body_json(&app, catalog("min_price=500&max_price=2000")).await;
+        assert_eq!(res.total, 1);
+        assert_eq!(res.courses[0].title, "Catalog Cheap");
+
+        let res: CatalogResponse = test::call_and_read_body_json(&app, catalog("category=nothing-here")).await;
+        assert_eq!(res.total, 0);
+        assert_eq!(res.total_pages, 0);
+
+        // Bad input
+        let res = test::call_service(&app, catalog("sort=cheapest")).await;
+        assert_eq!(res.status(), 422);
+
+        let res_body: ErrorBody = test::read_body_json(res).await;
+        assert_eq!(res_body.code, "validation_failed");
+        assert_eq!(res_body.details[0].field, "sort");
+
+        let res = test::call_service(&app, catalog("min_price=2000&max_price=500")).await;
+        assert_eq!(res.status(), 422);
+
+        let res = test::call_service(&app, catalog("min_price=cheap")).await;
+        assert_eq!(res.status(), 400);
+
+        let res_body: ErrorBody = test::read_body_json(res).await;
+        assert_eq!(res_body.code, "invalid_query");
+
+        // Cleanup
+        sqlx::query("DELETE FROM course_table WHERE admin_id = (SELECT id FROM admin_table WHERE email = $1)")
+            .bind("admin_catalog@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM admin_table WHERE email = $1")
+            .bind("admin_catalog@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_course_search() {
+        let (app, pool) = init().await;
+
+        let admin = CreateAdmin {
+            email: String::from("admin_search@test.com"),
+            name: String::from("Search Admin"),
+            password: String::from("adminpass123")
+        };
+
+        let _ = test::TestRequest::post()
+            .set_json(admin)
+            .uri("/api/v1/admin/signup")
+            .send_request(&app)
+            .await;
+
+        let signin_res = test::TestRequest::post()
+            .set_json(EmailAndPassword {
+                email: "admin_search@test.com".to_string(),
+                password: "adminpass123".to_string(),
+            })
+            .uri("/api/v1/admin/signin")
+            .send_request(&app)
+            .await;
+
+        let admin_token = test::read_body_json::<SigninResponse, _>(signin_res).await.token;
+
+        let courses = [
+            ("Quokkacraft Fundamentals", "Learn the basics of handling quokkas."),
+            ("Advanced Gardening", "Includes a short chapter on quokkacraft for gardeners."),
+        ];
+
+        for (title, description) in courses {
+            let course = CreateCourseWithoutAdminId {
+                title: title.to_string(),
+                image_url: None,
+                price: 1000,
+                category: None,
+                description: Some(description.to_string()),
+            };
+
+            let res = test::TestRequest::post()
+                .set_json(course)
+                .append_header(("Authorization", admin_token.clone()))
+                .uri("/api/v1/admin/course")
+                .send_request(&app)
+                .await;
+
+            let course_res: CourseResponse = test::read_body_json(res).await;
+            publish_course(&pool, &course_res.id).await;
+        }
+
+        let search = |q:&str| test::TestRequest::get()
+            .uri(&format!("/api/v1/courses/search?q={}", q))
+            .to_request();
+
+        // A title match ranks above a description match
+        let res: SearchResponse = test::call_and_read_body_json(&app, search("quokkacraft")).await;
+        assert_eq!(res.total, 2);
+        assert_eq!(res.results[0].course.title, "Quokkacraft Fundamentals");
+        assert!(res.results[0].title_highlight.contains("<mark>Quokkacraft</mark>"));
+        assert!(res.results[1].snippet.as_deref().unwrap().contains("<mark>quokkacraft</mark>"));
+
+        // The last word is matched as a prefix while typing
+        let res: SearchResponse = test::call_and_read_body_json(&app, search("quokkacr")).await;
+        assert_eq!(res.total, 2);
+
+        // Every word has to match
+        let res: SearchResponse = test::call_and_read_body_json(&app, search("quokkacraft%20garden")).await;
+        assert_eq!(res.total, 1);
+        assert_eq!(res.results[0].course.title, "Advanced Gardening");
+
+        let res = test::call_service(&app, search("%20!!")).await;
+        assert_eq!(res.status(), 422);
+
+        // Cleanup
+        sqlx::query("DELETE FROM course_table WHERE admin_id = (SELECT id FROM admin_table WHERE email = $1)")
+            .bind("admin_search@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM admin_table WHERE email = $1")
+            .bind("admin_search@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_course_detail_and_rating() {
+        let (app, pool) = init().await;
+
+        let _ = test::TestRequest::post()
+            .set_json(CreateAdmin {
+                email: String::from("admin_detail@test.com"),
+                name: String::from("Detail Admin"),
+                password: String::from("adminpass123")
+            })
+            .uri("/api/v1/admin/signup")
+            .send_request(&app)
+            .await;
+
+        let signin_res = test::TestRequest::post()
+            .set_json(EmailAndPassword {
+                email: "admin_detail@test.com".to_string(),
+                password: "adminpass123".to_string(),
+            })
+            .uri("/api/v1/admin/signin")
+            .send_request(&app)
+            .await;
+
+        let admin_token = test::read_body_json::<SigninResponse, _>(signin_res).await.token;
+
+        let create_course_res = test::TestRequest::post()
+            .set_json(CreateCourseWithoutAdminId {
+                title: "Detail Course".to_string(),
+                image_url: None,
+                price: 2000,
+                category: None,
+                description: None,
+            })
+            .append_header(("Authorization", admin_token.clone()))
+            .uri("/api/v1/admin/course")
+            .send_request(&app)
+            .await;
+
+        let course_res: CourseResponse = test::read_body_json(create_course_res).await;
+        let course_uuid = Uuid::from_str(&course_res.id).unwrap();
+        let sections_uri = format!("/api/v1/admin/course/{}/sections", course_res.id);
+
+        // 1. One section with two lessons
+        let res = test::TestRequest::post()
+            .set_json(SectionInput { title: "Basics".to_string() })
+            .append_header(("Authorization", admin_token.clone()))
+            .uri(&sections_uri)
+            .send_request(&app)
+            .await;
+
+        let section: SectionResponse = test::read_body_json(res).await;
+
+        for (title, duration_seconds) in [("Intro", 120), ("Deep dive", 600)] {
+            let _ = test::TestRequest::post()
+                .set_json(LessonInput {
+                    title: title.to_string(),
+                    body: None,
+                    video_url: None,
+                    duration_seconds,
+                    is_preview: false,
+                })
+                .append_header(("Authorization", admin_token.clone()))
+                .uri(&format!("{}/{}/lessons", sections_uri, section.id))
+                .send_request(&app)
+                .await;
+        }
+
+        let res = test::TestRequest::put()
+            .set_json(CourseStatusInput { status: "published".to_string() })
+            .append_header(("Authorization", admin_token.clone()))
+            .uri(&format!("/api/v1/admin/course/{}/status", course_res.id))
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+
+        // 2. Anonymous callers get the summary
+        let detail_uri = format!("/api/v1/courses/{}", course_res.id);
+
+        let detail: CourseDetailResponse = test::call_and_read_body_json(&app, test::TestRequest::get().uri(&detail_uri).to_request()).await;
+        assert_eq!(detail.course.title, "Detail Course");
+        assert_eq!(detail.instructor_name, "Detail Admin");
+        assert_eq!(detail.lesson_count, 2);
+        assert_eq!(detail.total_duration_seconds, 720);
+        assert_eq!(detail.purchase_count, 0);
+        assert_eq!(detail.average_rating, None);
+        assert!(!detail.owned);
+
+        // 3. A user who has not bought the course can't rate it
+        let signup_res = test::TestRequest::post()
+            .set_json(CreateUser {
+                email: String::from("learner_detail@test.com"),
+                name: String::from("Test User"),
+                password: String::from("userpass123")
+            })
+            .uri("/api/v1/user/signup")
+            .send_request(&app)
+            .await;
+
+        let user_id = test::read_body_json::<SignupResponse, _>(signup_res).await.id;
+        let user_uuid = Uuid::from_str(&user_id).unwrap();
+
+        let signin_res = test::TestRequest::post()
+            .set_json(EmailAndPassword {
+                email: "learner_detail@test.com".to_string(),
+                password: "userpass123".to_string(),
+            })
+            .uri("/api/v1/user/signin")
+            .send_request(&app)
+            .await;
+
+        let user_token = test::read_body_json::<SigninResponse, _>(signin_res).await.token;
+        let rating_uri = format!("/api/v1/user/ratings/{}", course_res.id);
+
+        let res = test::TestRequest::put()
+            .set_json(RatingInput { rating: 4 })
+            .append_header(("Authorization", user_token.clone()))
+            .uri(&rating_uri)
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 403);
+
+        // 4. A buyer rates it and sees the course as owned
+        purchase_course(&mut pool.acquire().await.unwrap(), course_uuid, user_uuid, None, None).await.unwrap();
+
+        // The database itself refuses a second active purchase or one of a missing course
+        let duplicate = purchase_course(&mut pool.acquire().await.unwrap(), course_uuid, user_uuid, None, None).await;
+        assert_eq!(duplicate.err(), Some(DbError::AlreadyPurchased));
+
+        let missing = purchase_course(&mut pool.acquire().await.unwrap(), Uuid::nil(), user_uuid, None, None).await;
+        assert_eq!(missing.err(), Some(DbError::CourseNotFound));
+
+        let res = test::TestRequest::put()
+            .set_json(RatingInput { rating: 7 })
+            .append_header(("Authorization", user_token.clone()))
+            .uri(&rating_uri)
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 422);
+
+        let res = test::TestRequest::put()
+            .set_json(RatingInput { rating: 4 })
+            .append_header(("Authorization", user_token.clone()))
+            .uri(&rating_uri)
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+
+        let req = test::TestRequest::get()
+            .append_header(("Authorization", user_token))
+            .uri(&detail_uri)
+            .to_request();
+
+        let detail: CourseDetailResponse = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(detail.purchase_count, 1);
+        assert_eq!(detail.average_rating, Some(4.0));
+        assert_eq!(detail.rating_count, 1);
+        assert!(detail.owned);
+
+        let res = test::TestRequest::get()
+            .uri(&format!("/api/v1/courses/{}", Uuid::nil()))
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 404);
+
+        // Cleanup
+        sqlx::query("DELETE FROM purchases_table WHERE course_id = $1")
+            .bind(course_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM course_table WHERE id = $1")
+            .bind(course_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM user_table WHERE id = $1")
+            .bind(user_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM admin_table WHERE email = $1")
+            .bind("admin_detail@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
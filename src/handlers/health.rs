@@ -0,0 +1,115 @@
+use actix_web::{get, rt::time::timeout, web, HttpResponse, Responder};
+
+use crate::{health::Health, models::health::{get_applied_migration_version, ping}, schema::{DependencyStatus, HealthResponse, ReadinessChecks, ReadinessResponse}, GlobalState};
+
+fn up(detail:impl Into<String>) -> DependencyStatus {
+    DependencyStatus{status:"up".to_string(), detail:detail.into()}
+}
+
+fn down(detail:impl Into<String>) -> DependencyStatus {
+    DependencyStatus{status:"down".to_string(), detail:detail.into()}
+}
+
+/// The process is alive and serving, says nothing about its dependencies.
+#[get("/healthz")]
+async fn liveness_handler() -> impl Responder{
+    HttpResponse::Ok().json(HealthResponse{status:"ok".to_string()})
+}
+
+/// 200 when this instance should get traffic, 503 with the failing checks otherwise.
+#[get("/readyz")]
+async fn readiness_handler(data:web::Data<GlobalState>) -> impl Responder{
+
+    let pool = &data.pool;
+    let check_timeout = data.health.check_timeout;
+    let timed_out = format!("No answer within {}ms", check_timeout.as_millis());
+
+    let database = match timeout(check_timeout, ping(pool)).await {
+        Ok(Ok(())) => up("reachable"),
+        Ok(Err(e)) => down(e.error),
+        Err(_) => down(timed_out.clone()),
+    };
+
+    let expected = Health::expected_migration();
+
+    let migrations = match timeout(check_timeout, get_applied_migration_version(pool)).await {
+        // a newer schema is fine, migrations are applied before the code that needs them ships
+        Ok(Ok(Some(applied))) if applied >= expected => up(format!("version {}", applied)),
+        Ok(Ok(Some(applied))) => down(format!("version {}, expected {}", applied, expected)),
+        Ok(Ok(None)) => down(format!("no migrations applied, expected {}", expected)),
+        Ok(Err(e)) => down(e.error),
+        Err(_) => down(timed_out),
+    };
+
+    let shutdown = if data.health.is_draining() {
+        down("draining")
+    } else {
+        up("running")
+    };
+
+    let ready = [&database, &migrations, &shutdown].iter().all(|check| check.status == "up");
+
+    let response = ReadinessResponse{
+        status: if ready { "ready" } else { "not_ready" }.to_string(),
+        checks: ReadinessChecks{database, migrations, shutdown},
+    };
+
+    if ready {
+        HttpResponse::Ok().json(response)
+    } else {
+        HttpResponse::ServiceUnavailable().json(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_init_app::init_with_state;
+    use actix_web::test;
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_health_checks() {
+        let (app, _pool, state) = init_with_state().await;
+
+        let res = test::TestRequest::get()
+            .uri("/healthz")
+            .send_request(&app)
+            .await;
+
+        let health: HealthResponse = test::read_body_json(res).await;
+        assert_eq!(health.status, "ok");
+
+        let res = test::TestRequest::get()
+            .uri("/readyz")
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+
+        let readiness: ReadinessResponse = test::read_body_json(res).await;
+        assert_eq!(readiness.status, "ready");
+        assert_eq!(readiness.checks.database.status, "up");
+        assert_eq!(readiness.checks.migrations.detail, format!("version {}", Health::expected_migration()));
+
+        // once shutdown starts the instance stops being ready but stays alive
+        state.health.start_draining();
+
+        let res = test::TestRequest::get()
+            .uri("/readyz")
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 503);
+
+        let readiness: ReadinessResponse = test::read_body_json(res).await;
+        assert_eq!(readiness.status, "not_ready");
+        assert_eq!(readiness.checks.shutdown.status, "down");
+
+        let res = test::TestRequest::get()
+            .uri("/healthz")
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+    }
+}
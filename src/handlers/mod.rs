@@ -1,23 +1,54 @@
 pub mod admin;
 pub mod user;
 pub mod course;
+pub mod content;
+pub mod progress;
+pub mod order;
+pub mod coupon;
+pub mod bundle;
+pub mod health;
 
-use actix_web::{Responder, get};
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder, ResponseError};
+
+use crate::{errors::ApiError, utils::hash_token, GlobalState};
 
 #[get("/hello")]
 pub async fn hello_world() -> impl Responder{
     "hello_world!"
 }
 
+/// Only served when a metrics token is configured, to callers sending it as `Bearer <token>`.
+#[get("/metrics")]
+pub async fn metrics_handler(data:web::Data<GlobalState>, req:HttpRequest) -> impl Responder{
+
+    let Some(metrics_token) = &data.metrics_token else {
+        return ApiError::not_found("not_found", "Not found").error_response();
+    };
+
+    let token = req.headers().get("Authorization")
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.strip_prefix("Bearer "));
+
+    // compared as hashes so the time taken says nothing about how much of the token matched
+    if token.is_none_or(|token| hash_token(token) != hash_token(metrics_token)){
+        return ApiError::unauthorized("invalid_metrics_token", "Invalid metrics token").error_response();
+    }
+
+    match data.metrics.render(&data.pool) {
+        Ok(body) => HttpResponse::Ok().content_type(prometheus::TEXT_FORMAT).body(body),
+        Err(e) => ApiError::internal(e.to_string()).error_response(),
+    }
+}
+
 #[cfg(test)]
 mod tests{
     use actix_web::{test::{self, TestRequest}};
 
-    use super::*;
+    use crate::{schema::{user::CreateUser, EmailAndPassword}, test_init_app::TEST_METRICS_TOKEN};
 
     #[actix_web::test]
     async fn test_hello_world(){
-        let (app, _s) = crate::test_init_app::init(hello_world).await;
+        let (app, _s) = crate::test_init_app::init().await;
 
         let req = TestRequest::get().uri("/api/v1/hello").to_request();
         let res = test::call_service(&app, req).await;
@@ -27,4 +58,59 @@ mod tests{
         
         assert_eq!(body_str, "hello_world!");
     }
-}
\ No newline at end of file
+
+    #[actix_web::test]
+    async fn test_metrics(){
+        let (app, pool) = crate::test_init_app::init().await;
+
+        let _ = TestRequest::post()
+            .set_json(CreateUser {
+                email: String::from("user_metrics@test.com"),
+                name: String::from("Test User"),
+                password: String::from("userpass123")
+            })
+            .uri("/api/v1/user/signup")
+            .send_request(&app)
+            .await;
+
+        let _ = TestRequest::post()
+            .set_json(EmailAndPassword {
+                email: "user_metrics@test.com".to_string(),
+                password: "wrongpass123".to_string(),
+            })
+            .uri("/api/v1/user/signin")
+            .send_request(&app)
+            .await;
+
+        let _ = TestRequest::get()
+            .uri("/api/v1/courses/not-a-uuid")
+            .send_request(&app)
+            .await;
+
+        let res = TestRequest::get().uri("/metrics").send_request(&app).await;
+        assert_eq!(res.status().as_u16(), 401);
+
+        let res = TestRequest::get()
+            .append_header(("Authorization", format!("Bearer {}", TEST_METRICS_TOKEN)))
+            .uri("/metrics")
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+
+        let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+
+        // routes are labelled by pattern, not by the id that was asked for
+        assert!(body.contains(r#"courser_http_requests_total{method="GET",route="/api/v1/courses/{course_id}",status="400"} 1"#));
+        assert!(body.contains(r#"courser_signups_total{kind="user"} 1"#));
+        assert!(body.contains(r#"courser_signins_total{kind="user",outcome="failed"} 1"#));
+        assert!(body.contains("courser_db_pool_max_connections 2"));
+
+        // Cleanup
+        sqlx::query("DELETE FROM user_table WHERE email = $1")
+            .bind("user_metrics@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
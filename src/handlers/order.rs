@@ -0,0 +1,767 @@
+use std::str::FromStr;
+
+use actix_web::{get, post, web::{self, Bytes}, HttpRequest, HttpResponse, Responder, ResponseError};
+use sqlx::{types::Uuid, Pool, Postgres};
+
+use crate::{errors::ApiError, extractors::{principal::{AuthAdmin, AuthUser}, validated_json::ValidatedJson}, models::{order::{self, get_order_by_provider_ref, get_user_orders, OrderItem}, refund::{refund_purchase, RefundOutcome}, user::is_user_verified}, payments::PaymentOutcome, schema::{admin::RefundRequest, CheckoutResponse, MessageResponse}, GlobalState};
+
+/// The signed in learner's id, as long as they verified their email.
+pub async fn verified_buyer(pool:&Pool<Postgres>, user:&AuthUser) -> Result<Uuid, HttpResponse>{
+
+    let is_verified = is_user_verified(pool, user.id).await
+    .map_err(|e| ApiError::from(e).error_response())?;
+
+    if !is_verified{
+        return Err(ApiError::forbidden("email_not_verified", "Verify your email before purchasing").error_response());
+    }
+
+    Ok(user.id)
+}
+
+/// Opens (or reuses) the pending order for the item and hands back where to pay for it.
+pub async fn checkout_order(data:&GlobalState, user_uuid:Uuid, item:OrderItem, amount:i32, coupon_uuid:Option<Uuid>) -> HttpResponse{
+
+    let pool = &data.pool;
+
+    let pending_order = order::get_pending_order(pool, user_uuid, item).await;
+
+    if let Err(e) = pending_order{
+        return ApiError::from(e).error_response();
+    }
+
+    let pending_order = pending_order.unwrap().filter(|pending_order|{
+        pending_order.coupon_id == coupon_uuid.map(|coupon_uuid| coupon_uuid.to_string())
+        && pending_order.amount == amount
+    });
+
+    let pending_order = match pending_order {
+        // hand back the open checkout instead of starting another one
+        Some(pending_order) => pending_order,
+        None => {
+            // a checkout with another coupon (or none) or an outdated price is dropped, freeing its redemption
+            if let Ok(Some(stale_order)) = order::get_pending_order(pool, user_uuid, item).await {
+                if let Ok(stale_uuid) = Uuid::from_str(&stale_order.id) {
+                    if let Err(e) = order::fail_order(pool, stale_uuid).await {
+                        return ApiError::from(e).error_response();
+                    }
+                }
+            }
+
+            let new_order = order::create_order(pool, user_uuid, item, amount, coupon_uuid, data.payments.name()).await;
+
+            if let Err(e) = new_order{
+                return ApiError::from(e).error_response();
+            }
+
+            let new_order = new_order.unwrap();
+
+            if new_order.is_none(){
+                return ApiError::bad_request("invalid_coupon", "Coupon has been fully redeemed").error_response();
+            }
+
+            new_order.unwrap()
+        }
+    };
+
+    let order_uuid = Uuid::from_str(&pending_order.id);
+
+    if order_uuid.is_err(){
+        return ApiError::internal("Internal Error").error_response();
+    }
+
+    let order_uuid = order_uuid.unwrap();
+
+    // nothing to charge, so there is no checkout to wait for
+    if pending_order.amount == 0 {
+        return match order::pay_order(pool, order_uuid).await {
+            Ok(Some(res)) => {
+                data.metrics.record_purchase(&res);
+
+                HttpResponse::Ok().json(CheckoutResponse{
+                    message: "Purchased Successfully".to_string(),
+                    order_id: res.id,
+                    status: res.status,
+                    amount: res.amount,
+                    provider: res.provider,
+                    checkout_url: None,
+                })
+            },
+            Ok(None) => ApiError::conflict("order_not_pending", "Order is no longer pending").error_response(),
+            Err(e) => ApiError::from(e).error_response(),
+        };
+    }
+
+    let checkout = data.payments.create_checkout(&pending_order.id, pending_order.amount);
+
+    if let Err(e) = checkout{
+        return ApiError::from(e).error_response();
+    }
+
+    let checkout = checkout.unwrap();
+
+    let order_res = order::set_provider_ref(pool, order_uuid, &checkout.provider_ref).await;
+
+    match order_res {
+        Ok(res) => HttpResponse::Ok().json(CheckoutResponse{
+            message: "Complete the payment to get access".to_string(),
+            order_id: res.id,
+            status: res.status,
+            amount: res.amount,
+            provider: res.provider,
+            checkout_url: Some(checkout.checkout_url),
+        }),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[get("")]
+async fn user_orders(data:web::Data<GlobalState>, user:AuthUser) -> impl Responder{
+
+    match get_user_orders(&data.pool, user.id).await {
+        Ok(orders) => HttpResponse::Ok().json(orders),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+/// Called by the payment provider, the signature in `X-Signature` is all that authenticates it.
+#[post("/webhook")]
+async fn payment_webhook_handler(data:web::Data<GlobalState>, req:HttpRequest, body:Bytes) -> impl Responder{
+
+    let signature = req.headers().get("X-Signature").and_then(|val| val.to_str().ok());
+
+    if signature.is_none(){
+        return ApiError::unauthorized("invalid_signature", "Signature missing").error_response();
+    }
+
+    let event = data.payments.parse_webhook(&body, signature.unwrap());
+
+    if let Err(e) = event{
+        return ApiError::unauthorized("invalid_signature", e.error).error_response();
+    }
+
+    let event = event.unwrap();
+    let pool = &data.pool;
+
+    let existing_order = get_order_by_provider_ref(pool, &event.provider_ref).await;
+
+    if let Err(e) = existing_order{
+        return ApiError::from(e).error_response();
+    }
+
+    let existing_order = existing_order.unwrap();
+
+    if existing_order.is_none(){
+        return ApiError::not_found("order_not_found", "Order not found").error_response();
+    }
+
+    let existing_order = existing_order.unwrap();
+
+    let order_uuid = Uuid::from_str(&existing_order.id);
+
+    if order_uuid.is_err(){
+        return ApiError::internal("Internal Error").error_response();
+    }
+
+    let order_uuid = order_uuid.unwrap();
+
+    let updated_order = match event.outcome {
+        PaymentOutcome::Paid => order::pay_order(pool, order_uuid).await,
+        PaymentOutcome::Failed => order::fail_order(pool, order_uuid).await,
+    };
+
+    // providers retry webhooks, so a replay for an order that already moved on is still a 200
+    match updated_order {
+        Ok(Some(res)) => {
+            if event.outcome == PaymentOutcome::Paid{
+                data.metrics.record_purchase(&res);
+            }
+
+            HttpResponse::Ok().json(MessageResponse{message:format!("Order {}", res.status)})
+        },
+        // a checkout dropped for another one (e.g. after switching coupons) can still be paid at
+        // the provider, nothing is granted for it so the payment is flagged to be paid back
+        Ok(None) if event.outcome == PaymentOutcome::Paid => match order::flag_failed_order(pool, order_uuid).await {
+            Ok(Some(_)) => HttpResponse::Ok().json(MessageResponse{message:"Order failed, payment flagged for refund".to_string()}),
+            Ok(None) => HttpResponse::Ok().json(MessageResponse{message:format!("Order already {}", existing_order.status)}),
+            Err(e) => ApiError::from(e).error_response(),
+        },
+        Ok(None) => HttpResponse::Ok().json(MessageResponse{message:format!("Order already {}", existing_order.status)}),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+// lets support reverse a purchase, the learner loses access but the purchase row is kept
+#[post("/{purchase_id}/refund")]
+async fn refund_purchase_handler(data:web::Data<GlobalState>, admin:AuthAdmin, path:web::Path<String>, body:ValidatedJson<RefundRequest>) -> impl Responder{
+
+    let purchase_uuid = Uuid::from_str(&path.into_inner());
+
+    if purchase_uuid.is_err(){
+        return ApiError::bad_request("invalid_id", "Invalid purchase id").error_response();
+    }
+
+    let pool = &data.pool;
+
+    let refund_res = refund_purchase(
+        pool,
+        data.payments.as_ref(),
+        purchase_uuid.unwrap(),
+        admin.id,
+        body.reason.as_deref(),
+        data.refund_window_days,
+    ).await;
+
+    match refund_res {
+        Ok(RefundOutcome::Refunded(refund)) => HttpResponse::Ok().json(refund),
+        Ok(RefundOutcome::NotFound) => ApiError::not_found("purchase_not_found", "Purchase not found").error_response(),
+        Ok(RefundOutcome::AlreadyRefunded) => ApiError::conflict("already_refunded", "Already refunded").error_response(),
+        Ok(RefundOutcome::WindowExpired) => ApiError::bad_request("refund_window_expired", format!("Refunds are only allowed within {} days of purchase", data.refund_window_days)).error_response(),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{errors::ErrorBody, models::{admin::create_admin, coupon::{create_coupon, NewCoupon, PERCENT_COUPON}, course::create_course, order::{create_order, Order, PENDING_ORDER_TTL_MINUTES},  purchase::{has_purchased, purchase_course}, refund::Refund, role::{assign_role, roles}, session::ADMIN_SESSION, user::create_user}, payments::PaymentEvent, schema::{admin::{CreateAdmin, CreateCourse}, user::CreateUser, CheckoutResponse, EmailAndPassword, SigninResponse, SignupResponse}, test_init_app::{init, publish_course, sign_webhook}};
+    use actix_web::test;
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_refund_purchase() {
+        let (app, pool) = init().await;
+
+        // 1. A support admin
+        let signup_res = test::TestRequest::post()
+            .set_json(CreateAdmin {
+                email: String::from("admin_refund@test.com"),
+                name: String::from("Test Admin"),
+                password: String::from("adminpass123")
+            })
+            .uri("/api/v1/admin/signup")
+            .send_request(&app)
+            .await;
+
+        let admin_id = test::read_body_json::<SignupResponse, _>(signup_res).await.id;
+        let admin_uuid = Uuid::from_str(&admin_id).unwrap();
+
+        assign_role(&pool, admin_uuid, ADMIN_SESSION, roles::SUPPORT).await.unwrap();
+
+        let signin_res = test::TestRequest::post()
+            .set_json(EmailAndPassword {
+                email: "admin_refund@test.com".to_string(),
+                password: "adminpass123".to_string(),
+            })
+            .uri("/api/v1/admin/signin")
+            .send_request(&app)
+            .await;
+
+        let admin_token = test::read_body_json::<SigninResponse, _>(signin_res).await.token;
+
+        // 2. A learner who paid for one course and was granted another before orders existed
+        let user_id = create_user(&pool, CreateUser {
+            email: String::from("learner_refund@test.com"),
+            name: String::from("Test User"),
+            password: String::from("not-a-hash"),
+        }).await.unwrap();
+
+        let user_uuid = Uuid::from_str(&user_id).unwrap();
+
+        let paid_course = create_course(&pool, CreateCourse {
+            title: "Refunded Course".to_string(),
+            image_url: None,
+            price: 5000,
+            category: None,
+            description: None,
+            admin_id: admin_uuid,
+        }).await.unwrap();
+
+        let old_course = create_course(&pool, CreateCourse {
+            title: "Old Course".to_string(),
+            image_url: None,
+            price: 1000,
+            category: None,
+            description: None,
+            admin_id: admin_uuid,
+        }).await.unwrap();
+
+        let paid_course_uuid = Uuid::from_str(&paid_course.id).unwrap();
+        let old_course_uuid = Uuid::from_str(&old_course.id).unwrap();
+
+        let paid_order = create_order(&pool, user_uuid, OrderItem::Course(paid_course_uuid), 5000, None, "mock").await.unwrap().unwrap();
+        let paid_order_uuid = Uuid::from_str(&paid_order.id).unwrap();
+
+        order::set_provider_ref(&pool, paid_order_uuid, &format!("mock_{}", paid_order.id)).await.unwrap();
+        order::pay_order(&pool, paid_order_uuid).await.unwrap();
+
+        let paid_purchase_id = sqlx::query_scalar::<_, Uuid>("SELECT id FROM purchases_table WHERE order_id = $1")
+            .bind(paid_order_uuid)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        let old_purchase = purchase_course(&mut pool.acquire().await.unwrap(), old_course_uuid, user_uuid, None, None).await.unwrap();
+
+        sqlx::query("UPDATE purchases_table SET purchased_at = now() - interval '31 days' WHERE id = $1")
+            .bind(Uuid::from_str(&old_purchase.id).unwrap())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // 3. Refund the paid purchase
+        let refund_uri = format!("/api/v1/admin/purchases/{}/refund", paid_purchase_id);
+
+        let res = test::TestRequest::post()
+            .set_json(RefundRequest { reason: Some("Bought by mistake".to_string()) })
+            .append_header(("Authorization", admin_token.clone()))
+            .uri(&refund_uri)
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+
+        let refund: Refund = test::read_body_json(res).await;
+        assert_eq!(refund.amount, 5000);
+        assert_eq!(refund.order_id, Some(paid_order.id.clone()));
+
+        // access is gone, the history is not
+        assert!(!has_purchased(&pool, user_uuid, paid_course_uuid).await.unwrap());
+
+        let revoked_rows = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM purchases_table WHERE id = $1 AND revoked_at IS NOT NULL")
+            .bind(paid_purchase_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(revoked_rows, 1);
+
+        let order_status = sqlx::query_scalar::<_, String>("SELECT status FROM orders_table WHERE id = $1")
+            .bind(paid_order_uuid)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(order_status, "refunded");
+
+        let res = test::TestRequest::post()
+            .set_json(RefundRequest { reason: None })
+            .append_header(("Authorization", admin_token.clone()))
+            .uri(&refund_uri)
+            .send_request(&app)
+            .await;
+
+        let error_body: ErrorBody = test::read_body_json(res).await;
+        assert_eq!(error_body.error, "Already refunded");
+
+        // 4. The old purchase is outside the refund window
+        let res = test::TestRequest::post()
+            .set_json(RefundRequest { reason: None })
+            .append_header(("Authorization", admin_token))
+            .uri(&format!("/api/v1/admin/purchases/{}/refund", old_purchase.id))
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 400);
+        assert!(has_purchased(&pool, user_uuid, old_course_uuid).await.unwrap());
+
+        // Cleanup
+        sqlx::query("DELETE FROM refunds_table WHERE refunded_by = $1")
+            .bind(admin_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM purchases_table WHERE user_id = $1")
+            .bind(user_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM orders_table WHERE user_id = $1")
+            .bind(user_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM course_table WHERE admin_id = $1")
+            .bind(admin_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM user_table WHERE id = $1")
+            .bind(user_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM sessions_table WHERE owner_id = $1")
+            .bind(admin_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM principal_roles_table WHERE principal_id = $1")
+            .bind(admin_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM admin_table WHERE id = $1")
+            .bind(admin_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_paid_orders_that_grant_nothing() {
+        let (app, pool) = init().await;
+
+        let admin_id = create_admin(&pool, CreateAdmin {
+            email: String::from("admin_owned_order@test.com"),
+            name: String::from("Test Admin"),
+            password: String::from("not-a-hash"),
+        }).await.unwrap();
+
+        let user_id = create_user(&pool, CreateUser {
+            email: String::from("learner_owned_order@test.com"),
+            name: String::from("Test User"),
+            password: String::from("not-a-hash"),
+        }).await.unwrap();
+
+        let admin_uuid = Uuid::from_str(&admin_id).unwrap();
+        let user_uuid = Uuid::from_str(&user_id).unwrap();
+
+        let course = create_course(&pool, CreateCourse {
+            title: "Owned Course".to_string(),
+            image_url: None,
+            price: 2000,
+            category: None,
+            description: None,
+            admin_id: admin_uuid,
+        }).await.unwrap();
+
+        let course_uuid = Uuid::from_str(&course.id).unwrap();
+
+        // the learner checks out, then gets the course some other way before paying
+        let pending_order = create_order(&pool, user_uuid, OrderItem::Course(course_uuid), 2000, None, "mock").await.unwrap().unwrap();
+        let provider_ref = format!("mock_{}", pending_order.id);
+
+        order::set_provider_ref(&pool, Uuid::from_str(&pending_order.id).unwrap(), &provider_ref).await.unwrap();
+        purchase_course(&mut pool.acquire().await.unwrap(), course_uuid, user_uuid, None, None).await.unwrap();
+
+        let payload = serde_json::to_vec(&PaymentEvent{provider_ref:provider_ref.clone(), outcome:PaymentOutcome::Paid}).unwrap();
+
+        let res = test::TestRequest::post()
+            .append_header(("X-Signature", sign_webhook(&payload)))
+            .set_payload(payload)
+            .uri("/api/v1/payments/webhook")
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+
+        let body: MessageResponse = test::read_body_json(res).await;
+        assert_eq!(body.message, "Order fulfilled");
+
+        // the money is kept on record and flagged, the course isn't granted twice
+        let paid_order: Order = get_order_by_provider_ref(&pool, &provider_ref).await.unwrap().unwrap();
+        assert_eq!(paid_order.status, "fulfilled");
+        assert!(paid_order.refund_due);
+
+        let purchase_rows = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM purchases_table WHERE user_id = $1")
+            .bind(user_uuid)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(purchase_rows, 1);
+
+        // a checkout that was dropped still gets paid at the provider
+        let dropped_order = create_order(&pool, user_uuid, OrderItem::Course(course_uuid), 1500, None, "mock").await.unwrap().unwrap();
+        let dropped_uuid = Uuid::from_str(&dropped_order.id).unwrap();
+        let provider_ref = format!("mock_{}", dropped_order.id);
+
+        order::set_provider_ref(&pool, dropped_uuid, &provider_ref).await.unwrap();
+        order::fail_order(&pool, dropped_uuid).await.unwrap();
+
+        let payload = serde_json::to_vec(&PaymentEvent{provider_ref:provider_ref.clone(), outcome:PaymentOutcome::Paid}).unwrap();
+
+        let res = test::TestRequest::post()
+            .append_header(("X-Signature", sign_webhook(&payload)))
+            .set_payload(payload)
+            .uri("/api/v1/payments/webhook")
+            .send_request(&app)
+            .await;
+
+        let body: MessageResponse = test::read_body_json(res).await;
+        assert_eq!(body.message, "Order failed, payment flagged for refund");
+
+        let dropped_order: Order = get_order_by_provider_ref(&pool, &provider_ref).await.unwrap().unwrap();
+        assert_eq!(dropped_order.status, "failed");
+        assert!(dropped_order.refund_due);
+
+        // Cleanup
+        sqlx::query("DELETE FROM purchases_table WHERE user_id = $1")
+            .bind(user_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM orders_table WHERE user_id = $1")
+            .bind(user_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM course_table WHERE id = $1")
+            .bind(course_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM user_table WHERE id = $1")
+            .bind(user_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM admin_table WHERE id = $1")
+            .bind(admin_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_checkout_follows_price_changes() {
+        let (app, pool) = init().await;
+
+        let admin_id = create_admin(&pool, CreateAdmin {
+            email: String::from("admin_reprice@test.com"),
+            name: String::from("Test Admin"),
+            password: String::from("not-a-hash"),
+        }).await.unwrap();
+
+        let admin_uuid = Uuid::from_str(&admin_id).unwrap();
+
+        let course = create_course(&pool, CreateCourse {
+            title: "Repriced Course".to_string(),
+            image_url: None,
+            price: 2000,
+            category: None,
+            description: None,
+            admin_id: admin_uuid,
+        }).await.unwrap();
+
+        let course_uuid = Uuid::from_str(&course.id).unwrap();
+        publish_course(&pool, &course.id).await;
+
+        let _ = test::TestRequest::post()
+            .set_json(CreateUser {
+                email: String::from("learner_reprice@test.com"),
+                name: String::from("Test User"),
+                password: String::from("userpass123")
+            })
+            .uri("/api/v1/user/signup")
+            .send_request(&app)
+            .await;
+
+        sqlx::query("UPDATE user_table SET email_verified_at = now() WHERE email = $1")
+            .bind("learner_reprice@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let signin_res = test::TestRequest::post()
+            .set_json(EmailAndPassword {
+                email: "learner_reprice@test.com".to_string(),
+                password: "userpass123".to_string(),
+            })
+            .uri("/api/v1/user/signin")
+            .send_request(&app)
+            .await;
+
+        let user_token = test::read_body_json::<SigninResponse, _>(signin_res).await.token;
+        let purchase_uri = format!("/api/v1/courses/purchase/{}", course.id);
+
+        let res = test::TestRequest::post()
+            .append_header(("Authorization", user_token.clone()))
+            .uri(&purchase_uri)
+            .send_request(&app)
+            .await;
+
+        let first_checkout: CheckoutResponse = test::read_body_json(res).await;
+        assert_eq!(first_checkout.amount, 2000);
+
+        // the same price hands back the open checkout
+        let res = test::TestRequest::post()
+            .append_header(("Authorization", user_token.clone()))
+            .uri(&purchase_uri)
+            .send_request(&app)
+            .await;
+
+        let checkout: CheckoutResponse = test::read_body_json(res).await;
+        assert_eq!(checkout.order_id, first_checkout.order_id);
+
+        // a new price replaces it
+        sqlx::query("UPDATE course_table SET price = 2500 WHERE id = $1")
+            .bind(course_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let res = test::TestRequest::post()
+            .append_header(("Authorization", user_token))
+            .uri(&purchase_uri)
+            .send_request(&app)
+            .await;
+
+        let checkout: CheckoutResponse = test::read_body_json(res).await;
+        assert_eq!(checkout.amount, 2500);
+        assert_ne!(checkout.order_id, first_checkout.order_id);
+
+        let first_status = sqlx::query_scalar::<_, String>("SELECT status FROM orders_table WHERE id = $1::uuid")
+            .bind(&first_checkout.order_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(first_status, "failed");
+
+        // Cleanup
+        sqlx::query("DELETE FROM orders_table WHERE course_id = $1")
+            .bind(course_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM course_table WHERE id = $1")
+            .bind(course_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM user_table WHERE email = $1")
+            .bind("learner_reprice@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM admin_table WHERE id = $1")
+            .bind(admin_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_abandoned_checkout_frees_its_coupon() {
+        let (_app, pool) = init().await;
+
+        let admin_id = create_admin(&pool, CreateAdmin {
+            email: String::from("admin_abandoned@test.com"),
+            name: String::from("Test Admin"),
+            password: String::from("not-a-hash"),
+        }).await.unwrap();
+
+        let admin_uuid = Uuid::from_str(&admin_id).unwrap();
+        let mut user_uuids = Vec::new();
+
+        for email in ["learner_abandoned@test.com", "learner_patient@test.com"] {
+            let user_id = create_user(&pool, CreateUser {
+                email: email.to_string(),
+                name: String::from("Test User"),
+                password: String::from("not-a-hash"),
+            }).await.unwrap();
+
+            user_uuids.push(Uuid::from_str(&user_id).unwrap());
+        }
+
+        let course = create_course(&pool, CreateCourse {
+            title: "Abandoned Checkout Course".to_string(),
+            image_url: None,
+            price: 2000,
+            category: None,
+            description: None,
+            admin_id: admin_uuid,
+        }).await.unwrap();
+
+        let course_uuid = Uuid::from_str(&course.id).unwrap();
+
+        let coupon = create_coupon(&pool, NewCoupon {
+            code: "ONLY_ONE",
+            kind: PERCENT_COUPON,
+            value: 50,
+            course_id: Some(course_uuid),
+            expires_at: None,
+            max_redemptions: Some(1),
+            created_by: admin_uuid,
+        }).await.unwrap().unwrap();
+
+        let coupon_uuid = Uuid::from_str(&coupon.id).unwrap();
+        let item = OrderItem::Course(course_uuid);
+
+        // the first learner takes the only redemption and never pays
+        let abandoned = create_order(&pool, user_uuids[0], item, 1000, Some(coupon_uuid), "mock").await.unwrap().unwrap();
+        assert!(create_order(&pool, user_uuids[1], item, 1000, Some(coupon_uuid), "mock").await.unwrap().is_none());
+
+        sqlx::query("UPDATE orders_table SET created_at = now() - make_interval(mins => $2) WHERE id = $1::uuid")
+            .bind(&abandoned.id)
+            .bind(PENDING_ORDER_TTL_MINUTES + 1)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // once the checkout is stale it is neither handed back nor holding the coupon
+        assert!(order::get_pending_order(&pool, user_uuids[0], item).await.unwrap().is_none());
+        assert!(create_order(&pool, user_uuids[1], item, 1000, Some(coupon_uuid), "mock").await.unwrap().is_some());
+
+        let abandoned_status = sqlx::query_scalar::<_, String>("SELECT status FROM orders_table WHERE id = $1::uuid")
+            .bind(&abandoned.id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(abandoned_status, "failed");
+
+        let redemptions = sqlx::query_scalar::<_, i32>("SELECT redemptions FROM coupons_table WHERE id = $1")
+            .bind(coupon_uuid)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(redemptions, 1);
+
+        // Cleanup
+        sqlx::query("DELETE FROM orders_table WHERE course_id = $1")
+            .bind(course_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM coupons_table WHERE id = $1")
+            .bind(coupon_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM course_table WHERE id = $1")
+            .bind(course_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM user_table WHERE id = ANY($1)")
+            .bind(&user_uuids)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM admin_table WHERE id = $1")
+            .bind(admin_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,263 @@
+use std::str::FromStr;
+
+use actix_web::{delete, put, web, HttpResponse, Responder, ResponseError};
+use sqlx::{types::Uuid, Pool, Postgres};
+
+use crate::{errors::ApiError, extractors::{principal::AuthUser, validated_json::ValidatedJson}, models::{lesson::{get_lesson_course, LessonCourse}, progress::{self, LessonProgress}, purchase::has_purchased}, schema::user::{LessonPositionInput, LessonProgressResponse}, GlobalState};
+
+/// The lesson in the path, as long as the user bought the lesson's course.
+async fn purchased_lesson(pool:&Pool<Postgres>, user:&AuthUser, lesson_id:&str) -> Result<(Uuid, LessonCourse), HttpResponse>{
+
+    let lesson_uuid = Uuid::from_str(lesson_id)
+    .map_err(|_e| ApiError::bad_request("invalid_id", "Invalid id").error_response())?;
+
+    let lesson_course = get_lesson_course(pool, lesson_uuid).await
+    .map_err(|e| ApiError::from(e).error_response())?
+    .ok_or_else(|| ApiError::not_found("lesson_not_found", "Lesson not found").error_response())?;
+
+    let is_owner = has_purchased(pool, user.id, lesson_course.course_id).await
+    .map_err(|e| ApiError::from(e).error_response())?;
+
+    if !is_owner{
+        return Err(ApiError::forbidden("purchase_required", "Purchase the course first").error_response());
+    }
+
+    Ok((lesson_uuid, lesson_course))
+}
+
+fn progress_response(progress:LessonProgress) -> LessonProgressResponse{
+    LessonProgressResponse{
+        lesson_id: progress.lesson_id.to_string(),
+        completed: progress.completed,
+        last_position_seconds: progress.last_position_seconds,
+    }
+}
+
+#[put("/{lesson_id}/complete")]
+async fn complete_lesson_handler(data:web::Data<GlobalState>, user:AuthUser, path:web::Path<String>) -> impl Responder {
+
+    let pool = &data.pool;
+
+    let (lesson_uuid, _) = match purchased_lesson(pool, &user, &path.into_inner()).await {
+        Ok(res) => res,
+        Err(res) => return res,
+    };
+
+    match progress::set_lesson_completed(pool, user.id, lesson_uuid, true).await {
+        Ok(res) => HttpResponse::Ok().json(progress_response(res)),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[delete("/{lesson_id}/complete")]
+async fn uncomplete_lesson_handler(data:web::Data<GlobalState>, user:AuthUser, path:web::Path<String>) -> impl Responder {
+
+    let pool = &data.pool;
+
+    let (lesson_uuid, _) = match purchased_lesson(pool, &user, &path.into_inner()).await {
+        Ok(res) => res,
+        Err(res) => return res,
+    };
+
+    match progress::set_lesson_completed(pool, user.id, lesson_uuid, false).await {
+        Ok(res) => HttpResponse::Ok().json(progress_response(res)),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[put("/{lesson_id}/position")]
+async fn lesson_position_handler(data:web::Data<GlobalState>, body:ValidatedJson<LessonPositionInput>, user:AuthUser, path:web::Path<String>) -> impl Responder {
+
+    let pool = &data.pool;
+
+    let (lesson_uuid, lesson_course) = match purchased_lesson(pool, &user, &path.into_inner()).await {
+        Ok(res) => res,
+        Err(res) => return res,
+    };
+
+    if lesson_course.video_url.is_none(){
+        return ApiError::bad_request("lesson_has_no_video", "Lesson has no video").error_response();
+    }
+
+    match progress::set_last_position(pool, user.id, lesson_uuid, body.position_seconds).await {
+        Ok(res) => HttpResponse::Ok().json(progress_response(res)),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{models::{admin::create_admin, course::create_course, lesson::create_lesson, progress::PurchaseWithProgress, purchase::purchase_course, section::create_section}, schema::{admin::{CreateAdmin, CreateCourse}, content::LessonInput, user::CreateUser, EmailAndPassword, SigninResponse, SignupResponse}, test_init_app::init};
+    use actix_web::test;
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_lesson_progress_and_completion() {
+        let (app, pool) = init().await;
+
+        // 1. A course with a video lesson and a text lesson
+        let admin_id = create_admin(&pool, CreateAdmin {
+            email: String::from("admin_progress@test.com"),
+            name: String::from("Test Admin"),
+            password: String::from("not-a-hash"),
+        }).await.unwrap();
+
+        let course = create_course(&pool, CreateCourse {
+            title: "Progress Course".to_string(),
+            image_url: None,
+            price: 1000,
+            category: None,
+            description: None,
+            admin_id: Uuid::from_str(&admin_id).unwrap(),
+        }).await.unwrap();
+
+        let course_uuid = Uuid::from_str(&course.id).unwrap();
+        let section = create_section(&pool, course_uuid, "Only section").await.unwrap();
+        let section_uuid = Uuid::from_str(&section.id).unwrap();
+
+        let video_lesson = create_lesson(&pool, section_uuid, &LessonInput {
+            title: "Video".to_string(),
+            body: None,
+            video_url: Some("https://test.com/video.mp4".to_string()),
+            duration_seconds: 600,
+            is_preview: false,
+        }).await.unwrap();
+
+        let text_lesson = create_lesson(&pool, section_uuid, &LessonInput {
+            title: "Reading".to_string(),
+            body: Some("Read me".to_string()),
+            video_url: None,
+            duration_seconds: 0,
+            is_preview: false,
+        }).await.unwrap();
+
+        // 2. A learner who has not bought it yet
+        let signup_res = test::TestRequest::post()
+            .set_json(CreateUser {
+                email: String::from("learner_progress@test.com"),
+                name: String::from("Test User"),
+                password: String::from("userpass123")
+            })
+            .uri("/api/v1/user/signup")
+            .send_request(&app)
+            .await;
+
+        let user_uuid = Uuid::from_str(&test::read_body_json::<SignupResponse, _>(signup_res).await.id).unwrap();
+
+        let signin_res = test::TestRequest::post()
+            .set_json(EmailAndPassword {
+                email: "learner_progress@test.com".to_string(),
+                password: "userpass123".to_string(),
+            })
+            .uri("/api/v1/user/signin")
+            .send_request(&app)
+            .await;
+
+        let token = test::read_body_json::<SigninResponse, _>(signin_res).await.token;
+
+        let complete_uri = format!("/api/v1/user/lessons/{}/complete", video_lesson.id);
+
+        let res = test::TestRequest::put()
+            .append_header(("Authorization", token.clone()))
+            .uri(&complete_uri)
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 403);
+
+        purchase_course(&mut pool.acquire().await.unwrap(), course_uuid, user_uuid, None, None).await.unwrap();
+
+        // 3. Completing one of two lessons is 50%
+        let res = test::TestRequest::put()
+            .append_header(("Authorization", token.clone()))
+            .uri(&complete_uri)
+            .send_request(&app)
+            .await;
+
+        assert!(res.status().is_success());
+
+        let progress: LessonProgressResponse = test::read_body_json(res).await;
+        assert!(progress.completed);
+
+        let res = test::TestRequest::get()
+            .append_header(("Authorization", token.clone()))
+            .uri("/api/v1/user/purchases")
+            .send_request(&app)
+            .await;
+
+        let purchases: Vec<PurchaseWithProgress> = test::read_body_json(res).await;
+        assert_eq!(purchases.len(), 1);
+        assert_eq!(purchases[0].completion_percentage, 50.0);
+
+        // 4. Positions are only tracked for video lessons
+        let res = test::TestRequest::put()
+            .set_json(LessonPositionInput { position_seconds: 42 })
+            .append_header(("Authorization", token.clone()))
+            .uri(&format!("/api/v1/user/lessons/{}/position", text_lesson.id))
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 400);
+
+        let res = test::TestRequest::put()
+            .set_json(LessonPositionInput { position_seconds: 42 })
+            .append_header(("Authorization", token.clone()))
+            .uri(&format!("/api/v1/user/lessons/{}/position", video_lesson.id))
+            .send_request(&app)
+            .await;
+
+        let progress: LessonProgressResponse = test::read_body_json(res).await;
+        assert_eq!(progress.last_position_seconds, 42);
+        assert!(progress.completed);
+
+        // 5. Marking it incomplete again drops back to 0%
+        let res = test::TestRequest::delete()
+            .append_header(("Authorization", token.clone()))
+            .uri(&complete_uri)
+            .send_request(&app)
+            .await;
+
+        let progress: LessonProgressResponse = test::read_body_json(res).await;
+        assert!(!progress.completed);
+
+        let res = test::TestRequest::get()
+            .append_header(("Authorization", token))
+            .uri("/api/v1/user/purchases")
+            .send_request(&app)
+            .await;
+
+        let purchases: Vec<PurchaseWithProgress> = test::read_body_json(res).await;
+        assert_eq!(purchases[0].completion_percentage, 0.0);
+
+        // Cleanup
+        sqlx::query("DELETE FROM lesson_progress_table WHERE user_id = $1")
+            .bind(user_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM purchases_table WHERE course_id = $1")
+            .bind(course_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM course_table WHERE id = $1")
+            .bind(course_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM user_table WHERE id = $1")
+            .bind(user_uuid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM admin_table WHERE email = $1")
+            .bind("admin_progress@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
@@ -1,27 +1,45 @@
 use std::str::FromStr;
 
-use actix_web::{get, post, web::{self, Json}, HttpMessage, HttpRequest, HttpResponse, Responder};
+use actix_web::{get, post, web, HttpResponse, Responder, ResponseError};
 use chrono::{Utc, Duration};
-use jsonwebtoken::{encode, EncodingKey, Header};
 use sqlx::types::{Uuid};
-use crate::{errors::{CustomError}, models::{purchase::get_user_purchases, user::{check_user_exists, create_user, get_user_id_by_email, retrieve_password}}, schema::{user::CreateUser, EmailAndPassword, JWTClaims, SigninResponse, SignupResponse, StructWithEmail}, utils::{hash_password, verify_password}, GlobalState};
+use crate::{errors::ApiError, extractors::{principal::AuthUser, validated_json::ValidatedJson}, mailer::Mail, models::{email_verification, password_reset, progress::get_user_purchases_with_progress, role::{assign_role, get_principal_roles, roles}, session::{self, USER_SESSION}, user::{check_user_exists, create_user, get_user_by_email, get_user_email_by_id, get_user_id_by_email, is_user_verified}}, schema::{user::{CreateUser, VerifyEmailQuery}, EmailAndPassword, EmailRequest, MessageResponse, RefreshTokenRequest, ResetPasswordRequest, SigninResponse, SignupResponse}, utils::{create_access_token, generate_token, hash_password, hash_token, verify_password, EMAIL_VERIFICATION_TOKEN_TTL_HOURS, PASSWORD_RESET_TOKEN_TTL_MINUTES}, GlobalState};
+
+/// Issues a fresh verification token and mails the link to the user.
+async fn send_verification_mail(data:&GlobalState, user_uuid:Uuid, email:&str) -> Result<(), ApiError>{
+
+    let token = generate_token();
+    let expiry = Utc::now() + Duration::hours(EMAIL_VERIFICATION_TOKEN_TTL_HOURS);
+
+    email_verification::create_verification_token(&data.pool, user_uuid, &hash_token(&token), expiry).await?;
+
+    let mail = Mail{
+        to: email.to_string(),
+        subject: "Verify your email".to_string(),
+        body: format!("Confirm your email by opening /api/v1/user/verify?token={}", token),
+    };
+
+    data.mailer.send(&mail)?;
+
+    Ok(())
+}
 
 #[post("/signup")]
-async fn signup_user(data:web::Data<GlobalState>, user:Json<CreateUser>) -> impl Responder{
+async fn signup_user(data:web::Data<GlobalState>, user:ValidatedJson<CreateUser>) -> impl Responder{
     let user_exists = check_user_exists(&data.pool, &user.email).await;
 
     if let Err(e) = user_exists{
-        return HttpResponse::InternalServerError().json(e);
+        return ApiError::from(e).error_response();
     }
 
-    if user_exists.unwrap() == true{
-        return HttpResponse::BadRequest().json(CustomError{error:"User exists already with this email".to_string()});
+    if user_exists.unwrap(){
+        return ApiError::conflict("email_taken", "User exists already with this email").error_response();
     }
 
     let password_hash = hash_password(&user.password);
 
     if let Err(_e) = password_hash{
-        return HttpResponse::InternalServerError().json(CustomError{error:"Something went wrong !".to_string()});
+        return ApiError::internal("Something went wrong !").error_response();
     }
 
     let user_meta = CreateUser{
@@ -32,89 +50,284 @@ async fn signup_user(data:web::Data<GlobalState>, user:Json<CreateUser>) -> impl
 
     let signup_result = create_user(&data.pool, user_meta).await;
 
-    match signup_result {
-        Ok(res) => HttpResponse::Ok().json(SignupResponse{message:String::from("Signed up successfully"),id: res}),
-        Err(e) => HttpResponse::BadGateway().json(e),
+    if let Err(e) = signup_result{
+        return ApiError::from(e).error_response();
+    }
+
+    let user_id = signup_result.unwrap();
+    data.metrics.record_signup(USER_SESSION);
+
+    let user_uuid = Uuid::from_str(&user_id);
+
+    if user_uuid.is_err(){
+        return ApiError::internal("Internal Error").error_response();
+    }
+
+    let user_uuid = user_uuid.unwrap();
+
+    let assigned = assign_role(&data.pool, user_uuid, USER_SESSION, roles::LEARNER).await;
+
+    if let Err(e) = assigned{
+        return ApiError::from(e).error_response();
+    }
+
+    // the account is saved by now, a mail that didn't go out can be asked for again at /verify/resend
+    if let Err(e) = send_verification_mail(&data, user_uuid, &user.email).await{
+        tracing::warn!(error = %e, "verification mail not sent");
+    }
+
+    HttpResponse::Ok().json(SignupResponse{message:String::from("Signed up successfully"),id: user_id})
+}
+
+#[get("/verify")]
+async fn verify_user_email(data:web::Data<GlobalState>, query:web::Query<VerifyEmailQuery>) -> impl Responder {
+
+    let verified = email_verification::verify_email_with_token(&data.pool, &hash_token(&query.token)).await;
+
+    match verified {
+        Ok(true) => HttpResponse::Ok().json(MessageResponse{message:"Email verified successfully".to_string()}),
+        Ok(false) => ApiError::bad_request("invalid_verification_token", "Invalid or expired verification token").error_response(),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[post("/verify/resend")]
+async fn resend_verification_email(data:web::Data<GlobalState>, body:ValidatedJson<EmailRequest>) -> impl Responder {
+
+    let pool = &data.pool;
+
+    // same answer whatever the account's state, so emails can't be enumerated
+    let response = MessageResponse{message:"If the account exists and is not verified yet, a verification link has been sent".to_string()};
+
+    let user_exists = check_user_exists(pool, &body.email).await;
+
+    if let Err(e) = user_exists{
+        return ApiError::from(e).error_response();
+    }
+
+    if !user_exists.unwrap(){
+        return HttpResponse::Ok().json(response);
+    }
+
+    let user_id = get_user_id_by_email(pool, &body.email).await;
+
+    if let Err(e) = user_id{
+        return ApiError::from(e).error_response();
+    }
+
+    let user_uuid = Uuid::from_str(&user_id.unwrap());
+
+    if user_uuid.is_err(){
+        return ApiError::internal("Internal Error").error_response();
+    }
+
+    let user_uuid = user_uuid.unwrap();
+
+    let is_verified = is_user_verified(pool, user_uuid).await;
+
+    if let Err(e) = is_verified{
+        return ApiError::from(e).error_response();
+    }
+
+    if is_verified.unwrap(){
+        return HttpResponse::Ok().json(response);
+    }
+
+    match send_verification_mail(&data, user_uuid, &body.email).await {
+        Ok(()) => HttpResponse::Ok().json(response),
+        Err(e) => e.error_response(),
     }
 }
 
 #[post("/signin")]
-async fn signin_user(data:web::Data<GlobalState>, user_data:web::Json<EmailAndPassword>) -> impl Responder {
+async fn signin_user(data:web::Data<GlobalState>, user_data:ValidatedJson<EmailAndPassword>) -> impl Responder {
 
     let user_exists = check_user_exists(&data.pool, &user_data.email).await;
 
     if let Err(e) = user_exists{
-        return HttpResponse::InternalServerError().json(e);
+        return ApiError::from(e).error_response();
     }
 
     // throw when user not found
-    if user_exists.unwrap() == false {
-        return HttpResponse::BadRequest().json(CustomError{error:"Signup first".to_string()});
+    if !user_exists.unwrap() {
+        data.metrics.record_signin(USER_SESSION, false);
+        return ApiError::unauthorized("invalid_credentials", "Signup first").error_response();
     }
 
     let pool = &data.pool;
-    let password_hash_res = retrieve_password(pool, &user_data.email).await;
+    let user_res = get_user_by_email(pool, &user_data.email).await;
+
+    if let Err(e) = user_res{
+        return ApiError::from(e).error_response();
+    }
+
+    let user = user_res.unwrap();
+
+    if verify_password(&user_data.password, &user.password).is_err(){
+        data.metrics.record_signin(USER_SESSION, false);
+        return ApiError::unauthorized("invalid_credentials", "Enter Valid Password").error_response();
+    }
 
-    if let Err(e) = password_hash_res{
-        return HttpResponse::InternalServerError().json(e);
+    let user_uuid = Uuid::from_str(&user.id);
+
+    if user_uuid.is_err(){
+        return ApiError::internal("Internal Error").error_response();
+    }
+
+    let user_uuid = user_uuid.unwrap();
+
+    let user_roles = get_principal_roles(pool, user_uuid, USER_SESSION).await;
+
+    if let Err(e) = user_roles{
+        return ApiError::from(e).error_response();
     }
 
-    let hash = password_hash_res.unwrap();
-    let is_valid = verify_password(&user_data.password, &hash);
+    let refresh_token = generate_token();
+    let refresh_expiry = Utc::now() + Duration::days(data.auth.refresh_token_ttl_days);
 
-    let jwt_secret = std::env::var("USER_JWT_PASSWORD").unwrap();
+    let session_id = session::create_session(pool, user_uuid, USER_SESSION, &hash_token(&refresh_token), refresh_expiry).await;
 
-    let tomorrow = Utc::now() + Duration::days(1);
+    if let Err(e) = session_id{
+        return ApiError::from(e).error_response();
+    }
+
+    let token = create_access_token(&data.auth, user_uuid, &user.email, &session_id.unwrap(), USER_SESSION, user_roles.unwrap());
+
+    match token {
+        Ok(token) => {
+            data.metrics.record_signin(USER_SESSION, true);
+            HttpResponse::Ok().json(SigninResponse{message:String::from("Signined in Successfully"), token, refresh_token})
+        },
+        Err(_) => ApiError::internal("Internal Error").error_response(),
+    }
+
+}
 
-    let claims = JWTClaims{
-        sub: user_data.email.clone(),
-        exp: tomorrow.timestamp() as usize
+#[post("/refresh")]
+async fn refresh_user_token(data:web::Data<GlobalState>, body:ValidatedJson<RefreshTokenRequest>) -> impl Responder {
+
+    let pool = &data.pool;
+
+    let refresh_token = generate_token();
+    let refresh_expiry = Utc::now() + Duration::days(data.auth.refresh_token_ttl_days);
+
+    let rotated = session::rotate_refresh_token(pool, USER_SESSION, &hash_token(&body.refresh_token), &hash_token(&refresh_token), refresh_expiry).await;
+
+    if let Err(e) = rotated{
+        return ApiError::from(e).error_response();
+    }
+
+    let session = match rotated.unwrap() {
+        Some(session) => session,
+        None => return ApiError::unauthorized("invalid_refresh_token", "Invalid refresh token").error_response(),
     };
 
-    let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(jwt_secret.as_bytes()));
+    let email = get_user_email_by_id(pool, session.owner_id).await;
 
-    if let Err(_) = token {
-        return HttpResponse::InternalServerError().json(CustomError{error:"Internal Error".to_string()});
+    if let Err(e) = email{
+        return ApiError::from(e).error_response();
     }
 
-    match is_valid {
-        Ok(()) => HttpResponse::Ok().json(SigninResponse{message:String::from("Signined in Successfully".to_string()), token:token.unwrap()}),
-        Err(_) => HttpResponse::BadRequest().json(CustomError{error:"Enter Valid Password".to_string()}) 
+    let user_roles = get_principal_roles(pool, session.owner_id, USER_SESSION).await;
+
+    if let Err(e) = user_roles{
+        return ApiError::from(e).error_response();
     }
 
+    let token = create_access_token(&data.auth, session.owner_id, &email.unwrap(), &session.id, USER_SESSION, user_roles.unwrap());
+
+    match token {
+        Ok(token) => HttpResponse::Ok().json(SigninResponse{message:String::from("Token refreshed"), token, refresh_token}),
+        Err(_) => ApiError::internal("Internal Error").error_response(),
+    }
 }
 
-#[get("")]
-async fn user_purchases(data:web::Data<GlobalState>, req:HttpRequest) -> impl Responder{
-    let user_struct = req.extensions().get::<StructWithEmail>().cloned();
+#[post("")]
+async fn logout_user(data:web::Data<GlobalState>, user:AuthUser) -> impl Responder {
 
-    if user_struct.is_none(){
-        return HttpResponse::Forbidden().json(CustomError{error:"email missing".to_string()});
+    match session::revoke_session(&data.pool, user.session_id).await {
+        Ok(()) => HttpResponse::Ok().json(MessageResponse{message:"Logged out successfully".to_string()}),
+        Err(e) => ApiError::from(e).error_response(),
     }
+}
 
-    let user_email = user_struct.unwrap().email;
+#[post("/password/forgot")]
+async fn forgot_user_password(data:web::Data<GlobalState>, body:ValidatedJson<EmailRequest>) -> impl Responder {
 
     let pool = &data.pool;
 
-    let user_id_res = get_user_id_by_email(pool, &user_email).await;
+    // same answer whether the account exists or not, so emails can't be enumerated
+    let response = MessageResponse{message:"If the account exists, a reset token has been sent".to_string()};
+
+    let user_exists = check_user_exists(pool, &body.email).await;
+
+    if let Err(e) = user_exists{
+        return ApiError::from(e).error_response();
+    }
 
-    if let Err(e) = user_id_res{
-        return HttpResponse::Forbidden().json(e);
+    if !user_exists.unwrap(){
+        return HttpResponse::Ok().json(response);
     }
 
-    let user_id = user_id_res.unwrap();
+    let user_id = get_user_id_by_email(pool, &body.email).await;
 
-    let user_uuid_res = Uuid::from_str(&user_id);
+    if let Err(e) = user_id{
+        return ApiError::from(e).error_response();
+    }
+
+    let user_uuid = Uuid::from_str(&user_id.unwrap());
 
-    if user_uuid_res.is_err(){
-        return HttpResponse::Forbidden().json(CustomError{error:"Internal Error".to_string()})
+    if user_uuid.is_err(){
+        return ApiError::internal("Internal Error").error_response();
     }
 
-    let purchases_res = get_user_purchases(pool, user_uuid_res.unwrap()).await;
+    let token = generate_token();
+    let expiry = Utc::now() + Duration::minutes(PASSWORD_RESET_TOKEN_TTL_MINUTES);
+
+    let created = password_reset::create_reset_token(pool, user_uuid.unwrap(), USER_SESSION, &hash_token(&token), expiry).await;
+
+    if let Err(e) = created{
+        return ApiError::from(e).error_response();
+    }
+
+    let mail = Mail{
+        to: body.email.clone(),
+        subject: "Reset your password".to_string(),
+        body: format!("Your password reset token expires in {} minutes: {}", PASSWORD_RESET_TOKEN_TTL_MINUTES, token),
+    };
+
+    match data.mailer.send(&mail) {
+        Ok(()) => HttpResponse::Ok().json(response),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[post("/password/reset")]
+async fn reset_user_password(data:web::Data<GlobalState>, body:ValidatedJson<ResetPasswordRequest>) -> impl Responder {
+
+    let password_hash = hash_password(&body.password);
+
+    if let Err(_e) = password_hash{
+        return ApiError::internal("Something went wrong !").error_response();
+    }
+
+    let reset = password_reset::reset_password_with_token(&data.pool, USER_SESSION, &hash_token(&body.token), &password_hash.unwrap()).await;
+
+    match reset {
+        Ok(true) => HttpResponse::Ok().json(MessageResponse{message:"Password reset successfully".to_string()}),
+        Ok(false) => ApiError::bad_request("invalid_reset_token", "Invalid or expired reset token").error_response(),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[get("")]
+async fn user_purchases(data:web::Data<GlobalState>, user:AuthUser) -> impl Responder{
+
+    let purchases_res = get_user_purchases_with_progress(&data.pool, user.id).await;
 
     match purchases_res {
         Ok(purchases) => HttpResponse::Ok().json(purchases),
-        Err(e) => HttpResponse::BadRequest().json(e)
+        Err(e) => ApiError::from(e).error_response()
     }
     
 
@@ -123,13 +336,13 @@ async fn user_purchases(data:web::Data<GlobalState>, req:HttpRequest) -> impl Re
 #[cfg(test)]
 mod tests{
 
-    use crate::{models::purchase::Purchase, test_init_app::init};
+    use crate::{errors::ErrorBody, models::purchase::Purchase, test_init_app::{init, last_mail_to, token_from_mail}};
     use actix_web::test;
     use super::*;
 
     #[actix_web::test]
     async fn test_signup_and_signin(){
-        let (app, pool) = init(signup_user).await;
+        let (app, pool) = init().await;
 
         let user = CreateUser{
             email: String::from("vk@gmail.com"),
@@ -174,9 +387,43 @@ mod tests{
 
     }
     
+    #[actix_web::test]
+    async fn test_signup_validation(){
+        let (app, pool) = init().await;
+
+        let user = CreateUser{
+            email: String::from("not-an-email"),
+            name: String::from("   "),
+            password: String::from("short")
+        };
+
+        let res = test::TestRequest::post()
+        .set_json(user)
+        .uri("/api/v1/user/signup")
+        .send_request(&app)
+        .await;
+
+        assert_eq!(res.status().as_u16(), 422);
+
+        // every broken field is reported, not just the first one
+        let res_body:ErrorBody = test::read_body_json(res).await;
+        let fields: Vec<&str> = res_body.details.iter().map(|detail| detail.field.as_str()).collect();
+
+        assert_eq!(res_body.code, "validation_failed");
+        assert_eq!(fields, vec!["email", "name", "password"]);
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM user_table WHERE email = $1")
+            .bind("not-an-email")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(count, 0);
+    }
+
     #[actix_web::test]
     async fn test_invalid_credentials(){
-        let (app, pool) = init(signup_user).await;
+        let (app, pool) = init().await;
 
         let user = CreateUser{
             email: String::from("newone@gmail.com"),
@@ -207,7 +454,7 @@ mod tests{
         .send_request(&app)
         .await;
 
-        let res_body:CustomError = test::read_body_json(res).await;
+        let res_body:ErrorBody = test::read_body_json(res).await;
         assert_eq!(res_body.error, "Enter Valid Password".to_string());
 
         sqlx::query("DELETE FROM user_table WHERE email = $1")
@@ -220,7 +467,7 @@ mod tests{
 
     #[actix_web::test]
     async fn test_signin_with_unused_email(){
-        let (app, _pool) = init(signin_user).await;
+        let (app, _pool) = init().await;
         let json = EmailAndPassword {
             email: "unused@gmail.com".to_string(),
             password: "THERIYATHU".to_string(),
@@ -232,14 +479,14 @@ mod tests{
         .send_request(&app)
         .await;
 
-        let res_body:CustomError = test::read_body_json(res).await;
+        let res_body:ErrorBody = test::read_body_json(res).await;
         assert_eq!(res_body.error, "Signup first".to_string());
     }
 
     #[actix_web::test]
     async fn test_signup_twice(){
 
-        let (app, pool) = init(signup_user).await;
+        let (app, pool) = init().await;
 
         let user = CreateUser{
             email: String::from("twice@gmail.com"),
@@ -273,7 +520,7 @@ mod tests{
 
         assert!(!res.status().is_success());
 
-        let res_body:CustomError = test::read_body_json(res).await;
+        let res_body:ErrorBody = test::read_body_json(res).await;
         assert_eq!(res_body.error, "User exists already with this email".to_string());
 
         sqlx::query("DELETE FROM user_table WHERE email = $1")
@@ -286,7 +533,7 @@ mod tests{
 
     #[actix_web::test]
     async fn test_user_purchases(){
-        let (app, pool) = init(signup_user).await;
+        let (app, pool) = init().await;
 
         let user = CreateUser{
             email: String::from("purchase@test.com"),
@@ -338,25 +585,343 @@ mod tests{
 
     #[actix_web::test]
     async fn test_user_purchases_wo_headers() {
-        let (app,_pool) = init(user_purchases).await;
+        let (app,_pool) = init().await;
     
-        // Method 1: Use call_service instead of send_request
         let req = test::TestRequest::get()
             .uri("/api/v1/user/purchases")
             .to_request();
             
-        let res = test::try_call_service(&app, req).await;
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status().as_u16(), 401);
+
+        let error_body: ErrorBody = test::read_body_json(res).await;
+        assert_eq!(error_body.code, "token_missing");
+    }
 
-        if let Err(e) = res {
-            let a: Option<&CustomError> = e.as_error();
-            assert!(a.is_some());
-            assert_eq!(a.unwrap().error, "Token Not found");
-            return;
-        }
+    #[actix_web::test]
+    async fn test_refresh_token_rotation(){
+        let (app, pool) = init().await;
 
-        panic!("Expected error");
+        let user = CreateUser{
+            email: String::from("refresh@test.com"),
+            name: String::from("Iron Man"),
+            password: String::from("THERIYATHU")
+        };
 
+        let _ = test::TestRequest::post()
+        .set_json(user)
+        .uri("/api/v1/user/signup")
+        .send_request(&app)
+        .await;
+
+        let json = EmailAndPassword {
+            email: "refresh@test.com".to_string(),
+            password: "THERIYATHU".to_string(),
+        };
+
+        let res = test::TestRequest::post()
+        .set_json(json)
+        .uri("/api/v1/user/signin")
+        .send_request(&app)
+        .await;
+
+        let signin_body:SigninResponse = test::read_body_json(res).await;
+
+        let res = test::TestRequest::post()
+        .set_json(RefreshTokenRequest{refresh_token:signin_body.refresh_token.clone()})
+        .uri("/api/v1/user/refresh")
+        .send_request(&app)
+        .await;
+
+        assert!(res.status().is_success());
+
+        let refresh_body:SigninResponse = test::read_body_json(res).await;
+        assert_eq!(refresh_body.message, "Token refreshed");
+        assert_ne!(refresh_body.refresh_token, signin_body.refresh_token);
+
+        // the new access token is usable
+        let res = test::TestRequest::get()
+        .uri("/api/v1/user/purchases")
+        .append_header(("Authorization", refresh_body.token))
+        .send_request(&app)
+        .await;
+
+        assert!(res.status().is_success());
+
+        // a rotated refresh token can't be redeemed twice
+        let res = test::TestRequest::post()
+        .set_json(RefreshTokenRequest{refresh_token:signin_body.refresh_token})
+        .uri("/api/v1/user/refresh")
+        .send_request(&app)
+        .await;
+
+        assert_eq!(res.status().as_u16(), 401);
+
+        let res_body:ErrorBody = test::read_body_json(res).await;
+        assert_eq!(res_body.error, "Invalid refresh token");
+
+        sqlx::query("DELETE FROM sessions_table WHERE owner_id = (SELECT id FROM user_table WHERE email = $1)")
+            .bind("refresh@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM user_table WHERE email = $1")
+            .bind("refresh@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_logout_revokes_session(){
+        let (app, pool) = init().await;
+
+        let user = CreateUser{
+            email: String::from("logout@test.com"),
+            name: String::from("Iron Man"),
+            password: String::from("THERIYATHU")
+        };
+
+        let _ = test::TestRequest::post()
+        .set_json(user)
+        .uri("/api/v1/user/signup")
+        .send_request(&app)
+        .await;
+
+        let json = EmailAndPassword {
+            email: "logout@test.com".to_string(),
+            password: "THERIYATHU".to_string(),
+        };
+
+        let res = test::TestRequest::post()
+        .set_json(json)
+        .uri("/api/v1/user/signin")
+        .send_request(&app)
+        .await;
+
+        let signin_body:SigninResponse = test::read_body_json(res).await;
+
+        let res = test::TestRequest::post()
+        .uri("/api/v1/user/logout")
+        .append_header(("Authorization", signin_body.token.clone()))
+        .send_request(&app)
+        .await;
+
+        assert!(res.status().is_success());
+
+        let logout_body:MessageResponse = test::read_body_json(res).await;
+        assert_eq!(logout_body.message, "Logged out successfully");
+
+        // the access token is dead even though it has not expired yet
+        let req = test::TestRequest::get()
+        .uri("/api/v1/user/purchases")
+        .append_header(("Authorization", signin_body.token))
+        .to_request();
+
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status().as_u16(), 401);
+
+        let error_body: ErrorBody = test::read_body_json(res).await;
+        assert_eq!(error_body.code, "session_revoked");
+
+        // and so is the refresh token
+        let res = test::TestRequest::post()
+        .set_json(RefreshTokenRequest{refresh_token:signin_body.refresh_token})
+        .uri("/api/v1/user/refresh")
+        .send_request(&app)
+        .await;
+
+        assert_eq!(res.status().as_u16(), 401);
+
+        sqlx::query("DELETE FROM sessions_table WHERE owner_id = (SELECT id FROM user_table WHERE email = $1)")
+            .bind("logout@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM user_table WHERE email = $1")
+            .bind("logout@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_password_reset(){
+        let (app, pool) = init().await;
+
+        let user = CreateUser{
+            email: String::from("forgetful@test.com"),
+            name: String::from("Iron Man"),
+            password: String::from("THERIYATHU")
+        };
+
+        let _ = test::TestRequest::post()
+        .set_json(user)
+        .uri("/api/v1/user/signup")
+        .send_request(&app)
+        .await;
+
+        let res = test::TestRequest::post()
+        .set_json(EmailRequest{email:"forgetful@test.com".to_string()})
+        .uri("/api/v1/user/password/forgot")
+        .send_request(&app)
+        .await;
+
+        assert!(res.status().is_success());
+
+        let mail = last_mail_to("forgetful@test.com").expect("reset mail not sent");
+        let token = token_from_mail(&mail);
+
+        let res = test::TestRequest::post()
+        .set_json(ResetPasswordRequest{token:token.clone(), password:"NEWPASSWORD".to_string()})
+        .uri("/api/v1/user/password/reset")
+        .send_request(&app)
+        .await;
+
+        assert!(res.status().is_success());
+
+        let res_body:MessageResponse = test::read_body_json(res).await;
+        assert_eq!(res_body.message, "Password reset successfully");
+
+        // tokens are single use
+        let res = test::TestRequest::post()
+        .set_json(ResetPasswordRequest{token, password:"ANOTHERONE".to_string()})
+        .uri("/api/v1/user/password/reset")
+        .send_request(&app)
+        .await;
+
+        let res_body:ErrorBody = test::read_body_json(res).await;
+        assert_eq!(res_body.error, "Invalid or expired reset token");
+
+        let res = test::TestRequest::post()
+        .set_json(EmailAndPassword{email:"forgetful@test.com".to_string(), password:"THERIYATHU".to_string()})
+        .uri("/api/v1/user/signin")
+        .send_request(&app)
+        .await;
+
+        let res_body:ErrorBody = test::read_body_json(res).await;
+        assert_eq!(res_body.error, "Enter Valid Password");
+
+        let res = test::TestRequest::post()
+        .set_json(EmailAndPassword{email:"forgetful@test.com".to_string(), password:"NEWPASSWORD".to_string()})
+        .uri("/api/v1/user/signin")
+        .send_request(&app)
+        .await;
+
+        assert!(res.status().is_success());
+
+        sqlx::query("DELETE FROM password_reset_tokens_table WHERE owner_id = (SELECT id FROM user_table WHERE email = $1)")
+            .bind("forgetful@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM sessions_table WHERE owner_id = (SELECT id FROM user_table WHERE email = $1)")
+            .bind("forgetful@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM user_table WHERE email = $1")
+            .bind("forgetful@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
     }
 
+    #[actix_web::test]
+    async fn test_forgot_password_unknown_email(){
+        let (app, _pool) = init().await;
+
+        let res = test::TestRequest::post()
+        .set_json(EmailRequest{email:"nobody@test.com".to_string()})
+        .uri("/api/v1/user/password/forgot")
+        .send_request(&app)
+        .await;
+
+        assert!(res.status().is_success());
+        assert!(last_mail_to("nobody@test.com").is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_verify_email(){
+        let (app, pool) = init().await;
+
+        let user = CreateUser{
+            email: String::from("verify@test.com"),
+            name: String::from("Iron Man"),
+            password: String::from("THERIYATHU")
+        };
+
+        let _ = test::TestRequest::post()
+        .set_json(user)
+        .uri("/api/v1/user/signup")
+        .send_request(&app)
+        .await;
+
+        let res = test::TestRequest::get()
+        .uri("/api/v1/user/verify?token=notarealtoken")
+        .send_request(&app)
+        .await;
+
+        let res_body:ErrorBody = test::read_body_json(res).await;
+        assert_eq!(res_body.error, "Invalid or expired verification token");
+
+        let first_mail = last_mail_to("verify@test.com").expect("verification mail not sent");
+
+        // a lost mail can be sent again, with a new link
+        let res = test::TestRequest::post()
+        .set_json(EmailRequest{email:"verify@test.com".to_string()})
+        .uri("/api/v1/user/verify/resend")
+        .send_request(&app)
+        .await;
+
+        assert!(res.status().is_success());
+
+        let mail = last_mail_to("verify@test.com").expect("verification mail not resent");
+        assert_ne!(token_from_mail(&mail), token_from_mail(&first_mail));
+
+        let res = test::TestRequest::get()
+        .uri(&format!("/api/v1/user/verify?token={}", token_from_mail(&mail)))
+        .send_request(&app)
+        .await;
+
+        assert!(res.status().is_success());
+
+        let res_body:MessageResponse = test::read_body_json(res).await;
+        assert_eq!(res_body.message, "Email verified successfully");
+
+        let verified_at: Option<chrono::DateTime<Utc>> = sqlx::query_scalar("SELECT email_verified_at FROM user_table WHERE email = $1")
+            .bind("verify@test.com")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        assert!(verified_at.is_some());
+
+        // nothing is sent once the email is verified
+        let res = test::TestRequest::post()
+        .set_json(EmailRequest{email:"verify@test.com".to_string()})
+        .uri("/api/v1/user/verify/resend")
+        .send_request(&app)
+        .await;
+
+        assert!(res.status().is_success());
+        assert_eq!(last_mail_to("verify@test.com").unwrap().body, mail.body);
+
+        sqlx::query("DELETE FROM email_verification_tokens_table WHERE user_id = (SELECT id FROM user_table WHERE email = $1)")
+            .bind("verify@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM user_table WHERE email = $1")
+            .bind("verify@test.com")
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
 
 }
\ No newline at end of file
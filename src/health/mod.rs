@@ -0,0 +1,32 @@
+use std::{sync::atomic::{AtomicBool, Ordering}, time::Duration};
+
+use sqlx::migrate::Migrator;
+
+/// The migrations this build was compiled against, readiness waits until the database has them.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// What `/readyz` needs beyond the pool.
+pub struct Health{
+    draining: AtomicBool,
+    pub check_timeout: Duration,
+}
+
+impl Health{
+    pub fn new(check_timeout:Duration) -> Health {
+        Health{draining: AtomicBool::new(false), check_timeout}
+    }
+
+    /// Flips readiness to failing for good, called once shutdown starts.
+    pub fn start_draining(&self){
+        self.draining.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Version of the newest migration shipped with this build.
+    pub fn expected_migration() -> i64 {
+        MIGRATOR.iter().map(|migration| migration.version).max().unwrap_or_default()
+    }
+}
@@ -0,0 +1,194 @@
+use std::sync::Arc;
+
+use actix_web::{middleware::from_fn, web::{self, scope, ServiceConfig}};
+use mailer::MailSender;
+use middlewares::{auth, request_id};
+use models::role::permissions;
+use payments::PaymentProvider;
+use sqlx::{Pool, Postgres};
+use config::AuthConfig;
+use metrics::Metrics;
+use health::Health;
+
+pub mod config;
+pub mod errors;
+pub mod models;
+pub mod schema;
+pub mod handlers;
+pub mod utils;
+pub mod middlewares;
+pub mod mailer;
+pub mod payments;
+pub mod extractors;
+pub mod telemetry;
+pub mod metrics;
+pub mod health;
+
+#[cfg(test)]
+mod test_init_app;
+
+pub struct GlobalState{
+    pub pool: Pool<Postgres>,
+    pub mailer: Arc<dyn MailSender>,
+    pub payments: Arc<dyn PaymentProvider>,
+    pub auth: AuthConfig,
+    pub refund_window_days: i32,
+    pub metrics: Metrics,
+    // `/metrics` answers 404 when none is configured
+    pub metrics_token: Option<String>,
+    pub health: Health,
+}
+
+/// Mounts the probes, `/metrics` and the whole `/api/v1` tree, the server and the tests both go through this.
+/// Call it as `App::new().configure(|cfg| configure_app(cfg, state.clone()))`.
+pub fn configure_app(cfg:&mut ServiceConfig, state:web::Data<GlobalState>){
+
+    let (json_config, query_config, path_config) = errors::extractor_configs();
+
+    cfg.app_data(state);
+
+    // scraped by Prometheus (with the metrics token) and probed by the load balancer, kept out of /api/v1 so they aren't counted
+    cfg.service(handlers::metrics_handler)
+    .service(handlers::health::liveness_handler)
+    .service(handlers::health::readiness_handler);
+
+    cfg.service(
+        scope("/api/v1")
+        .wrap(from_fn(metrics::track_requests))
+        .wrap(from_fn(request_id::request_id))
+        .app_data(json_config)
+        .app_data(query_config)
+        .app_data(path_config)
+        .service(handlers::hello_world)
+        // place this before /user , else other will get matched
+        .service(
+            scope("/user/purchases")
+            .wrap(from_fn(auth::require(permissions::PURCHASES_READ)))
+            .service(handlers::user::user_purchases)
+        )
+        .service(
+            scope("/user/orders")
+            .wrap(from_fn(auth::require(permissions::PURCHASES_READ)))
+            .service(handlers::order::user_orders)
+        )
+        .service(
+            scope("/user/lessons")
+            .wrap(from_fn(auth::require(permissions::PROGRESS_WRITE)))
+            .service(handlers::progress::complete_lesson_handler)
+            .service(handlers::progress::uncomplete_lesson_handler)
+            .service(handlers::progress::lesson_position_handler)
+        )
+        .service(
+            scope("/user/ratings")
+            .wrap(from_fn(auth::require(permissions::COURSE_RATE)))
+            .service(handlers::course::rate_course_handler)
+        )
+        .service(
+            scope("/user/logout")
+            .wrap(from_fn(auth::authenticated()))
+            .service(handlers::user::logout_user)
+        )
+        .service(
+            scope("/user")
+            .service(handlers::user::signup_user)
+            .service(handlers::user::signin_user)
+            .service(handlers::user::refresh_user_token)
+            .service(handlers::user::forgot_user_password)
+            .service(handlers::user::reset_user_password)
+            .service(handlers::user::verify_user_email)
+            .service(handlers::user::resend_verification_email)
+        )
+        .service(
+            // guard the purchase handler
+            scope("/courses/purchase")
+            .wrap(from_fn(auth::require(permissions::COURSE_PURCHASE)))
+            .service(handlers::course::purchase_course_handler)
+        )
+        .service(
+            //this will match /course/{id} as well as /courses
+            scope("/admin/course")
+            .wrap(from_fn(auth::require(permissions::COURSE_MANAGE)))
+            .service(handlers::admin::create_course_handler)
+            .service(handlers::admin::update_course_handler)
+            .service(handlers::admin::patch_course_handler)
+            .service(handlers::admin::delete_course_handler)
+            .service(handlers::admin::get_all_courses_handler)
+            .service(handlers::content::create_section_handler)
+            .service(handlers::content::get_sections_handler)
+            .service(handlers::content::reorder_sections_handler)
+            .service(handlers::content::update_section_handler)
+            .service(handlers::content::delete_section_handler)
+            .service(handlers::content::create_lesson_handler)
+            .service(handlers::content::reorder_lessons_handler)
+            .service(handlers::content::update_lesson_handler)
+            .service(handlers::content::delete_lesson_handler)
+            .service(handlers::content::update_course_status_handler)
+        )
+        .service(
+            scope("/admin/bundle")
+            .wrap(from_fn(auth::require(permissions::COURSE_MANAGE)))
+            .service(handlers::bundle::create_bundle_handler)
+            .service(handlers::bundle::get_admin_bundles_handler)
+        )
+        .service(
+            scope("/admin/coupons")
+            .wrap(from_fn(auth::require(permissions::COURSE_MANAGE)))
+            .service(handlers::coupon::create_coupon_handler)
+            .service(handlers::coupon::get_coupons_handler)
+        )
+        .service(
+            scope("/admin/logout")
+            .wrap(from_fn(auth::authenticated()))
+            .service(handlers::admin::logout_admin)
+        )
+        .service(
+            scope("/admin/users")
+            .wrap(from_fn(auth::require(permissions::SESSION_REVOKE)))
+            .service(handlers::admin::force_logout_user)
+        )
+        .service(
+            scope("/admin/admins")
+            .wrap(from_fn(auth::require(permissions::SESSION_REVOKE)))
+            .service(handlers::admin::force_logout_admin)
+        )
+        .service(
+            scope("/admin/purchases")
+            .wrap(from_fn(auth::require(permissions::ORDER_REFUND)))
+            .service(handlers::order::refund_purchase_handler)
+        )
+        .service(
+            scope("/admin/roles")
+            .wrap(from_fn(auth::require(permissions::ROLES_MANAGE)))
+            .service(handlers::admin::assign_role_handler)
+            .service(handlers::admin::unassign_role_handler)
+        )
+        .service(
+            scope("/admin")
+            .service(handlers::admin::signup_admin)
+            .service(handlers::admin::signin_admin)
+            .service(handlers::admin::refresh_admin_token)
+            .service(handlers::admin::forgot_admin_password)
+            .service(handlers::admin::reset_admin_password)
+        )
+        .service(
+            scope("/courses")
+            .service(handlers::course::get_all_courses_handler)
+            .service(handlers::course::search_courses_handler)
+            .service(handlers::course::get_course_detail_handler)
+            .service(handlers::content::get_course_content_handler)
+        )
+        .service(
+            scope("/bundles/purchase")
+            .wrap(from_fn(auth::require(permissions::COURSE_PURCHASE)))
+            .service(handlers::bundle::purchase_bundle_handler)
+        )
+        .service(
+            scope("/bundles")
+            .service(handlers::bundle::get_all_bundles_handler)
+        )
+        .service(
+            scope("/payments")
+            .service(handlers::order::payment_webhook_handler)
+        )
+    );
+}
@@ -0,0 +1,61 @@
+use std::{fs::OpenOptions, io::Write, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::CustomError;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mail{
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Anything able to deliver a transactional mail, swap in a real provider by implementing this.
+pub trait MailSender: Send + Sync{
+    fn send(&self, mail:&Mail) -> Result<(), CustomError>;
+}
+
+/// Logs every mail without delivering it, handy when running locally. Bodies carry
+/// sign-in tokens, so they are only logged, at debug level, when `log_body` is set.
+pub struct LogMailSender{
+    pub log_body: bool,
+}
+
+impl MailSender for LogMailSender{
+    fn send(&self, mail:&Mail) -> Result<(), CustomError> {
+        tracing::info!(to = %mail.to, subject = %mail.subject, "mail sent");
+
+        if self.log_body{
+            tracing::debug!(to = %mail.to, body = %mail.body, "mail body");
+        }
+
+        Ok(())
+    }
+}
+
+/// Appends every mail as a JSON line to an outbox file, so flows can be tested offline.
+pub struct FileMailSender{
+    pub path: PathBuf,
+}
+
+impl MailSender for FileMailSender{
+    fn send(&self, mail:&Mail) -> Result<(), CustomError> {
+
+        let mut line = serde_json::to_string(mail)
+        .map_err(|_e|CustomError{error:"Error while sending mail".to_string()})?;
+
+        line.push('\n');
+
+        let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&self.path)
+        .map_err(|_e|CustomError{error:"Error while sending mail".to_string()})?;
+
+        file.write_all(line.as_bytes())
+        .map_err(|_e|CustomError{error:"Error while sending mail".to_string()})?;
+
+        Ok(())
+    }
+}
@@ -1,94 +1,105 @@
-use actix_web::{middleware::from_fn, web::{self, scope}, App, HttpServer};
-use errors::AppError;
+use std::{sync::Arc, time::Duration};
+
+use actix_web::{dev::ServerHandle, rt::time::sleep, web, App, HttpServer};
+use course_selling::{config::AppConfig, configure_app, errors::AppError, health::Health, mailer::{FileMailSender, LogMailSender, MailSender}, metrics::Metrics, payments::{MockPaymentProvider, PaymentProvider}, telemetry, GlobalState};
 use dotenv::dotenv;
-use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
 
-mod errors;
-mod models;
-mod schema;
-mod handlers;
-mod utils;
-mod middlewares;
+fn main() -> Result<(), AppError> {
 
-#[cfg(test)]
-mod test_init_app;
+    dotenv().ok();
 
-struct GlobalState{
-    pool: Pool<Postgres>
-}
+    let config = AppConfig::load().map_err(AppError::Config)?;
 
-#[actix_web::main]
-async fn main() -> Result<(), AppError> {
+    // set up before the runtime starts, the trace exporter blocks while sending
+    let telemetry = telemetry::init(&config.log).map_err(|e| AppError::Config(vec![e]))?;
 
-    dotenv().ok();
+    let result = actix_web::rt::System::new().block_on(serve(config));
+
+    telemetry.shutdown();
 
-    let address = "127.0.0.1:8080";
-    let database_url = std::env::var("DATABASE_URL").expect("DATABASE URL must be set");
+    result
+}
 
-    std::env::var("USER_JWT_PASSWORD").expect("USER_JWT_PASSWORD must be set");
-    std::env::var("ADMIN_JWT_PASSWORD").expect("ADMIN_JWT_PASSWORD must be set");
+async fn serve(config:AppConfig) -> Result<(), AppError> {
 
-    let pool = PgPoolOptions::new()
-    .max_connections(5)
-    .connect(&database_url)
+    let pool = config.database.connect()
     .await
     .map_err(|_e| AppError::DbConnect)?;
 
-    let global_state = GlobalState{pool};
+    // write mails to an outbox file when configured, else just log them
+    let mailer: Arc<dyn MailSender> = match &config.mail.outbox_path {
+        Some(path) => Arc::new(FileMailSender{path: path.clone()}),
+        None => {
+            tracing::warn!("no mail delivery configured, mails are only logged and never reach anyone");
+            Arc::new(LogMailSender{log_body: config.mail.log_body})
+        },
+    };
 
-    let app_data = web::Data::new(global_state);
+    // only the mock provider exists for now, its webhooks are signed with this secret
+    let payments: Arc<dyn PaymentProvider> = Arc::new(MockPaymentProvider{secret: config.payments.webhook_secret.clone()});
+
+    let global_state = GlobalState{
+        pool,
+        mailer,
+        payments,
+        auth: config.auth.clone(),
+        refund_window_days: config.payments.refund_window_days,
+        metrics: Metrics::new(&config.payments.currency),
+        metrics_token: config.metrics.token.clone(),
+        health: Health::new(Duration::from_millis(config.database.health_check_timeout_ms)),
+    };
 
-    println!("The Server is running at PORT : 8080");
+    let app_data = web::Data::new(global_state);
+    let state = app_data.clone();
+    let cors_config = config.cors.clone();
 
-    HttpServer::new(
+    let mut server = HttpServer::new(
         move||{
             App::new()
-            .service(
-                scope("/api/v1")
-                .app_data(app_data.clone())
-                .service(handlers::hello_world)
-                // place this before /user , else other will get matched
-                .service(
-                    scope("/user/purchases")
-                    .wrap(from_fn(middlewares::user::user_middleware))
-                    .service(handlers::user::user_purchases)
-                )
-                .service(
-                    scope("/user")
-                    .service(handlers::user::signup_user)
-                    .service(handlers::user::signin_user)
-                )
-                .service(
-                    // guard the purchase handler
-                    scope("/courses/purchase")
-                    .wrap(from_fn(middlewares::user::user_middleware))
-                    .service(handlers::course::purchase_course_handler)
-                )
-                .service(
-                    //this will match /course/{id} as well as /courses
-                    scope("/admin/course")
-                    .wrap(from_fn(middlewares::admin::admin_middleware))
-                    .service(handlers::admin::create_course_handler)
-                    .service(handlers::admin::update_course_handler)
-                    .service(handlers::admin::get_all_courses_handler)
-                )
-                .service(
-                    scope("/admin")
-                    .service(handlers::admin::signup_admin)
-                    .service(handlers::admin::signin_admin)
-                )
-                .service(
-                    scope("/courses")
-                    .service(handlers::course::get_all_courses_handler)
-                )
-            )
+            .wrap(cors_config.middleware())
+            .configure(|cfg| configure_app(cfg, app_data.clone()))
         }
-    ).bind(address)
+    );
+
+    if let Some(workers) = config.server.workers{
+        server = server.workers(workers);
+    }
+
+    tracing::info!("The Server is running at {}:{}", config.server.host, config.server.port);
+
+    // signals are handled in drain_on_shutdown, so readiness can fail before connections close
+    let server = server.disable_signals()
+    .bind(config.server.address())
     .map_err(|_e|AppError::SocketBind)?
-    .run()
-    .await
+    .run();
+
+    let drain = Duration::from_secs(config.server.shutdown_drain_secs);
+    actix_web::rt::spawn(drain_on_shutdown(server.handle(), state, drain));
+
+    server.await
     .map_err(|_e|AppError::ServerStart)?;
 
     Ok(())
-    
+}
+
+/// On SIGINT or SIGTERM `/readyz` starts failing, after `drain` the server finishes
+/// the requests in flight and stops.
+async fn drain_on_shutdown(handle:ServerHandle, state:web::Data<GlobalState>, drain:Duration){
+
+    let terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate());
+
+    match terminate {
+        Ok(mut terminate) => {
+            futures_util::future::select(Box::pin(tokio::signal::ctrl_c()), Box::pin(terminate.recv())).await;
+        },
+        Err(_) => {
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+
+    tracing::info!("Shutting down, draining for {}s", drain.as_secs());
+    state.health.start_draining();
+
+    sleep(drain).await;
+    handle.stop(true).await;
 }
@@ -0,0 +1,146 @@
+use std::time::Instant;
+
+use actix_web::{body::BoxBody, dev::{ServiceRequest, ServiceResponse}, middleware::Next, web, Error};
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder};
+use sqlx::{Pool, Postgres};
+
+use crate::{models::order::Order, GlobalState};
+
+/// Everything `/metrics` exposes. Each app owns its registry, so parallel tests don't share counts.
+pub struct Metrics{
+    registry: Registry,
+    currency: String,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    db_pool_connections: IntGaugeVec,
+    db_pool_max_connections: IntGauge,
+    signups: IntCounterVec,
+    signins: IntCounterVec,
+    purchases: IntCounterVec,
+    revenue: IntCounterVec,
+}
+
+impl Metrics{
+
+    /// Revenue is reported in `currency`, the one every price in the store is in.
+    pub fn new(currency:&str) -> Metrics {
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("courser_http_requests_total", "HTTP requests by route and status"),
+            &["method", "route", "status"]
+        ).unwrap();
+
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new("courser_http_request_duration_seconds", "HTTP request latency by route"),
+            &["method", "route"]
+        ).unwrap();
+
+        let db_pool_connections = IntGaugeVec::new(
+            Opts::new("courser_db_pool_connections", "Database pool connections by state"),
+            &["state"]
+        ).unwrap();
+
+        let db_pool_max_connections = IntGauge::new("courser_db_pool_max_connections", "Size limit of the database pool").unwrap();
+
+        let signups = IntCounterVec::new(
+            Opts::new("courser_signups_total", "Accounts created"),
+            &["kind"]
+        ).unwrap();
+
+        let signins = IntCounterVec::new(
+            Opts::new("courser_signins_total", "Sign in attempts by outcome"),
+            &["kind", "outcome"]
+        ).unwrap();
+
+        let purchases = IntCounterVec::new(
+            Opts::new("courser_purchases_total", "Orders fulfilled"),
+            &["item"]
+        ).unwrap();
+
+        let revenue = IntCounterVec::new(
+            Opts::new("courser_revenue_total", "Amount paid for fulfilled orders"),
+            &["currency"]
+        ).unwrap();
+
+        let registry = Registry::new();
+
+        // the names are fixed and distinct, registering can't fail
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_request_duration.clone())).unwrap();
+        registry.register(Box::new(db_pool_connections.clone())).unwrap();
+        registry.register(Box::new(db_pool_max_connections.clone())).unwrap();
+        registry.register(Box::new(signups.clone())).unwrap();
+        registry.register(Box::new(signins.clone())).unwrap();
+        registry.register(Box::new(purchases.clone())).unwrap();
+        registry.register(Box::new(revenue.clone())).unwrap();
+
+        Metrics{
+            registry,
+            currency: currency.to_string(),
+            http_requests,
+            http_request_duration,
+            db_pool_connections,
+            db_pool_max_connections,
+            signups,
+            signins,
+            purchases,
+            revenue,
+        }
+    }
+
+    /// `kind` is the session kind, USER_SESSION or ADMIN_SESSION.
+    pub fn record_signup(&self, kind:&str){
+        self.signups.with_label_values(&[kind]).inc();
+    }
+
+    pub fn record_signin(&self, kind:&str, succeeded:bool){
+        let outcome = if succeeded { "succeeded" } else { "failed" };
+        self.signins.with_label_values(&[kind, outcome]).inc();
+    }
+
+    /// Call once an order is paid, free orders count as purchases too.
+    pub fn record_purchase(&self, order:&Order){
+        let item = if order.bundle_id.is_some() { "bundle" } else { "course" };
+
+        self.purchases.with_label_values(&[item]).inc();
+        self.revenue.with_label_values(&[self.currency.as_str()]).inc_by(order.amount.max(0) as u64);
+    }
+
+    /// Samples the pool and renders everything in the Prometheus text format.
+    pub fn render(&self, pool:&Pool<Postgres>) -> Result<String, prometheus::Error> {
+
+        let total = pool.size() as i64;
+        let idle = pool.num_idle() as i64;
+
+        self.db_pool_connections.with_label_values(&["idle"]).set(idle);
+        self.db_pool_connections.with_label_values(&["in_use"]).set(total - idle);
+        self.db_pool_max_connections.set(pool.options().get_max_connections() as i64);
+
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+/// Counts and times every request. Routes are labelled by their pattern, e.g.
+/// `/api/v1/courses/{course_id}`, so ids don't blow up the number of series.
+pub async fn track_requests(req:ServiceRequest, next:Next<BoxBody>) -> Result<ServiceResponse<BoxBody>, Error> {
+
+    let data = req.app_data::<web::Data<GlobalState>>().cloned();
+    let method = req.method().to_string();
+    let started = Instant::now();
+
+    let res = next.call(req).await?;
+
+    if let Some(data) = data{
+
+        let route = res.request().match_pattern().unwrap_or_else(|| "unmatched".to_string());
+        let status = res.status().as_u16().to_string();
+
+        data.metrics.http_requests.with_label_values(&[method.as_str(), route.as_str(), status.as_str()]).inc();
+        data.metrics.http_request_duration.with_label_values(&[method.as_str(), route.as_str()]).observe(started.elapsed().as_secs_f64());
+    }
+
+    Ok(res)
+}
@@ -0,0 +1,120 @@
+use std::str::FromStr;
+
+use actix_web::{body::BoxBody, dev::{ServiceRequest, ServiceResponse}, middleware::Next, web, Error, HttpMessage, HttpRequest};
+use futures_util::future::LocalBoxFuture;
+use jsonwebtoken::{decode, DecodingKey, Validation};
+use sqlx::types::Uuid;
+
+use crate::{errors::ApiError, extractors::principal::{AuthAdmin, AuthUser}, models::{role::roles_have_permission, session::{is_session_active, ADMIN_SESSION}}, schema::JWTClaims, GlobalState};
+
+type GuardFuture = LocalBoxFuture<'static, Result<ServiceResponse<BoxBody>, Error>>;
+
+/// Guard for routes that need a signed in principal holding `permission`.
+/// Wrap a scope with `from_fn(auth::require(permissions::COURSE_MANAGE))`.
+pub fn require(permission:&'static str) -> impl Fn(ServiceRequest, Next<BoxBody>) -> GuardFuture {
+    move |req, next| Box::pin(guard(req, next, Some(permission)))
+}
+
+/// Guard for routes any signed in principal may call, e.g. logout.
+pub fn authenticated() -> impl Fn(ServiceRequest, Next<BoxBody>) -> GuardFuture {
+    |req, next| Box::pin(guard(req, next, None))
+}
+
+/// For public routes that show more to a signed in caller.
+/// Any missing, invalid or revoked token is treated as anonymous.
+pub async fn optional_claims(req:&HttpRequest, data:&GlobalState) -> Option<JWTClaims> {
+
+    let token = req.headers().get("Authorization")?.to_str().ok()?;
+
+    verify_token(data, token).await.ok()
+}
+
+/// Decodes the token and makes sure its session is still alive.
+async fn verify_token(data:&GlobalState, token:&str) -> Result<JWTClaims, Error> {
+
+    let decoded = decode::<JWTClaims>(token, &DecodingKey::from_secret(data.auth.jwt_secret.as_bytes()), &Validation::default());
+
+    if decoded.is_err(){
+        return Err(ApiError::unauthorized("invalid_token", "Invalid token").into());
+    }
+
+    let claims = decoded.unwrap().claims;
+
+    let session_uuid = Uuid::from_str(&claims.sid);
+
+    if session_uuid.is_err() || Uuid::from_str(&claims.uid).is_err(){
+        return Err(ApiError::unauthorized("invalid_token", "Invalid token").into());
+    }
+
+    // reject tokens whose session was logged out or force revoked
+    let is_active = is_session_active(&data.pool, session_uuid.unwrap(), &claims.kind).await
+    .map_err(ApiError::from)?;
+
+    if !is_active{
+        return Err(ApiError::unauthorized("session_revoked", "Session revoked").into());
+    }
+
+    Ok(claims)
+}
+
+async fn guard(
+    req:ServiceRequest,
+    next: Next<BoxBody>,
+    permission: Option<&'static str>
+) -> Result<ServiceResponse<BoxBody>, Error>{
+
+    // answered here rather than bubbled up, so the rejection still carries the request id
+    if let Err(e) = authorize(&req, permission).await{
+        return Ok(req.error_response(e));
+    }
+
+    next.call(req).await
+}
+
+/// Checks the token and permission, then hands the principal to the handlers.
+async fn authorize(req:&ServiceRequest, permission:Option<&'static str>) -> Result<(), Error>{
+
+    let authorization = req.headers().get("Authorization");
+
+    if authorization.is_none(){
+        return Err(ApiError::unauthorized("token_missing", "token missing").into());
+    }
+
+    let token = authorization.unwrap().to_str();
+
+    if token.is_err() {
+        return Err(ApiError::unauthorized("invalid_token", "Invalid token").into());
+    }
+
+    let data = req.app_data::<web::Data<GlobalState>>().cloned();
+
+    if data.is_none(){
+        return Err(ApiError::internal("Internal Error").into());
+    }
+
+    let data = data.unwrap();
+
+    let claims = verify_token(&data, token.unwrap()).await?;
+
+    if let Some(permission) = permission {
+        let is_allowed = roles_have_permission(&data.pool, &claims.roles, permission).await
+        .map_err(ApiError::from)?;
+
+        if !is_allowed{
+            return Err(ApiError::forbidden("missing_permission", format!("Missing permission: {}", permission)).into());
+        }
+    }
+
+    // both ids were checked in verify_token
+    let id = Uuid::from_str(&claims.uid).unwrap();
+    let session_id = Uuid::from_str(&claims.sid).unwrap();
+
+    // handlers pick this up as an `AuthUser` or `AuthAdmin` argument
+    if claims.kind == ADMIN_SESSION {
+        req.extensions_mut().insert(AuthAdmin{id, email:claims.sub, roles:claims.roles, session_id});
+    } else {
+        req.extensions_mut().insert(AuthUser{id, email:claims.sub, roles:claims.roles, session_id});
+    }
+
+    Ok(())
+}
@@ -1,2 +1,2 @@
-pub mod admin;
-pub mod user;
\ No newline at end of file
+pub mod auth;
+pub mod request_id;
@@ -0,0 +1,105 @@
+use actix_web::{body::BoxBody, dev::{ServiceRequest, ServiceResponse}, http::header::{HeaderName, HeaderValue}, middleware::Next, Error, HttpMessage};
+use tracing::{field, Instrument};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Id of the request being handled, None outside of a request.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(|request_id| request_id.clone()).ok()
+}
+
+/// The id of the request, also available to handlers through `req.extensions()`.
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// A caller supplied id is kept so a request can be followed across services,
+/// anything that doesn't look like an id is replaced.
+fn incoming_request_id(req:&ServiceRequest) -> Option<String> {
+
+    let value = req.headers().get(REQUEST_ID_HEADER)?.to_str().ok()?.trim();
+
+    let valid = !value.is_empty()
+    && value.len() <= 128
+    && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+
+    valid.then(|| value.to_string())
+}
+
+/// Gives every request an id, runs it inside a span carrying that id and echoes it
+/// back in `X-Request-Id`. Error bodies pick it up through `current`.
+pub async fn request_id(req:ServiceRequest, next:Next<BoxBody>) -> Result<ServiceResponse<BoxBody>, Error> {
+
+    let request_id = incoming_request_id(&req).unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    req.extensions_mut().insert(RequestId(request_id.clone()));
+
+    let span = tracing::info_span!(
+        "http_request",
+        request_id = %request_id,
+        method = %req.method(),
+        path = %req.path(),
+        status = field::Empty,
+    );
+
+    // the guards answer their own rejections inside this scope, so those bodies get the id too
+    let mut res = REQUEST_ID.scope(request_id.clone(), next.call(req))
+    .instrument(span.clone())
+    .await?;
+
+    span.record("status", res.status().as_u16());
+
+    if let Ok(value) = HeaderValue::from_str(&request_id){
+        res.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test;
+
+    use crate::{errors::ErrorBody, test_init_app::init};
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_request_id() {
+        let (app, _pool) = init().await;
+
+        // a fresh id is generated when the caller has none
+        let res = test::TestRequest::get()
+            .uri("/api/v1/hello")
+            .send_request(&app)
+            .await;
+
+        let generated = res.headers().get(REQUEST_ID_HEADER).unwrap().to_str().unwrap();
+        assert!(Uuid::parse_str(generated).is_ok());
+
+        // the caller's id is kept and ends up in error bodies
+        let res = test::TestRequest::get()
+            .append_header(("X-Request-Id", "upstream-42"))
+            .uri("/api/v1/user/purchases")
+            .send_request(&app)
+            .await;
+
+        assert_eq!(res.status().as_u16(), 401);
+        assert_eq!(res.headers().get(REQUEST_ID_HEADER).unwrap(), "upstream-42");
+
+        let error_body: ErrorBody = test::read_body_json(res).await;
+        assert_eq!(error_body.request_id.as_deref(), Some("upstream-42"));
+
+        // ids that could mess up the logs are replaced
+        let res = test::TestRequest::get()
+            .append_header(("X-Request-Id", "bad id\twith spaces"))
+            .uri("/api/v1/hello")
+            .send_request(&app)
+            .await;
+
+        assert_ne!(res.headers().get(REQUEST_ID_HEADER).unwrap(), "bad id\twith spaces");
+    }
+}
@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
-use sqlx::{Pool, Postgres};
+use sqlx::{types::Uuid, Pool, Postgres};
+use tracing::instrument;
 
-use crate::{errors::CustomError, schema::{admin::CreateAdmin, StructWithId, StructWithVal}};
+use crate::{errors::{CustomError, DbError}, schema::{admin::CreateAdmin, StructWithId, StructWithVal}};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Admin{
@@ -11,7 +12,8 @@ pub struct Admin{
     pub password: String,
 }
 
-pub async fn create_admin(pool:&Pool<Postgres>, admin_meta: CreateAdmin) -> Result<String, CustomError>{
+#[instrument(level = "debug", skip_all, err)]
+pub async fn create_admin(pool:&Pool<Postgres>, admin_meta: CreateAdmin) -> Result<String, DbError>{
 
     let user = sqlx::query_as!(
         StructWithId,
@@ -28,27 +30,29 @@ pub async fn create_admin(pool:&Pool<Postgres>, admin_meta: CreateAdmin) -> Resu
 
     match user {
         Ok(val) => Ok(val.id),
-        Err(_e) => Err(CustomError{error:"Error while creating admin".to_string()})
+        Err(e) => Err(DbError::from_sqlx(&e, "Error while creating admin"))
     }
 }
 
-pub async fn retrieve_admin_password(pool:&Pool<Postgres>, email:&str) -> Result<String, CustomError>{
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_admin_by_email(pool:&Pool<Postgres>, email:&str) -> Result<Admin, CustomError>{
 
     let res = sqlx::query_as!(
-        StructWithVal,
+        Admin,
         r#"
-            SELECT password AS val FROM admin_table
+            SELECT id, name, email, password FROM admin_table
             WHERE email = $1
         "#,
         email
     )
     .fetch_one(pool)
     .await
-    .map_err(|_e|CustomError{error:"Error while retrieving admin password".to_string()})?;
+    .map_err(|_e|CustomError{error:"Error while retrieving admin".to_string()})?;
 
-    Ok(res.val)
+    Ok(res)
 }
 
+#[instrument(level = "debug", skip_all, err)]
 pub async fn check_admin_exists(pool:&Pool<Postgres>, email:&str) -> Result<bool, CustomError>{
 
     let result = sqlx::query_as!(
@@ -69,6 +73,7 @@ pub async fn check_admin_exists(pool:&Pool<Postgres>, email:&str) -> Result<bool
 
 }
 
+#[instrument(level = "debug", skip_all, err)]
 pub async fn get_admin_id_by_email(pool:&Pool<Postgres>, email:&String) -> Result<String, CustomError>{
 
     let result = sqlx::query_as!(
@@ -83,4 +88,21 @@ pub async fn get_admin_id_by_email(pool:&Pool<Postgres>, email:&String) -> Resul
     .map_err(|_|CustomError{error:"Error while fetching admin id".to_string()})?;
 
     Ok(result.id)
-}
\ No newline at end of file
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_admin_email_by_id(pool:&Pool<Postgres>, id:Uuid) -> Result<String, CustomError>{
+
+    let result = sqlx::query_as!(
+        StructWithVal,
+        r#"
+            SELECT email as val FROM admin_table
+            WHERE id = $1
+        "#,
+        id
+    ).fetch_one(pool)
+    .await
+    .map_err(|_|CustomError{error:"Error while fetching admin email".to_string()})?;
+
+    Ok(result.val)
+}
@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Uuid, PgConnection, Pool, Postgres};
+use tracing::instrument;
+
+use crate::errors::{CustomError, DbError};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Bundle{
+    pub id: String,
+    pub title: String,
+    pub price: i32,
+    pub admin_id: String,
+    pub course_ids: Vec<String>,
+    // every course in it is published and not deleted, only then it is listed and sold
+    pub available: bool,
+}
+
+/// A bundle course the buyer does not own yet, with the list price used to split the bundle price.
+pub struct BundleCourse{
+    pub course_id: Uuid,
+    pub price: i32,
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn create_bundle(pool:&Pool<Postgres>, admin_id:Uuid, title:&str, price:i32, course_ids:&[Uuid]) -> Result<Bundle, CustomError>{
+
+    let mut tx = pool.begin().await
+    .map_err(|_e|CustomError{error:"Error while creating the bundle".to_string()})?;
+
+    let bundle_id = sqlx::query_scalar!(
+        r#"
+            INSERT INTO bundles_table (title, price, admin_id)
+            VALUES ($1, $2, $3)
+            RETURNING id
+        "#,
+        title,
+        price,
+        admin_id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_e|CustomError{error:"Error while creating the bundle".to_string()})?;
+
+    sqlx::query!(
+        r#"
+            INSERT INTO bundle_courses_table (bundle_id, course_id)
+            SELECT $1, course_id FROM UNNEST($2::uuid[]) AS course_id
+            ON CONFLICT DO NOTHING
+        "#,
+        bundle_id,
+        course_ids
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e|CustomError::from(DbError::from_sqlx(&e, "Error while creating the bundle")))?;
+
+    tx.commit().await
+    .map_err(|_e|CustomError{error:"Error while creating the bundle".to_string()})?;
+
+    get_bundle(pool, bundle_id).await?
+    .ok_or_else(||CustomError{error:"Error while creating the bundle".to_string()})
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_bundle(pool:&Pool<Postgres>, bundle_id:Uuid) -> Result<Option<Bundle>, CustomError>{
+
+    let result = sqlx::query_as!(
+        Bundle,
+        r#"
+            SELECT b.id, b.title, b.price, b.admin_id,
+            ARRAY_AGG(bc.course_id::text ORDER BY bc.course_id) AS "course_ids!",
+            BOOL_AND(c.status = 'published' AND c.deleted_at IS NULL) AS "available!"
+            FROM bundles_table b
+            JOIN bundle_courses_table bc ON bc.bundle_id = b.id
+            JOIN course_table c ON c.id = bc.course_id
+            WHERE b.id = $1
+            GROUP BY b.id
+        "#,
+        bundle_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching the bundle".to_string()})?;
+
+    Ok(result)
+}
+
+/// The bundles on sale, see `Bundle::available`.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_all_bundles(pool:&Pool<Postgres>) -> Result<Vec<Bundle>, CustomError>{
+
+    let result = sqlx::query_as!(
+        Bundle,
+        r#"
+            SELECT b.id, b.title, b.price, b.admin_id,
+            ARRAY_AGG(bc.course_id::text ORDER BY bc.course_id) AS "course_ids!",
+            BOOL_AND(c.status = 'published' AND c.deleted_at IS NULL) AS "available!"
+            FROM bundles_table b
+            JOIN bundle_courses_table bc ON bc.bundle_id = b.id
+            JOIN course_table c ON c.id = bc.course_id
+            GROUP BY b.id
+            HAVING BOOL_AND(c.status = 'published' AND c.deleted_at IS NULL)
+            ORDER BY b.created_at DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching the bundles".to_string()})?;
+
+    Ok(result)
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_admin_bundles(pool:&Pool<Postgres>, admin_id:Uuid) -> Result<Vec<Bundle>, CustomError>{
+
+    let result = sqlx::query_as!(
+        Bundle,
+        r#"
+            SELECT b.id, b.title, b.price, b.admin_id,
+            ARRAY_AGG(bc.course_id::text ORDER BY bc.course_id) AS "course_ids!",
+            BOOL_AND(c.status = 'published' AND c.deleted_at IS NULL) AS "available!"
+            FROM bundles_table b
+            JOIN bundle_courses_table bc ON bc.bundle_id = b.id
+            JOIN course_table c ON c.id = bc.course_id
+            WHERE b.admin_id = $1
+            GROUP BY b.id
+            ORDER BY b.created_at DESC
+        "#,
+        admin_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching the bundles".to_string()})?;
+
+    Ok(result)
+}
+
+/// Courses of the bundle the user has no active purchase for, leaving out the ones taken off
+/// sale. Takes a connection so the order payment can read and grant them in one transaction.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_unowned_bundle_courses(conn:&mut PgConnection, bundle_id:Uuid, user_id:Uuid) -> Result<Vec<BundleCourse>, CustomError>{
+
+    let result = sqlx::query_as!(
+        BundleCourse,
+        r#"
+            SELECT c.id AS course_id, c.price AS "price!"
+            FROM bundle_courses_table bc
+            JOIN course_table c ON c.id = bc.course_id
+            WHERE bc.bundle_id = $1
+            AND c.status = 'published' AND c.deleted_at IS NULL
+            AND NOT EXISTS(
+                SELECT 1 FROM purchases_table p
+                WHERE p.course_id = c.id AND p.user_id = $2 AND p.revoked_at IS NULL
+            )
+            ORDER BY c.id
+        "#,
+        bundle_id,
+        user_id
+    )
+    .fetch_all(conn)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching the bundle courses".to_string()})?;
+
+    Ok(result)
+}
+
+/// Splits the bundle price over its courses in proportion to their list prices,
+/// the last course takes the rounding so the shares always add up to the bundle price.
+pub fn split_price(price:i32, courses:&[BundleCourse]) -> Vec<i32>{
+
+    let list_total: i64 = courses.iter().map(|course| course.price as i64).sum();
+    let mut shares = Vec::with_capacity(courses.len());
+    let mut assigned = 0;
+
+    for (index, course) in courses.iter().enumerate() {
+        let share = if index + 1 == courses.len() {
+            price - assigned
+        } else if list_total == 0 {
+            price / courses.len() as i32
+        } else {
+            (price as i64 * course.price as i64 / list_total) as i32
+        };
+
+        assigned += share;
+        shares.push(share);
+    }
+
+    shares
+}
@@ -0,0 +1,191 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Uuid, PgConnection, Pool, Postgres};
+use tracing::instrument;
+
+use crate::errors::CustomError;
+
+pub const PERCENT_COUPON: &str = "percent";
+pub const FIXED_COUPON: &str = "fixed";
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Coupon{
+    pub id: String,
+    pub code: String,
+    pub kind: String,
+    pub value: i32,
+    pub course_id: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub max_redemptions: Option<i32>,
+    pub redemptions: i32,
+}
+
+impl Coupon{
+    /// The discount never takes the price below zero or above what it was,
+    /// worked out in i64 so large prices can't overflow.
+    pub fn apply(&self, price:i32) -> i32 {
+        let price = i64::from(price);
+
+        let discount = match self.kind.as_str() {
+            PERCENT_COUPON => price * i64::from(self.value) / 100,
+            _ => i64::from(self.value),
+        };
+
+        // the clamped result always fits back into the i32 it came from
+        (price - discount.clamp(0, price.max(0))) as i32
+    }
+
+    /// Why the coupon can't be used on this course right now, if it can't.
+    pub fn check(&self, course_id:&str) -> Result<(), CustomError> {
+
+        if self.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()){
+            return Err(CustomError{error:"Coupon has expired".to_string()});
+        }
+
+        if self.course_id.as_deref().is_some_and(|coupon_course| coupon_course != course_id){
+            return Err(CustomError{error:"Coupon does not apply to this course".to_string()});
+        }
+
+        if self.max_redemptions.is_some_and(|max_redemptions| self.redemptions >= max_redemptions){
+            return Err(CustomError{error:"Coupon has been fully redeemed".to_string()});
+        }
+
+        Ok(())
+    }
+}
+
+pub struct NewCoupon<'a>{
+    pub code: &'a str,
+    pub kind: &'a str,
+    pub value: i32,
+    pub course_id: Option<Uuid>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub max_redemptions: Option<i32>,
+    pub created_by: Uuid,
+}
+
+/// Returns None when the code is already taken.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn create_coupon(pool:&Pool<Postgres>, coupon:NewCoupon<'_>) -> Result<Option<Coupon>, CustomError>{
+
+    let result = sqlx::query_as!(
+        Coupon,
+        r#"
+            INSERT INTO coupons_table (code, kind, value, course_id, expires_at, max_redemptions, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (code) DO NOTHING
+            RETURNING id, code, kind, value, course_id::text, expires_at, max_redemptions, redemptions
+        "#,
+        coupon.code,
+        coupon.kind,
+        coupon.value,
+        coupon.course_id,
+        coupon.expires_at,
+        coupon.max_redemptions,
+        coupon.created_by
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while creating the coupon".to_string()})?;
+
+    Ok(result)
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_coupon_by_code(pool:&Pool<Postgres>, code:&str) -> Result<Option<Coupon>, CustomError>{
+
+    let result = sqlx::query_as!(
+        Coupon,
+        r#"
+            SELECT id, code, kind, value, course_id::text, expires_at, max_redemptions, redemptions
+            FROM coupons_table
+            WHERE code = $1
+        "#,
+        code
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching the coupon".to_string()})?;
+
+    Ok(result)
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_admin_coupons(pool:&Pool<Postgres>, admin_id:Uuid) -> Result<Vec<Coupon>, CustomError>{
+
+    let result = sqlx::query_as!(
+        Coupon,
+        r#"
+            SELECT id, code, kind, value, course_id::text, expires_at, max_redemptions, redemptions
+            FROM coupons_table
+            WHERE created_by = $1
+            ORDER BY created_at DESC
+        "#,
+        admin_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching the coupons".to_string()})?;
+
+    Ok(result)
+}
+
+/// Takes one redemption, returns false when none are left. Runs on the order's transaction.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn redeem_coupon(conn:&mut PgConnection, coupon_id:Uuid) -> Result<bool, CustomError>{
+
+    let result = sqlx::query!(
+        r#"
+            UPDATE coupons_table SET redemptions = redemptions + 1
+            WHERE id = $1 AND (max_redemptions IS NULL OR redemptions < max_redemptions)
+        "#,
+        coupon_id
+    )
+    .execute(conn)
+    .await
+    .map_err(|_e|CustomError{error:"Error while redeeming the coupon".to_string()})?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Gives back the redemption of an order that was never paid.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn release_coupon(conn:&mut PgConnection, coupon_id:Uuid) -> Result<(), CustomError>{
+
+    sqlx::query!(
+        r#"
+            UPDATE coupons_table SET redemptions = redemptions - 1
+            WHERE id = $1 AND redemptions > 0
+        "#,
+        coupon_id
+    )
+    .execute(conn)
+    .await
+    .map_err(|_e|CustomError{error:"Error while releasing the coupon".to_string()})?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coupon_apply_stays_in_bounds() {
+        let coupon = |kind:&str, value:i32| Coupon {
+            id: String::new(),
+            code: String::new(),
+            kind: kind.to_string(),
+            value,
+            course_id: None,
+            expires_at: None,
+            max_redemptions: None,
+            redemptions: 0,
+        };
+
+        assert_eq!(coupon(PERCENT_COUPON, 25).apply(4000), 3000);
+        assert_eq!(coupon(PERCENT_COUPON, 50).apply(i32::MAX), i32::MAX - i32::MAX / 2);
+        assert_eq!(coupon(FIXED_COUPON, i32::MAX).apply(4000), 0);
+        assert_eq!(coupon(FIXED_COUPON, -500).apply(4000), 4000);
+    }
+}
@@ -1,6 +1,8 @@
+use chrono::{DateTime, Utc};
 use sqlx::{types::{uuid, Uuid}, Pool, Postgres};
+use tracing::instrument;
 
-use crate::{errors::CustomError, schema::{admin::{CreateCourse, UpdateCourse}}};
+use crate::{errors::{CustomError, DbError}, schema::{admin::{CourseResponse, CreateCourse, PatchCourse, UpdateCourse}, user::SearchResult}};
 
 #[derive(Debug)]
 pub struct Course{
@@ -9,41 +11,161 @@ pub struct Course{
     pub image_url: Option<String>,
     pub price: i32,
     pub admin_id: uuid::Uuid,
+    pub category: Option<String>,
+    pub description: Option<String>,
+    pub status: String,
+    // bumped on every edit, sent back to clients as the ETag
+    pub version: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    // set when an admin removed a course that still has buyers
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
-pub async fn create_course(pool:&Pool<Postgres>, course_details:CreateCourse) -> Result<Course, CustomError>{
+impl From<Course> for CourseResponse{
+    fn from(course:Course) -> Self {
+        CourseResponse{
+            id: course.id,
+            admin_id: course.admin_id.to_string(),
+            title: course.title,
+            image_url: course.image_url,
+            price: course.price,
+            category: course.category,
+            description: course.description,
+            status: course.status,
+            version: course.version,
+            created_at: course.created_at,
+            updated_at: course.updated_at,
+        }
+    }
+}
+
+/// Orderings offered by the public catalog, newest first unless asked otherwise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CatalogSort{
+    Newest,
+    PriceAsc,
+    PriceDesc,
+    Popular,
+}
+
+impl CatalogSort{
+    pub fn parse(sort:&str) -> Option<CatalogSort> {
+        match sort {
+            "newest" => Some(CatalogSort::Newest),
+            "price_asc" => Some(CatalogSort::PriceAsc),
+            "price_desc" => Some(CatalogSort::PriceDesc),
+            "popular" => Some(CatalogSort::Popular),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            CatalogSort::Newest => "newest",
+            CatalogSort::PriceAsc => "price_asc",
+            CatalogSort::PriceDesc => "price_desc",
+            CatalogSort::Popular => "popular",
+        }
+    }
+}
+
+/// Where a course is in its lifecycle. Only published courses are listed publicly,
+/// unlisted ones can still be opened and bought through a direct link.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CourseStatus{
+    Draft,
+    Published,
+    Unlisted,
+    Archived,
+}
+
+impl CourseStatus{
+    pub fn parse(status:&str) -> Option<CourseStatus> {
+        match status {
+            "draft" => Some(CourseStatus::Draft),
+            "published" => Some(CourseStatus::Published),
+            "unlisted" => Some(CourseStatus::Unlisted),
+            "archived" => Some(CourseStatus::Archived),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CourseStatus::Draft => "draft",
+            CourseStatus::Published => "published",
+            CourseStatus::Unlisted => "unlisted",
+            CourseStatus::Archived => "archived",
+        }
+    }
+
+    /// The states a course has to be in to move into this one.
+    /// A course never goes back to draft once learners could have bought it.
+    pub fn allowed_from(&self) -> &'static [CourseStatus] {
+        match self {
+            CourseStatus::Draft => &[],
+            CourseStatus::Published => &[CourseStatus::Draft, CourseStatus::Unlisted, CourseStatus::Archived],
+            CourseStatus::Unlisted => &[CourseStatus::Draft, CourseStatus::Published, CourseStatus::Archived],
+            CourseStatus::Archived => &[CourseStatus::Draft, CourseStatus::Published, CourseStatus::Unlisted],
+        }
+    }
+
+    /// Whether new buyers can reach and purchase the course.
+    pub fn is_live(&self) -> bool {
+        matches!(self, CourseStatus::Published | CourseStatus::Unlisted)
+    }
+}
+
+pub struct CatalogFilter{
+    pub min_price: Option<i32>,
+    pub max_price: Option<i32>,
+    pub instructor: Option<Uuid>,
+    pub category: Option<String>,
+    pub free_only: bool,
+    pub sort: CatalogSort,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn create_course(pool:&Pool<Postgres>, course_details:CreateCourse) -> Result<Course, DbError>{
     let result = sqlx::query_as!(
         Course,
         r#"
-            INSERT INTO course_table (title, image_url, price, admin_id)
-            VALUES ($1, $2, $3, $4)  
-            RETURNING *
+            INSERT INTO course_table (title, image_url, price, admin_id, category, description)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, title, image_url, price, admin_id, category, description, status, version, created_at, updated_at, deleted_at
         "#,
         course_details.title,
         course_details.image_url,
         course_details.price,
         course_details.admin_id,
+        course_details.category,
+        course_details.description,
     )
     .fetch_one(pool)
     .await;
 
     match result {
         Ok(val) => Ok(val),
-        Err(_) => Err(CustomError { error: "Error while creating a course".to_string() })
+        Err(e) => Err(DbError::from_sqlx(&e, "Error while creating a course"))
     }
 }
 
-pub async fn get_course_by_id(pool:&Pool<Postgres>, id:Uuid)->Result<Course, CustomError>{
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_course_by_id(pool:&Pool<Postgres>, id:Uuid)->Result<Option<Course>, CustomError>{
 
     let result = sqlx::query_as!(
         Course,
         r#"
-            SELECT * FROM course_table
+            SELECT id, title, image_url, price, admin_id, category, description, status, version, created_at, updated_at, deleted_at
+            FROM course_table
             WHERE id = $1
         "#,
         id
     )
-    .fetch_one(pool)
+    .fetch_optional(pool)
     .await;
 
     match result {
@@ -53,19 +175,28 @@ pub async fn get_course_by_id(pool:&Pool<Postgres>, id:Uuid)->Result<Course, Cus
 
 }
 
-pub async fn update_course(pool:&Pool<Postgres>, updated_course: UpdateCourse) -> Result<Course, CustomError>{
+/// Replaces every editable field of the course. With `expected_version` set the update only
+/// goes through if nobody changed the course since, None means it was changed in between.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn update_course(pool:&Pool<Postgres>, id:Uuid, updated_course:UpdateCourse, expected_version:Option<i32>) -> Result<Option<Course>, CustomError>{
     let result = sqlx::query_as!(
         Course,
         r#"
             UPDATE course_table
-            SET title = $1, image_url = $2, price = $3
-            RETURNING *
+            SET title = $2, image_url = $3, price = $4, category = $5, description = $6,
+                version = version + 1, updated_at = now()
+            WHERE id = $1 AND ($7::int IS NULL OR version = $7)
+            RETURNING id, title, image_url, price, admin_id, category, description, status, version, created_at, updated_at, deleted_at
         "#,
+        id,
         updated_course.title,
         updated_course.image_url,
-        updated_course.price
+        updated_course.price,
+        updated_course.category,
+        updated_course.description,
+        expected_version
     )
-    .fetch_one(pool)
+    .fetch_optional(pool)
     .await;
 
     match result {
@@ -74,13 +205,50 @@ pub async fn update_course(pool:&Pool<Postgres>, updated_course: UpdateCourse) -
     }
 }
 
+/// Same as `update_course` but only touches the fields that were supplied,
+/// a nullable field supplied as `Some(None)` is cleared.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn patch_course(pool:&Pool<Postgres>, id:Uuid, patch:PatchCourse, expected_version:Option<i32>) -> Result<Option<Course>, CustomError>{
+    let result = sqlx::query_as!(
+        Course,
+        r#"
+            UPDATE course_table
+            SET title = COALESCE($2, title),
+                image_url = CASE WHEN $3 THEN $4 ELSE image_url END,
+                price = COALESCE($5, price),
+                category = CASE WHEN $6 THEN $7 ELSE category END,
+                description = CASE WHEN $8 THEN $9 ELSE description END,
+                version = version + 1, updated_at = now()
+            WHERE id = $1 AND ($10::int IS NULL OR version = $10)
+            RETURNING id, title, image_url, price, admin_id, category, description, status, version, created_at, updated_at, deleted_at
+        "#,
+        id,
+        patch.title,
+        patch.image_url.is_some(),
+        patch.image_url.flatten(),
+        patch.price,
+        patch.category.is_some(),
+        patch.category.flatten(),
+        patch.description.is_some(),
+        patch.description.flatten(),
+        expected_version
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while updating the course".to_string()})?;
+
+    Ok(result)
+}
+
+#[instrument(level = "debug", skip_all, err)]
 pub async fn get_all_admin_courses(pool:&Pool<Postgres>, admin_id:Uuid) -> Result<Vec<Course>, CustomError>{
 
     let result = sqlx::query_as!(
         Course,
         r#"
-            SELECT * FROM course_table
-            WHERE admin_id = $1
+            SELECT id, title, image_url, price, admin_id, category, description, status, version, created_at, updated_at, deleted_at
+            FROM course_table
+            WHERE admin_id = $1 AND deleted_at IS NULL
         "#,
         admin_id
     )
@@ -93,19 +261,353 @@ pub async fn get_all_admin_courses(pool:&Pool<Postgres>, admin_id:Uuid) -> Resul
     }
 }
 
-pub async fn get_all_courses(pool:&Pool<Postgres>) -> Result<Vec<Course>, CustomError>{
+/// One page of the public catalog along with how many courses match the filters overall.
+/// Every filter is optional, a missing one matches everything.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_catalog(pool:&Pool<Postgres>, filter:&CatalogFilter) -> Result<(Vec<Course>, i64), CustomError>{
 
-    let result = sqlx::query_as!(
+    let courses = sqlx::query_as!(
         Course,
         r#"
-            SELECT * FROM course_table
+            SELECT c.id, c.title, c.image_url, c.price, c.admin_id, c.category, c.description, c.status, c.version, c.created_at, c.updated_at, c.deleted_at
+            FROM course_table c
+            WHERE ($1::int IS NULL OR c.price >= $1)
+            AND ($2::int IS NULL OR c.price <= $2)
+            AND ($3::uuid IS NULL OR c.admin_id = $3)
+            AND ($4::text IS NULL OR c.category = $4)
+            AND (NOT $5 OR c.price = 0)
+            AND c.status = 'published'
+            ORDER BY
+                CASE WHEN $6 = 'price_asc' THEN c.price END ASC,
+                CASE WHEN $6 = 'price_desc' THEN c.price END DESC,
+                CASE WHEN $6 = 'popular' THEN (
+                    SELECT COUNT(*) FROM purchases_table p
+                    WHERE p.course_id = c.id AND p.revoked_at IS NULL
+                ) END DESC,
+                c.created_at DESC, c.id
+            LIMIT $7 OFFSET $8
         "#,
+        filter.min_price,
+        filter.max_price,
+        filter.instructor,
+        filter.category,
+        filter.free_only,
+        filter.sort.as_str(),
+        filter.limit,
+        filter.offset
     )
     .fetch_all(pool)
-    .await;
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching all the courses".to_string()})?;
 
-    match result {
-        Ok(val) => Ok(val),
-        Err(_) => Err(CustomError { error: "Error while fetching all the courses".to_string()})
+    let total = sqlx::query_scalar!(
+        r#"
+            SELECT COUNT(*) AS "total!" FROM course_table c
+            WHERE ($1::int IS NULL OR c.price >= $1)
+            AND ($2::int IS NULL OR c.price <= $2)
+            AND ($3::uuid IS NULL OR c.admin_id = $3)
+            AND ($4::text IS NULL OR c.category = $4)
+            AND (NOT $5 OR c.price = 0)
+            AND c.status = 'published'
+        "#,
+        filter.min_price,
+        filter.max_price,
+        filter.instructor,
+        filter.category,
+        filter.free_only
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching all the courses".to_string()})?;
+
+    Ok((courses, total))
+}
+
+/// A course matching a search along with its rank and the matched words wrapped in `<mark>` tags.
+pub struct CourseSearchHit{
+    pub id: String,
+    pub title: String,
+    pub image_url: Option<String>,
+    pub price: i32,
+    pub admin_id: uuid::Uuid,
+    pub category: Option<String>,
+    pub description: Option<String>,
+    pub status: String,
+    pub version: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub rank: f32,
+    pub title_highlight: String,
+    pub snippet: Option<String>,
+}
+
+impl From<CourseSearchHit> for SearchResult{
+    fn from(hit:CourseSearchHit) -> Self {
+        SearchResult{
+            course: CourseResponse{
+                id: hit.id,
+                admin_id: hit.admin_id.to_string(),
+                title: hit.title,
+                image_url: hit.image_url,
+                price: hit.price,
+                category: hit.category,
+                description: hit.description,
+                status: hit.status,
+                version: hit.version,
+                created_at: hit.created_at,
+                updated_at: hit.updated_at,
+            },
+            rank: hit.rank,
+            title_highlight: hit.title_highlight,
+            snippet: hit.snippet,
+        }
     }
 }
+
+/// Turns free text into a tsquery where every word has to match and the last one
+/// is matched as a prefix, so partially typed words still find results.
+/// Returns None when there is nothing searchable left.
+pub fn search_query(text:&str) -> Option<String> {
+
+    let words = text
+        .split(|c:char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<String>>();
+
+    let (last, rest) = words.split_last()?;
+
+    let mut terms = rest.to_vec();
+    terms.push(format!("{}:*", last));
+
+    Some(terms.join(" & "))
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn search_courses(pool:&Pool<Postgres>, query:&str, limit:i64, offset:i64) -> Result<(Vec<CourseSearchHit>, i64), CustomError>{
+
+    let hits = sqlx::query_as!(
+        CourseSearchHit,
+        r#"
+            SELECT c.id, c.title, c.image_url, c.price, c.admin_id, c.category, c.description, c.status, c.version, c.created_at, c.updated_at,
+            ts_rank(c.search_vector, q) AS "rank!",
+            ts_headline('english', c.title, q, 'StartSel=<mark>, StopSel=</mark>, HighlightAll=true') AS "title_highlight!",
+            CASE WHEN c.description IS NULL THEN NULL
+            ELSE ts_headline('english', c.description, q, 'StartSel=<mark>, StopSel=</mark>, MaxWords=25, MinWords=10, MaxFragments=2')
+            END AS snippet
+            FROM course_table c, to_tsquery('english', $1) q
+            WHERE c.search_vector @@ q AND c.status = 'published'
+            ORDER BY ts_rank(c.search_vector, q) DESC, c.created_at DESC, c.id
+            LIMIT $2 OFFSET $3
+        "#,
+        query,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while searching the courses".to_string()})?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+            SELECT COUNT(*) AS "total!" FROM course_table
+            WHERE search_vector @@ to_tsquery('english', $1) AND status = 'published'
+        "#,
+        query
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while searching the courses".to_string()})?;
+
+    Ok((hits, total))
+}
+
+/// A course with its instructor and the totals shown on the course page.
+pub struct CourseDetail{
+    pub course: Course,
+    pub instructor_name: String,
+    pub lesson_count: i64,
+    pub total_duration_seconds: i64,
+    pub purchase_count: i64,
+    pub average_rating: Option<f64>,
+    pub rating_count: i64,
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_course_detail(pool:&Pool<Postgres>, id:Uuid) -> Result<Option<CourseDetail>, CustomError>{
+
+    let result = sqlx::query!(
+        r#"
+            SELECT c.id, c.title, c.image_url, c.price, c.admin_id, c.category, c.description, c.status, c.version, c.created_at, c.updated_at, c.deleted_at,
+            a.name AS instructor_name,
+            (
+                SELECT COUNT(*) FROM lessons_table l
+                JOIN sections_table s ON s.id = l.section_id
+                WHERE s.course_id = c.id
+            ) AS "lesson_count!",
+            (
+                SELECT COALESCE(SUM(l.duration_seconds), 0) FROM lessons_table l
+                JOIN sections_table s ON s.id = l.section_id
+                WHERE s.course_id = c.id
+            ) AS "total_duration_seconds!",
+            (
+                SELECT COUNT(*) FROM purchases_table p
+                WHERE p.course_id = c.id AND p.revoked_at IS NULL
+            ) AS "purchase_count!",
+            (
+                SELECT AVG(r.rating)::float8 FROM course_ratings_table r
+                WHERE r.course_id = c.id
+            ) AS average_rating,
+            (
+                SELECT COUNT(*) FROM course_ratings_table r
+                WHERE r.course_id = c.id
+            ) AS "rating_count!"
+            FROM course_table c
+            JOIN admin_table a ON a.id = c.admin_id
+            WHERE c.id = $1
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching the course".to_string()})?;
+
+    Ok(result.map(|row| CourseDetail{
+        course: Course{
+            id: row.id.to_string(),
+            title: row.title,
+            image_url: row.image_url,
+            price: row.price,
+            admin_id: row.admin_id,
+            category: row.category,
+            description: row.description,
+            status: row.status,
+            version: row.version,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            deleted_at: row.deleted_at,
+        },
+        instructor_name: row.instructor_name,
+        lesson_count: row.lesson_count,
+        total_duration_seconds: row.total_duration_seconds,
+        purchase_count: row.purchase_count,
+        average_rating: row.average_rating,
+        rating_count: row.rating_count,
+    }))
+}
+
+/// Moves the course into `status` if it is still in one of the states allowed to lead there.
+/// Returns None when the course changed underneath us.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn set_course_status(pool:&Pool<Postgres>, id:Uuid, status:CourseStatus) -> Result<Option<Course>, CustomError>{
+
+    let allowed_from = status.allowed_from().iter().map(|from| from.as_str().to_string()).collect::<Vec<String>>();
+
+    let result = sqlx::query_as!(
+        Course,
+        r#"
+            UPDATE course_table SET status = $2, version = version + 1, updated_at = now()
+            WHERE id = $1 AND status = ANY($3)
+            RETURNING id, title, image_url, price, admin_id, category, description, status, version, created_at, updated_at, deleted_at
+        "#,
+        id,
+        status.as_str(),
+        &allowed_from
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while updating the course status".to_string()})?;
+
+    Ok(result)
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn count_course_lessons(pool:&Pool<Postgres>, id:Uuid) -> Result<i64, CustomError>{
+
+    let result = sqlx::query_scalar!(
+        r#"
+            SELECT COUNT(*) AS "count!" FROM lessons_table l
+            JOIN sections_table s ON s.id = l.section_id
+            WHERE s.course_id = $1
+        "#,
+        id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while counting the lessons".to_string()})?;
+
+    Ok(result)
+}
+
+/// What removing a course ended up doing.
+#[derive(Debug, PartialEq)]
+pub enum CourseDeletion{
+    Deleted,
+    // the course had buyers, so it was archived and hidden instead
+    SoftDeleted,
+}
+
+/// Deletes a course nobody has bought or tried to pay for. Courses with purchases or
+/// orders, even failed ones, are archived and marked deleted so their buyers keep access
+/// and the order history stays intact. So are courses in a bundle, which stops selling it.
+/// Returns None when the course is no longer at `expected_version`.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn delete_course(pool:&Pool<Postgres>, id:Uuid, expected_version:Option<i32>) -> Result<Option<CourseDeletion>, CustomError>{
+
+    let mut tx = pool.begin().await
+    .map_err(|_e|CustomError{error:"Error while deleting the course".to_string()})?;
+
+    let has_buyers = sqlx::query_scalar!(
+        r#"
+            SELECT EXISTS(SELECT 1 FROM purchases_table WHERE course_id = c.id)
+            OR EXISTS(SELECT 1 FROM orders_table WHERE course_id = c.id)
+            OR EXISTS(SELECT 1 FROM bundle_courses_table WHERE course_id = c.id)
+            AS "has_buyers!"
+            FROM course_table c
+            WHERE c.id = $1 AND ($2::int IS NULL OR c.version = $2)
+            FOR UPDATE
+        "#,
+        id,
+        expected_version
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|_e|CustomError{error:"Error while deleting the course".to_string()})?;
+
+    // someone changed the course since the caller last saw it
+    let Some(has_buyers) = has_buyers else {
+        return Ok(None);
+    };
+
+    let deletion = if has_buyers {
+        sqlx::query!(
+            r#"
+                UPDATE course_table SET status = 'archived', deleted_at = now()
+                WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|_e|CustomError{error:"Error while deleting the course".to_string()})?;
+
+        CourseDeletion::SoftDeleted
+    } else {
+        sqlx::query!(
+            r#"
+                DELETE FROM course_table WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|_e|CustomError{error:"Error while deleting the course".to_string()})?;
+
+        CourseDeletion::Deleted
+    };
+
+    tx.commit().await
+    .map_err(|_e|CustomError{error:"Error while deleting the course".to_string()})?;
+
+    Ok(Some(deletion))
+}
@@ -0,0 +1,74 @@
+use chrono::{DateTime, Utc};
+use sqlx::{types::Uuid, Pool, Postgres};
+use tracing::instrument;
+
+use crate::{errors::CustomError, schema::StructWithId};
+
+pub struct VerificationTokenOwner{
+    pub user_id: Uuid,
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn create_verification_token(pool:&Pool<Postgres>, user_id:Uuid, token_hash:&str, expires_at:DateTime<Utc>) -> Result<String, CustomError>{
+
+    let result = sqlx::query_as!(
+        StructWithId,
+        r#"
+            INSERT INTO email_verification_tokens_table (user_id, token_hash, expires_at)
+            VALUES ($1, $2, $3)
+            RETURNING id
+        "#,
+        user_id,
+        token_hash,
+        expires_at
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while creating verification token".to_string()})?;
+
+    Ok(result.id)
+}
+
+/// Burns the verification token and marks the user's email as verified.
+/// Returns false when the token is unknown, already used or expired.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn verify_email_with_token(pool:&Pool<Postgres>, token_hash:&str) -> Result<bool, CustomError>{
+
+    let err = |_e| CustomError{error:"Error while verifying email".to_string()};
+
+    let mut tx = pool.begin().await.map_err(err)?;
+
+    let owner = sqlx::query_as!(
+        VerificationTokenOwner,
+        r#"
+            UPDATE email_verification_tokens_table
+            SET used_at = now()
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > now()
+            RETURNING user_id
+        "#,
+        token_hash
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(err)?;
+
+    let user_id = match owner {
+        Some(owner) => owner.user_id,
+        None => return Ok(false),
+    };
+
+    sqlx::query!(
+        r#"
+            UPDATE user_table SET email_verified_at = now()
+            WHERE id = $1 AND email_verified_at IS NULL
+        "#,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(err)?;
+
+    tx.commit().await.map_err(err)?;
+
+    Ok(true)
+}
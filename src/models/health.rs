@@ -0,0 +1,32 @@
+use sqlx::{Pool, Postgres};
+use tracing::instrument;
+
+use crate::errors::CustomError;
+
+/// Round trip to the database, fails when no connection can be had.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn ping(pool:&Pool<Postgres>) -> Result<(), CustomError>{
+
+    sqlx::query!("SELECT 1 AS one")
+    .fetch_one(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Database unreachable".to_string()})?;
+
+    Ok(())
+}
+
+/// Newest migration applied successfully, None when the database was never migrated.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_applied_migration_version(pool:&Pool<Postgres>) -> Result<Option<i64>, CustomError>{
+
+    let result = sqlx::query_scalar!(
+        r#"
+            SELECT MAX(version) FROM _sqlx_migrations WHERE success
+        "#
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while reading the applied migrations".to_string()})?;
+
+    Ok(result)
+}
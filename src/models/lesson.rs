@@ -0,0 +1,198 @@
+use std::collections::HashSet;
+
+use sqlx::{types::Uuid, Pool, Postgres};
+use tracing::instrument;
+
+use crate::{errors::CustomError, schema::content::LessonInput};
+
+#[derive(Debug)]
+pub struct Lesson{
+    pub id: String,
+    pub section_id: Uuid,
+    pub title: String,
+    pub body: Option<String>,
+    pub video_url: Option<String>,
+    pub duration_seconds: i32,
+    pub is_preview: bool,
+    pub position: i32,
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn create_lesson(pool:&Pool<Postgres>, section_id:Uuid, lesson:&LessonInput) -> Result<Lesson, CustomError>{
+
+    // new lessons go to the end of the section
+    let result = sqlx::query_as!(
+        Lesson,
+        r#"
+            INSERT INTO lessons_table (section_id, title, body, video_url, duration_seconds, is_preview, position)
+            VALUES ($1, $2, $3, $4, $5, $6, COALESCE((SELECT MAX(position) FROM lessons_table WHERE section_id = $1), 0) + 1)
+            RETURNING id, section_id, title, body, video_url, duration_seconds, is_preview, position
+        "#,
+        section_id,
+        lesson.title,
+        lesson.body,
+        lesson.video_url,
+        lesson.duration_seconds,
+        lesson.is_preview
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while creating the lesson".to_string()})?;
+
+    Ok(result)
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_lesson(pool:&Pool<Postgres>, lesson_id:Uuid, section_id:Uuid) -> Result<Option<Lesson>, CustomError>{
+
+    let result = sqlx::query_as!(
+        Lesson,
+        r#"
+            SELECT id, section_id, title, body, video_url, duration_seconds, is_preview, position
+            FROM lessons_table
+            WHERE id = $1 AND section_id = $2
+        "#,
+        lesson_id,
+        section_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching the lesson".to_string()})?;
+
+    Ok(result)
+}
+
+/// All lessons of a course, in section order then lesson order.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_course_lessons(pool:&Pool<Postgres>, course_id:Uuid) -> Result<Vec<Lesson>, CustomError>{
+
+    let result = sqlx::query_as!(
+        Lesson,
+        r#"
+            SELECT l.id, l.section_id, l.title, l.body, l.video_url, l.duration_seconds, l.is_preview, l.position
+            FROM lessons_table l
+            JOIN sections_table s ON s.id = l.section_id
+            WHERE s.course_id = $1
+            ORDER BY s.position, l.position
+        "#,
+        course_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching the lessons".to_string()})?;
+
+    Ok(result)
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn update_lesson(pool:&Pool<Postgres>, lesson_id:Uuid, lesson:&LessonInput) -> Result<Lesson, CustomError>{
+
+    let result = sqlx::query_as!(
+        Lesson,
+        r#"
+            UPDATE lessons_table
+            SET title = $2, body = $3, video_url = $4, duration_seconds = $5, is_preview = $6
+            WHERE id = $1
+            RETURNING id, section_id, title, body, video_url, duration_seconds, is_preview, position
+        "#,
+        lesson_id,
+        lesson.title,
+        lesson.body,
+        lesson.video_url,
+        lesson.duration_seconds,
+        lesson.is_preview
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while updating the lesson".to_string()})?;
+
+    Ok(result)
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn delete_lesson(pool:&Pool<Postgres>, lesson_id:Uuid) -> Result<(), CustomError>{
+
+    sqlx::query!(
+        r#"
+            DELETE FROM lessons_table
+            WHERE id = $1
+        "#,
+        lesson_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while deleting the lesson".to_string()})?;
+
+    Ok(())
+}
+
+/// Sets the lesson order of a section to the order of `ids`.
+/// Returns false when `ids` is not exactly the set of lessons in the section.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn reorder_lessons(pool:&Pool<Postgres>, section_id:Uuid, ids:&[Uuid]) -> Result<bool, CustomError>{
+
+    let err = |_e| CustomError{error:"Error while reordering the lessons".to_string()};
+
+    let mut tx = pool.begin().await.map_err(err)?;
+
+    let existing = sqlx::query_scalar!(
+        r#"
+            SELECT id FROM lessons_table
+            WHERE section_id = $1
+            FOR UPDATE
+        "#,
+        section_id
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(err)?;
+
+    let requested = ids.iter().collect::<HashSet<_>>();
+
+    if requested.len() != ids.len() || existing.len() != ids.len() || !existing.iter().all(|id| requested.contains(id)){
+        return Ok(false);
+    }
+
+    sqlx::query!(
+        r#"
+            UPDATE lessons_table
+            SET position = ordered.position
+            FROM UNNEST($2::uuid[]) WITH ORDINALITY AS ordered(id, position)
+            WHERE lessons_table.id = ordered.id AND lessons_table.section_id = $1
+        "#,
+        section_id,
+        ids
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(err)?;
+
+    tx.commit().await.map_err(err)?;
+
+    Ok(true)
+}
+
+pub struct LessonCourse{
+    pub course_id: Uuid,
+    pub video_url: Option<String>,
+}
+
+/// The course a lesson belongs to, used to check the caller bought it.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_lesson_course(pool:&Pool<Postgres>, lesson_id:Uuid) -> Result<Option<LessonCourse>, CustomError>{
+
+    let result = sqlx::query_as!(
+        LessonCourse,
+        r#"
+            SELECT s.course_id, l.video_url FROM lessons_table l
+            JOIN sections_table s ON s.id = l.section_id
+            WHERE l.id = $1
+        "#,
+        lesson_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching the lesson".to_string()})?;
+
+    Ok(result)
+}
@@ -1,4 +1,17 @@
 pub mod user;
 pub mod admin;
 pub mod course;
-pub mod purchase;
\ No newline at end of file
+pub mod purchase;
+pub mod session;
+pub mod password_reset;
+pub mod email_verification;
+pub mod role;
+pub mod section;
+pub mod lesson;
+pub mod progress;
+pub mod order;
+pub mod refund;
+pub mod coupon;
+pub mod bundle;
+pub mod rating;
+pub mod health;
@@ -0,0 +1,389 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Uuid, Connection, PgConnection, Pool, Postgres};
+use tracing::instrument;
+
+use crate::{errors::{CustomError, DbError}, models::{bundle::{get_unowned_bundle_courses, split_price}, coupon::{redeem_coupon, release_coupon}, purchase::purchase_course}};
+
+/// pending -> paid -> fulfilled, pending -> failed, and a paid or fulfilled order can be refunded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrderStatus{
+    Pending,
+    Paid,
+    Fulfilled,
+    Failed,
+    Refunded,
+}
+
+impl OrderStatus{
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Pending => "pending",
+            OrderStatus::Paid => "paid",
+            OrderStatus::Fulfilled => "fulfilled",
+            OrderStatus::Failed => "failed",
+            OrderStatus::Refunded => "refunded",
+        }
+    }
+
+    /// The states an order has to be in to move into this one.
+    fn allowed_from(&self) -> &'static [OrderStatus] {
+        match self {
+            OrderStatus::Pending => &[],
+            OrderStatus::Paid | OrderStatus::Failed => &[OrderStatus::Pending],
+            OrderStatus::Fulfilled => &[OrderStatus::Paid],
+            OrderStatus::Refunded => &[OrderStatus::Paid, OrderStatus::Fulfilled],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Order{
+    pub id: String,
+    pub user_id: String,
+    pub course_id: Option<String>,
+    pub amount: i32,
+    pub status: String,
+    pub provider: String,
+    pub provider_ref: Option<String>,
+    pub coupon_id: Option<String>,
+    pub bundle_id: Option<String>,
+    // paid for but nothing could be granted, the money has to go back
+    pub refund_due: bool,
+}
+
+/// How long a checkout stays open. Past that a pending order is no longer handed back and
+/// its coupon redemption can be taken by someone else.
+pub const PENDING_ORDER_TTL_MINUTES: i32 = 30;
+
+/// What an order is for, either a single course or a bundle of them.
+#[derive(Debug, Clone, Copy)]
+pub enum OrderItem{
+    Course(Uuid),
+    Bundle(Uuid),
+}
+
+impl OrderItem{
+    fn course_and_bundle(&self) -> (Option<Uuid>, Option<Uuid>) {
+        match self {
+            OrderItem::Course(course_id) => (Some(*course_id), None),
+            OrderItem::Bundle(bundle_id) => (None, Some(*bundle_id)),
+        }
+    }
+}
+
+/// Opens a pending order, redeeming the coupon in the same transaction.
+/// Returns None when the coupon has no redemptions left.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn create_order(pool:&Pool<Postgres>, user_id:Uuid, item:OrderItem, amount:i32, coupon_id:Option<Uuid>, provider:&str) -> Result<Option<Order>, CustomError>{
+
+    let (course_id, bundle_id) = item.course_and_bundle();
+
+    let mut tx = pool.begin().await
+    .map_err(|_e|CustomError{error:"Error while creating the order".to_string()})?;
+
+    if let Some(coupon_id) = coupon_id {
+        expire_coupon_orders(&mut tx, coupon_id).await?;
+
+        if !redeem_coupon(&mut tx, coupon_id).await? {
+            return Ok(None);
+        }
+    }
+
+    let result = sqlx::query_as!(
+        Order,
+        r#"
+            INSERT INTO orders_table (user_id, course_id, bundle_id, amount, coupon_id, provider)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, user_id, course_id::text, amount, status, provider, provider_ref, coupon_id::text, bundle_id::text, refund_due
+        "#,
+        user_id,
+        course_id,
+        bundle_id,
+        amount,
+        coupon_id,
+        provider
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e|CustomError::from(DbError::from_sqlx(&e, "Error while creating the order")))?;
+
+    tx.commit().await
+    .map_err(|_e|CustomError{error:"Error while creating the order".to_string()})?;
+
+    Ok(Some(result))
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn set_provider_ref(pool:&Pool<Postgres>, order_id:Uuid, provider_ref:&str) -> Result<Order, CustomError>{
+
+    let result = sqlx::query_as!(
+        Order,
+        r#"
+            UPDATE orders_table
+            SET provider_ref = $2, updated_at = now()
+            WHERE id = $1
+            RETURNING id, user_id, course_id::text, amount, status, provider, provider_ref, coupon_id::text, bundle_id::text, refund_due
+        "#,
+        order_id,
+        provider_ref
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while updating the order".to_string()})?;
+
+    Ok(result)
+}
+
+/// Fails the abandoned checkouts holding a redemption of the coupon and gives those redemptions back,
+/// so unpaid orders can't use a limited coupon up.
+async fn expire_coupon_orders(conn:&mut PgConnection, coupon_id:Uuid) -> Result<(), CustomError>{
+
+    sqlx::query!(
+        r#"
+            WITH expired AS (
+                UPDATE orders_table SET status = $2, updated_at = now()
+                WHERE coupon_id = $1 AND status = $3
+                AND created_at < now() - make_interval(mins => $4)
+                RETURNING id
+            )
+            UPDATE coupons_table SET redemptions = GREATEST(redemptions - (SELECT COUNT(*) FROM expired)::int, 0)
+            WHERE id = $1
+        "#,
+        coupon_id,
+        OrderStatus::Failed.as_str(),
+        OrderStatus::Pending.as_str(),
+        PENDING_ORDER_TTL_MINUTES
+    )
+    .execute(conn)
+    .await
+    .map_err(|_e|CustomError{error:"Error while expiring the orders".to_string()})?;
+
+    Ok(())
+}
+
+/// An unpaid order for the same course or bundle is reused rather than opening a second checkout,
+/// as long as it hasn't been open for longer than `PENDING_ORDER_TTL_MINUTES`.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_pending_order(pool:&Pool<Postgres>, user_id:Uuid, item:OrderItem) -> Result<Option<Order>, CustomError>{
+
+    let (course_id, bundle_id) = item.course_and_bundle();
+
+    let result = sqlx::query_as!(
+        Order,
+        r#"
+            SELECT id, user_id, course_id::text, amount, status, provider, provider_ref, coupon_id::text, bundle_id::text, refund_due
+            FROM orders_table
+            WHERE user_id = $1 AND course_id IS NOT DISTINCT FROM $2
+            AND bundle_id IS NOT DISTINCT FROM $3 AND status = $4
+            AND created_at >= now() - make_interval(mins => $5)
+            ORDER BY created_at DESC
+            LIMIT 1
+        "#,
+        user_id,
+        course_id,
+        bundle_id,
+        OrderStatus::Pending.as_str(),
+        PENDING_ORDER_TTL_MINUTES
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching the order".to_string()})?;
+
+    Ok(result)
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_order_by_provider_ref(pool:&Pool<Postgres>, provider_ref:&str) -> Result<Option<Order>, CustomError>{
+
+    let result = sqlx::query_as!(
+        Order,
+        r#"
+            SELECT id, user_id, course_id::text, amount, status, provider, provider_ref, coupon_id::text, bundle_id::text, refund_due
+            FROM orders_table
+            WHERE provider_ref = $1
+        "#,
+        provider_ref
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching the order".to_string()})?;
+
+    Ok(result)
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_user_orders(pool:&Pool<Postgres>, user_id:Uuid) -> Result<Vec<Order>, CustomError>{
+
+    let result = sqlx::query_as!(
+        Order,
+        r#"
+            SELECT id, user_id, course_id::text, amount, status, provider, provider_ref, coupon_id::text, bundle_id::text, refund_due
+            FROM orders_table
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching the orders".to_string()})?;
+
+    Ok(result)
+}
+
+/// Moves the order into `to` only if it currently sits in a state allowed to precede it,
+/// returns None when the transition is not allowed (or someone else already made it).
+async fn transition_order(conn:&mut PgConnection, order_id:Uuid, to:OrderStatus) -> Result<Option<Order>, CustomError>{
+
+    let allowed_from = to.allowed_from().iter().map(|status| status.as_str().to_string()).collect::<Vec<String>>();
+
+    let result = sqlx::query_as!(
+        Order,
+        r#"
+            UPDATE orders_table
+            SET status = $2, updated_at = now()
+            WHERE id = $1 AND status = ANY($3)
+            RETURNING id, user_id, course_id::text, amount, status, provider, provider_ref, coupon_id::text, bundle_id::text, refund_due
+        "#,
+        order_id,
+        to.as_str(),
+        &allowed_from
+    )
+    .fetch_optional(conn)
+    .await
+    .map_err(|_e|CustomError{error:"Error while updating the order".to_string()})?;
+
+    Ok(result)
+}
+
+/// Flags an order whose money has to be paid back, see `Order::refund_due`.
+/// Returns None when the order is not in `status`.
+async fn flag_refund_due(conn:&mut PgConnection, order_id:Uuid, status:OrderStatus) -> Result<Option<Order>, CustomError>{
+
+    let result = sqlx::query_as!(
+        Order,
+        r#"
+            UPDATE orders_table
+            SET refund_due = true, updated_at = now()
+            WHERE id = $1 AND status = $2
+            RETURNING id, user_id, course_id::text, amount, status, provider, provider_ref, coupon_id::text, bundle_id::text, refund_due
+        "#,
+        order_id,
+        status.as_str()
+    )
+    .fetch_optional(conn)
+    .await
+    .map_err(|_e|CustomError{error:"Error while updating the order".to_string()})?;
+
+    Ok(result)
+}
+
+/// Marks the order paid and grants its courses in one transaction, the order ends up fulfilled.
+/// Returns None when the order was not pending, so replayed webhooks are harmless. When the
+/// buyer already owns everything the order was for, it is still fulfilled but flagged `refund_due`.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn pay_order(pool:&Pool<Postgres>, order_id:Uuid) -> Result<Option<Order>, CustomError>{
+
+    let mut tx = pool.begin().await
+    .map_err(|_e|CustomError{error:"Error while paying the order".to_string()})?;
+
+    let paid = transition_order(&mut tx, order_id, OrderStatus::Paid).await?;
+
+    let Some(paid) = paid else {
+        return Ok(None);
+    };
+
+    let user_uuid = Uuid::parse_str(&paid.user_id)
+    .map_err(|_e|CustomError{error:"Error while paying the order".to_string()})?;
+
+    let item = match (&paid.course_id, &paid.bundle_id) {
+        (Some(course_id), _) => Uuid::parse_str(course_id).ok().map(OrderItem::Course),
+        (None, Some(bundle_id)) => Uuid::parse_str(bundle_id).ok().map(OrderItem::Bundle),
+        (None, None) => None,
+    }
+    .ok_or_else(||CustomError{error:"Error while paying the order".to_string()})?;
+
+    let granted = match item {
+        OrderItem::Course(course_uuid) => {
+            // the course may have been granted some other way while the checkout was open, the
+            // savepoint keeps the failed insert from aborting the whole transaction
+            let mut savepoint = tx.begin().await
+            .map_err(|_e|CustomError{error:"Error while paying the order".to_string()})?;
+
+            match purchase_course(&mut savepoint, course_uuid, user_uuid, Some(order_id), Some(paid.amount)).await {
+                Ok(_) => {
+                    savepoint.commit().await
+                    .map_err(|_e|CustomError{error:"Error while paying the order".to_string()})?;
+                    true
+                },
+                Err(DbError::AlreadyPurchased) => {
+                    savepoint.rollback().await
+                    .map_err(|_e|CustomError{error:"Error while paying the order".to_string()})?;
+                    false
+                },
+                Err(e) => return Err(e.into()),
+            }
+        },
+        // courses bought since the checkout opened are skipped, the rest share the bundle price
+        OrderItem::Bundle(bundle_uuid) => {
+            let courses = get_unowned_bundle_courses(&mut tx, bundle_uuid, user_uuid).await?;
+            let shares = split_price(paid.amount, &courses);
+
+            for (course, share) in courses.iter().zip(shares) {
+                purchase_course(&mut tx, course.course_id, user_uuid, Some(order_id), Some(share)).await?;
+            }
+
+            !courses.is_empty()
+        },
+    };
+
+    if !granted && paid.amount > 0 {
+        flag_refund_due(&mut tx, order_id, OrderStatus::Paid).await?;
+    }
+
+    let fulfilled = transition_order(&mut tx, order_id, OrderStatus::Fulfilled).await?;
+
+    tx.commit().await
+    .map_err(|_e|CustomError{error:"Error while paying the order".to_string()})?;
+
+    Ok(fulfilled)
+}
+
+/// Gives the coupon redemption back as well. Returns None when the order was not pending.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn fail_order(pool:&Pool<Postgres>, order_id:Uuid) -> Result<Option<Order>, CustomError>{
+
+    let mut tx = pool.begin().await
+    .map_err(|_e|CustomError{error:"Error while updating the order".to_string()})?;
+
+    let failed = transition_order(&mut tx, order_id, OrderStatus::Failed).await?;
+
+    let coupon_id = failed.as_ref().and_then(|order| order.coupon_id.as_deref()).map(Uuid::parse_str);
+
+    if let Some(coupon_id) = coupon_id {
+        let coupon_id = coupon_id.map_err(|_e|CustomError{error:"Error while updating the order".to_string()})?;
+        release_coupon(&mut tx, coupon_id).await?;
+    }
+
+    tx.commit().await
+    .map_err(|_e|CustomError{error:"Error while updating the order".to_string()})?;
+
+    Ok(failed)
+}
+
+/// For a payment that came in after the order was failed, nothing gets granted for it so the
+/// money has to go back. Returns None when the order is not failed.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn flag_failed_order(pool:&Pool<Postgres>, order_id:Uuid) -> Result<Option<Order>, CustomError>{
+
+    let mut conn = pool.acquire().await
+    .map_err(|_e|CustomError{error:"Error while updating the order".to_string()})?;
+
+    flag_refund_due(&mut conn, order_id, OrderStatus::Failed).await
+}
+
+/// Part of a refund, so it runs on the refund's transaction. Returns None when the order was never paid.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn refund_order(conn:&mut PgConnection, order_id:Uuid) -> Result<Option<Order>, CustomError>{
+    transition_order(conn, order_id, OrderStatus::Refunded).await
+}
@@ -0,0 +1,119 @@
+use chrono::{DateTime, Utc};
+use sqlx::{types::Uuid, Pool, Postgres};
+use tracing::instrument;
+
+use crate::{errors::CustomError, models::session::{ADMIN_SESSION, USER_SESSION}, schema::StructWithId};
+
+pub struct ResetTokenOwner{
+    pub owner_id: Uuid,
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn create_reset_token(pool:&Pool<Postgres>, owner_id:Uuid, owner_kind:&str, token_hash:&str, expires_at:DateTime<Utc>) -> Result<String, CustomError>{
+
+    let result = sqlx::query_as!(
+        StructWithId,
+        r#"
+            INSERT INTO password_reset_tokens_table (owner_id, owner_kind, token_hash, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+        "#,
+        owner_id,
+        owner_kind,
+        token_hash,
+        expires_at
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while creating reset token".to_string()})?;
+
+    Ok(result.id)
+}
+
+/// Burns the reset token and sets the new password in one transaction.
+/// Every other outstanding token and every session of the account is invalidated too.
+/// Returns false when the token is unknown, already used or expired.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn reset_password_with_token(pool:&Pool<Postgres>, owner_kind:&str, token_hash:&str, password_hash:&str) -> Result<bool, CustomError>{
+
+    let err = |_e| CustomError{error:"Error while resetting password".to_string()};
+
+    let mut tx = pool.begin().await.map_err(err)?;
+
+    let owner = sqlx::query_as!(
+        ResetTokenOwner,
+        r#"
+            UPDATE password_reset_tokens_table
+            SET used_at = now()
+            WHERE token_hash = $1 AND owner_kind = $2
+            AND used_at IS NULL AND expires_at > now()
+            RETURNING owner_id
+        "#,
+        token_hash,
+        owner_kind
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(err)?;
+
+    let owner_id = match owner {
+        Some(owner) => owner.owner_id,
+        None => return Ok(false),
+    };
+
+    if owner_kind == USER_SESSION {
+        sqlx::query!(
+            r#"
+                UPDATE user_table SET password = $1
+                WHERE id = $2
+            "#,
+            password_hash,
+            owner_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(err)?;
+    } else if owner_kind == ADMIN_SESSION {
+        sqlx::query!(
+            r#"
+                UPDATE admin_table SET password = $1
+                WHERE id = $2
+            "#,
+            password_hash,
+            owner_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(err)?;
+    }
+
+    sqlx::query!(
+        r#"
+            UPDATE password_reset_tokens_table
+            SET used_at = now()
+            WHERE owner_id = $1 AND owner_kind = $2 AND used_at IS NULL
+        "#,
+        owner_id,
+        owner_kind
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(err)?;
+
+    sqlx::query!(
+        r#"
+            UPDATE sessions_table
+            SET revoked_at = now()
+            WHERE owner_id = $1 AND owner_kind = $2 AND revoked_at IS NULL
+        "#,
+        owner_id,
+        owner_kind
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(err)?;
+
+    tx.commit().await.map_err(err)?;
+
+    Ok(true)
+}
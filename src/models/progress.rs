@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Uuid, Pool, Postgres};
+use tracing::instrument;
+
+use crate::errors::CustomError;
+
+#[derive(Debug)]
+pub struct LessonProgress{
+    pub lesson_id: Uuid,
+    pub completed: bool,
+    pub last_position_seconds: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PurchaseWithProgress{
+    pub id: String,
+    pub user_id: String,
+    pub course_id: String,
+    pub amount_paid: Option<i32>,
+    pub completion_percentage: f64,
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn set_lesson_completed(pool:&Pool<Postgres>, user_id:Uuid, lesson_id:Uuid, completed:bool) -> Result<LessonProgress, CustomError>{
+
+    let result = sqlx::query_as!(
+        LessonProgress,
+        r#"
+            INSERT INTO lesson_progress_table (user_id, lesson_id, completed_at)
+            VALUES ($1, $2, CASE WHEN $3 THEN now() END)
+            ON CONFLICT (user_id, lesson_id) DO UPDATE
+            SET completed_at = CASE
+                    WHEN $3 THEN COALESCE(lesson_progress_table.completed_at, now())
+                END,
+                updated_at = now()
+            RETURNING lesson_id, completed_at IS NOT NULL AS "completed!", last_position_seconds
+        "#,
+        user_id,
+        lesson_id,
+        completed
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while updating lesson progress".to_string()})?;
+
+    Ok(result)
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn set_last_position(pool:&Pool<Postgres>, user_id:Uuid, lesson_id:Uuid, position_seconds:i32) -> Result<LessonProgress, CustomError>{
+
+    let result = sqlx::query_as!(
+        LessonProgress,
+        r#"
+            INSERT INTO lesson_progress_table (user_id, lesson_id, last_position_seconds)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id, lesson_id) DO UPDATE
+            SET last_position_seconds = $3, updated_at = now()
+            RETURNING lesson_id, completed_at IS NOT NULL AS "completed!", last_position_seconds
+        "#,
+        user_id,
+        lesson_id,
+        position_seconds
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while updating lesson progress".to_string()})?;
+
+    Ok(result)
+}
+
+/// Active purchases of a user along with the share of lessons completed in each course.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_user_purchases_with_progress(pool:&Pool<Postgres>, user_id:Uuid) -> Result<Vec<PurchaseWithProgress>, CustomError>{
+
+    let result = sqlx::query_as!(
+        PurchaseWithProgress,
+        r#"
+            SELECT p.id, p.user_id, p.course_id, p.amount_paid,
+            COALESCE(ROUND(100.0 * COUNT(lp.completed_at) / NULLIF(COUNT(l.id), 0), 2), 0)::float8 AS "completion_percentage!"
+            FROM purchases_table p
+            LEFT JOIN sections_table s ON s.course_id = p.course_id
+            LEFT JOIN lessons_table l ON l.section_id = s.id
+            LEFT JOIN lesson_progress_table lp ON lp.lesson_id = l.id AND lp.user_id = p.user_id
+            WHERE p.user_id = $1 AND p.revoked_at IS NULL
+            GROUP BY p.id
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching user purchases".to_string()})?;
+
+    Ok(result)
+}
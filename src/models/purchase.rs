@@ -1,22 +1,25 @@
 use serde::{Deserialize, Serialize};
-use sqlx::{types::Uuid, Pool, Postgres};
+use sqlx::{types::Uuid, PgConnection, Pool, Postgres};
+use tracing::instrument;
 
-use crate::{errors::CustomError, schema::StructWithId};
+use crate::{errors::{CustomError, DbError}, schema::StructWithId};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Purchase{
     pub id: String,
     pub user_id: String,
     pub course_id: String,
+    pub amount_paid: Option<i32>,
 }
 
+#[instrument(level = "debug", skip_all, err)]
 pub async fn get_user_purchases(pool:&Pool<Postgres>, user_id:Uuid) -> Result<Vec<Purchase>, CustomError>{
 
     let user_purchases = sqlx::query_as!(
         Purchase,
         r#"
-            SELECT id, user_id, course_id FROM purchases_table 
-            WHERE user_id = $1
+            SELECT id, user_id, course_id, amount_paid FROM purchases_table 
+            WHERE user_id = $1 AND revoked_at IS NULL
         "#,
         user_id
     )
@@ -27,23 +30,48 @@ pub async fn get_user_purchases(pool:&Pool<Postgres>, user_id:Uuid) -> Result<Ve
     Ok(user_purchases)
 }
 
-pub async fn purchase_course(pool:&Pool<Postgres>, course_id:Uuid, user_id:Uuid) -> Result<StructWithId, CustomError>{
+/// Takes a connection so the purchase can be written in the same transaction as its order.
+/// Fails with `DbError::AlreadyPurchased` when the user already holds an active purchase of the course.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn purchase_course(conn:&mut PgConnection, course_id:Uuid, user_id:Uuid, order_id:Option<Uuid>, amount_paid:Option<i32>) -> Result<StructWithId, DbError>{
 
     let result = sqlx::query_as!(
         StructWithId,
         r#"
-            INSERT INTO purchases_table (user_id, course_id)
-            VALUES ($1, $2)
+            INSERT INTO purchases_table (user_id, course_id, order_id, amount_paid)
+            VALUES ($1, $2, $3, $4)
             RETURNING id
         "#,
         user_id,
-        course_id
+        course_id,
+        order_id,
+        amount_paid
     )
-    .fetch_one(pool)
+    .fetch_one(conn)
     .await;
 
     match result {
         Ok(val) => Ok(val),
-        Err(_) => Err(CustomError { error: "Error while purchasing the course".to_string()})
+        Err(e) => Err(DbError::from_sqlx(&e, "Error while purchasing the course"))
     }
-}
\ No newline at end of file
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn has_purchased(pool:&Pool<Postgres>, user_id:Uuid, course_id:Uuid) -> Result<bool, CustomError>{
+
+    let result = sqlx::query_scalar!(
+        r#"
+            SELECT EXISTS(
+                SELECT 1 FROM purchases_table
+                WHERE user_id = $1 AND course_id = $2 AND revoked_at IS NULL
+            ) AS "exists!"
+        "#,
+        user_id,
+        course_id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching the purchase".to_string()})?;
+
+    Ok(result)
+}
@@ -0,0 +1,26 @@
+use sqlx::{types::Uuid, Pool, Postgres};
+use tracing::instrument;
+
+use crate::errors::DbError;
+
+/// Stores the user's rating of the course, rating again replaces the previous one.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn rate_course(pool:&Pool<Postgres>, user_id:Uuid, course_id:Uuid, rating:i32) -> Result<(), DbError>{
+
+    sqlx::query!(
+        r#"
+            INSERT INTO course_ratings_table (user_id, course_id, rating)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id, course_id) DO UPDATE
+            SET rating = EXCLUDED.rating, updated_at = now()
+        "#,
+        user_id,
+        course_id,
+        rating
+    )
+    .execute(pool)
+    .await
+    .map_err(|e|DbError::from_sqlx(&e, "Error while rating the course"))?;
+
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Uuid, Pool, Postgres};
+use tracing::instrument;
+
+use crate::{errors::CustomError, models::order::refund_order, payments::PaymentProvider};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Refund{
+    pub id: String,
+    pub purchase_id: String,
+    pub order_id: Option<String>,
+    pub amount: i32,
+    pub reason: Option<String>,
+}
+
+pub enum RefundOutcome{
+    Refunded(Refund),
+    NotFound,
+    AlreadyRefunded,
+    WindowExpired,
+}
+
+/// Revokes the purchase, marks its order refunded, records the refund and asks the provider for
+/// the money back, all in one transaction so a failed provider call leaves the purchase untouched.
+/// The learner gets back what they were charged for this course, purchases made before orders
+/// existed have nothing to pay back and are refunded for 0.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn refund_purchase(pool:&Pool<Postgres>, payments:&dyn PaymentProvider, purchase_id:Uuid, refunded_by:Uuid, reason:Option<&str>, window_days:i32) -> Result<RefundOutcome, CustomError>{
+
+    let mut tx = pool.begin().await
+    .map_err(|_e|CustomError{error:"Error while refunding the purchase".to_string()})?;
+
+    let purchase = sqlx::query!(
+        r#"
+            SELECT p.order_id, p.amount_paid, o.provider_ref,
+            p.revoked_at IS NOT NULL AS "revoked!",
+            p.purchased_at < now() - make_interval(days => $2) AS "expired!"
+            FROM purchases_table p
+            LEFT JOIN orders_table o ON o.id = p.order_id
+            WHERE p.id = $1
+            FOR UPDATE OF p
+        "#,
+        purchase_id,
+        window_days
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|_e|CustomError{error:"Error while refunding the purchase".to_string()})?;
+
+    let Some(purchase) = purchase else {
+        return Ok(RefundOutcome::NotFound);
+    };
+
+    if purchase.revoked {
+        return Ok(RefundOutcome::AlreadyRefunded);
+    }
+
+    if purchase.expired {
+        return Ok(RefundOutcome::WindowExpired);
+    }
+
+    sqlx::query!(
+        r#"
+            UPDATE purchases_table SET revoked_at = now()
+            WHERE id = $1
+        "#,
+        purchase_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|_e|CustomError{error:"Error while refunding the purchase".to_string()})?;
+
+    // a bundle order is only refunded once none of its courses are still granted
+    if let Some(order_id) = purchase.order_id {
+
+        let fully_revoked = sqlx::query_scalar!(
+            r#"
+                SELECT NOT EXISTS(
+                    SELECT 1 FROM purchases_table
+                    WHERE order_id = $1 AND revoked_at IS NULL
+                ) AS "fully_revoked!"
+            "#,
+            order_id
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|_e|CustomError{error:"Error while refunding the purchase".to_string()})?;
+
+        if fully_revoked {
+            refund_order(&mut tx, order_id).await?
+            .ok_or_else(||CustomError{error:"Order was never paid".to_string()})?;
+        }
+    }
+
+    let amount = purchase.amount_paid.unwrap_or(0);
+
+    let refund = sqlx::query_as!(
+        Refund,
+        r#"
+            INSERT INTO refunds_table (purchase_id, order_id, amount, reason, refunded_by)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, purchase_id, order_id::text, amount, reason
+        "#,
+        purchase_id,
+        purchase.order_id,
+        amount,
+        reason,
+        refunded_by
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_e|CustomError{error:"Error while refunding the purchase".to_string()})?;
+
+    if let Some(provider_ref) = purchase.provider_ref {
+        payments.refund(&provider_ref, amount)?;
+    }
+
+    tx.commit().await
+    .map_err(|_e|CustomError{error:"Error while refunding the purchase".to_string()})?;
+
+    Ok(RefundOutcome::Refunded(refund))
+}
@@ -0,0 +1,130 @@
+use sqlx::{types::Uuid, Pool, Postgres};
+use tracing::instrument;
+
+use crate::errors::CustomError;
+
+// mirrors the roles seeded by the migrations, staff roles are only granted, never referenced in handlers
+#[allow(dead_code)]
+pub mod roles{
+    pub const LEARNER: &str = "learner";
+    pub const INSTRUCTOR: &str = "instructor";
+    pub const SUPPORT: &str = "support";
+    pub const PLATFORM_ADMIN: &str = "platform-admin";
+}
+
+pub mod permissions{
+    pub const COURSE_PURCHASE: &str = "course:purchase";
+    pub const PURCHASES_READ: &str = "purchases:read";
+    pub const COURSE_MANAGE: &str = "course:manage";
+    pub const SESSION_REVOKE: &str = "session:revoke";
+    pub const ROLES_MANAGE: &str = "roles:manage";
+    pub const PROGRESS_WRITE: &str = "progress:write";
+    pub const ORDER_REFUND: &str = "order:refund";
+    pub const COUPON_SITEWIDE: &str = "coupon:sitewide";
+    pub const COURSE_RATE: &str = "course:rate";
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_principal_roles(pool:&Pool<Postgres>, principal_id:Uuid, principal_kind:&str) -> Result<Vec<String>, CustomError>{
+
+    let result = sqlx::query_scalar!(
+        r#"
+            SELECT r.name FROM principal_roles_table pr
+            JOIN roles_table r ON r.id = pr.role_id
+            WHERE pr.principal_id = $1 AND pr.principal_kind = $2
+            ORDER BY r.name
+        "#,
+        principal_id,
+        principal_kind
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching roles".to_string()})?;
+
+    Ok(result)
+}
+
+/// Returns false when the role does not exist.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn assign_role(pool:&Pool<Postgres>, principal_id:Uuid, principal_kind:&str, role:&str) -> Result<bool, CustomError>{
+
+    let result = sqlx::query!(
+        r#"
+            INSERT INTO principal_roles_table (principal_id, principal_kind, role_id)
+            SELECT $1, $2, id FROM roles_table WHERE name = $3
+            ON CONFLICT DO NOTHING
+        "#,
+        principal_id,
+        principal_kind,
+        role
+    )
+    .execute(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while assigning role".to_string()})?;
+
+    if result.rows_affected() > 0 {
+        return Ok(true);
+    }
+
+    role_exists(pool, role).await
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn unassign_role(pool:&Pool<Postgres>, principal_id:Uuid, principal_kind:&str, role:&str) -> Result<(), CustomError>{
+
+    sqlx::query!(
+        r#"
+            DELETE FROM principal_roles_table
+            WHERE principal_id = $1 AND principal_kind = $2
+            AND role_id = (SELECT id FROM roles_table WHERE name = $3)
+        "#,
+        principal_id,
+        principal_kind,
+        role
+    )
+    .execute(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while removing role".to_string()})?;
+
+    Ok(())
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn role_exists(pool:&Pool<Postgres>, role:&str) -> Result<bool, CustomError>{
+
+    let result = sqlx::query_scalar!(
+        r#"
+            SELECT EXISTS(SELECT 1 FROM roles_table WHERE name = $1) AS "exists!"
+        "#,
+        role
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching role".to_string()})?;
+
+    Ok(result)
+}
+
+/// Permissions are resolved against the database on every request,
+/// so changing what a role may do applies without reissuing tokens.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn roles_have_permission(pool:&Pool<Postgres>, roles:&[String], permission:&str) -> Result<bool, CustomError>{
+
+    let result = sqlx::query_scalar!(
+        r#"
+            SELECT EXISTS(
+                SELECT 1 FROM role_permissions_table rp
+                JOIN roles_table r ON r.id = rp.role_id
+                JOIN permissions_table p ON p.id = rp.permission_id
+                WHERE r.name = ANY($1) AND p.name = $2
+            ) AS "exists!"
+        "#,
+        roles,
+        permission
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching permissions".to_string()})?;
+
+    Ok(result)
+}
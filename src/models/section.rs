@@ -0,0 +1,157 @@
+use std::collections::HashSet;
+
+use sqlx::{types::Uuid, Pool, Postgres};
+use tracing::instrument;
+
+use crate::errors::CustomError;
+
+#[derive(Debug)]
+pub struct Section{
+    pub id: String,
+    pub course_id: Uuid,
+    pub title: String,
+    pub position: i32,
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn create_section(pool:&Pool<Postgres>, course_id:Uuid, title:&str) -> Result<Section, CustomError>{
+
+    // new sections go to the end of the course
+    let result = sqlx::query_as!(
+        Section,
+        r#"
+            INSERT INTO sections_table (course_id, title, position)
+            VALUES ($1, $2, COALESCE((SELECT MAX(position) FROM sections_table WHERE course_id = $1), 0) + 1)
+            RETURNING id, course_id, title, position
+        "#,
+        course_id,
+        title
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while creating the section".to_string()})?;
+
+    Ok(result)
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_section(pool:&Pool<Postgres>, section_id:Uuid, course_id:Uuid) -> Result<Option<Section>, CustomError>{
+
+    let result = sqlx::query_as!(
+        Section,
+        r#"
+            SELECT id, course_id, title, position FROM sections_table
+            WHERE id = $1 AND course_id = $2
+        "#,
+        section_id,
+        course_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching the section".to_string()})?;
+
+    Ok(result)
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_course_sections(pool:&Pool<Postgres>, course_id:Uuid) -> Result<Vec<Section>, CustomError>{
+
+    let result = sqlx::query_as!(
+        Section,
+        r#"
+            SELECT id, course_id, title, position FROM sections_table
+            WHERE course_id = $1
+            ORDER BY position
+        "#,
+        course_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching the sections".to_string()})?;
+
+    Ok(result)
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn update_section(pool:&Pool<Postgres>, section_id:Uuid, title:&str) -> Result<Section, CustomError>{
+
+    let result = sqlx::query_as!(
+        Section,
+        r#"
+            UPDATE sections_table
+            SET title = $2
+            WHERE id = $1
+            RETURNING id, course_id, title, position
+        "#,
+        section_id,
+        title
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while updating the section".to_string()})?;
+
+    Ok(result)
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn delete_section(pool:&Pool<Postgres>, section_id:Uuid) -> Result<(), CustomError>{
+
+    sqlx::query!(
+        r#"
+            DELETE FROM sections_table
+            WHERE id = $1
+        "#,
+        section_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while deleting the section".to_string()})?;
+
+    Ok(())
+}
+
+/// Sets the section order of a course to the order of `ids`.
+/// Returns false when `ids` is not exactly the set of sections in the course.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn reorder_sections(pool:&Pool<Postgres>, course_id:Uuid, ids:&[Uuid]) -> Result<bool, CustomError>{
+
+    let err = |_e| CustomError{error:"Error while reordering the sections".to_string()};
+
+    let mut tx = pool.begin().await.map_err(err)?;
+
+    let existing = sqlx::query_scalar!(
+        r#"
+            SELECT id FROM sections_table
+            WHERE course_id = $1
+            FOR UPDATE
+        "#,
+        course_id
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(err)?;
+
+    let requested = ids.iter().collect::<HashSet<_>>();
+
+    if requested.len() != ids.len() || existing.len() != ids.len() || !existing.iter().all(|id| requested.contains(id)){
+        return Ok(false);
+    }
+
+    sqlx::query!(
+        r#"
+            UPDATE sections_table
+            SET position = ordered.position
+            FROM UNNEST($2::uuid[]) WITH ORDINALITY AS ordered(id, position)
+            WHERE sections_table.id = ordered.id AND sections_table.course_id = $1
+        "#,
+        course_id,
+        ids
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(err)?;
+
+    tx.commit().await.map_err(err)?;
+
+    Ok(true)
+}
@@ -0,0 +1,119 @@
+use chrono::{DateTime, Utc};
+use sqlx::{types::Uuid, Pool, Postgres};
+use tracing::instrument;
+
+use crate::{errors::CustomError, schema::StructWithId};
+
+pub const USER_SESSION: &str = "user";
+pub const ADMIN_SESSION: &str = "admin";
+
+#[derive(Debug)]
+pub struct Session{
+    pub id: String,
+    pub owner_id: Uuid,
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn create_session(pool:&Pool<Postgres>, owner_id:Uuid, owner_kind:&str, refresh_token_hash:&str, expires_at:DateTime<Utc>) -> Result<String, CustomError>{
+
+    let result = sqlx::query_as!(
+        StructWithId,
+        r#"
+            INSERT INTO sessions_table (owner_id, owner_kind, refresh_token_hash, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+        "#,
+        owner_id,
+        owner_kind,
+        refresh_token_hash,
+        expires_at
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while creating session".to_string()})?;
+
+    Ok(result.id)
+}
+
+/// Swaps the refresh token of a live session for a new one in a single statement,
+/// so a refresh token can only ever be redeemed once.
+#[instrument(level = "debug", skip_all, err)]
+pub async fn rotate_refresh_token(pool:&Pool<Postgres>, owner_kind:&str, old_hash:&str, new_hash:&str, expires_at:DateTime<Utc>) -> Result<Option<Session>, CustomError>{
+
+    let result = sqlx::query_as!(
+        Session,
+        r#"
+            UPDATE sessions_table
+            SET refresh_token_hash = $3, expires_at = $4
+            WHERE refresh_token_hash = $2 AND owner_kind = $1
+            AND revoked_at IS NULL AND expires_at > now()
+            RETURNING id, owner_id
+        "#,
+        owner_kind,
+        old_hash,
+        new_hash,
+        expires_at
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while refreshing session".to_string()})?;
+
+    Ok(result)
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn is_session_active(pool:&Pool<Postgres>, session_id:Uuid, owner_kind:&str) -> Result<bool, CustomError>{
+
+    let result = sqlx::query_as!(
+        StructWithId,
+        r#"
+            SELECT id FROM sessions_table
+            WHERE id = $1 AND owner_kind = $2
+            AND revoked_at IS NULL AND expires_at > now()
+        "#,
+        session_id,
+        owner_kind
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while fetching session".to_string()})?;
+
+    Ok(result.is_some())
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn revoke_session(pool:&Pool<Postgres>, session_id:Uuid) -> Result<(), CustomError>{
+
+    sqlx::query!(
+        r#"
+            UPDATE sessions_table
+            SET revoked_at = now()
+            WHERE id = $1 AND revoked_at IS NULL
+        "#,
+        session_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while revoking session".to_string()})?;
+
+    Ok(())
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn revoke_all_sessions(pool:&Pool<Postgres>, owner_id:Uuid, owner_kind:&str) -> Result<u64, CustomError>{
+
+    let result = sqlx::query!(
+        r#"
+            UPDATE sessions_table
+            SET revoked_at = now()
+            WHERE owner_id = $1 AND owner_kind = $2 AND revoked_at IS NULL
+        "#,
+        owner_id,
+        owner_kind
+    )
+    .execute(pool)
+    .await
+    .map_err(|_e|CustomError{error:"Error while revoking sessions".to_string()})?;
+
+    Ok(result.rows_affected())
+}
@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
-use sqlx::{Pool, Postgres};
+use sqlx::{types::Uuid, Pool, Postgres};
+use tracing::instrument;
 
-use crate::{errors::CustomError, schema::{user::CreateUser, StructWithId, StructWithVal}};
+use crate::{errors::{CustomError, DbError}, schema::{user::CreateUser, StructWithId, StructWithVal}};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct User{
@@ -11,6 +12,7 @@ pub struct User{
     pub password: String,
 }
 
+#[instrument(level = "debug", skip_all, err)]
 pub async fn check_user_exists(pool:&Pool<Postgres>, email:&str) -> Result<bool, CustomError>{
 
     let result = sqlx::query_as!(
@@ -31,7 +33,8 @@ pub async fn check_user_exists(pool:&Pool<Postgres>, email:&str) -> Result<bool,
 
 }   
 
-pub async fn create_user(pool:&Pool<Postgres>, user_meta: CreateUser) -> Result<String, CustomError>{
+#[instrument(level = "debug", skip_all, err)]
+pub async fn create_user(pool:&Pool<Postgres>, user_meta: CreateUser) -> Result<String, DbError>{
 
     let user = sqlx::query_as!(
         StructWithId,
@@ -48,27 +51,29 @@ pub async fn create_user(pool:&Pool<Postgres>, user_meta: CreateUser) -> Result<
 
     match user {
         Ok(val) => Ok(val.id),
-        Err(_e) => Err(CustomError{error:"Error while creating user".to_string()})
+        Err(e) => Err(DbError::from_sqlx(&e, "Error while creating user"))
     }
 }
 
-pub async fn retrieve_password(pool:&Pool<Postgres>, email:&str) -> Result<String, CustomError>{
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_user_by_email(pool:&Pool<Postgres>, email:&str) -> Result<User, CustomError>{
 
     let res = sqlx::query_as!(
-        StructWithVal,
+        User,
         r#"
-            SELECT password AS val FROM user_table
+            SELECT id, name, email, password FROM user_table
             WHERE email = $1
         "#,
         email
     )
     .fetch_one(pool)
     .await
-    .map_err(|_e|CustomError{error:"Error while retrieving user password".to_string()})?;
+    .map_err(|_e|CustomError{error:"Error while retrieving user".to_string()})?;
 
-    Ok(res.val)
+    Ok(res)
 }
 
+#[instrument(level = "debug", skip_all, err)]
 pub async fn get_user_id_by_email(pool:&Pool<Postgres>, email:&String) -> Result<String, CustomError>{
 
     let result = sqlx::query_as!(
@@ -83,4 +88,37 @@ pub async fn get_user_id_by_email(pool:&Pool<Postgres>, email:&String) -> Result
     .map_err(|_|CustomError{error:"Error while fetching user id".to_string()})?;
 
     Ok(result.id)
-}
\ No newline at end of file
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn get_user_email_by_id(pool:&Pool<Postgres>, id:Uuid) -> Result<String, CustomError>{
+
+    let result = sqlx::query_as!(
+        StructWithVal,
+        r#"
+            SELECT email as val FROM user_table
+            WHERE id = $1
+        "#,
+        id
+    ).fetch_one(pool)
+    .await
+    .map_err(|_|CustomError{error:"Error while fetching user email".to_string()})?;
+
+    Ok(result.val)
+}
+
+#[instrument(level = "debug", skip_all, err)]
+pub async fn is_user_verified(pool:&Pool<Postgres>, id:Uuid) -> Result<bool, CustomError>{
+
+    let result = sqlx::query!(
+        r#"
+            SELECT email_verified_at FROM user_table
+            WHERE id = $1
+        "#,
+        id
+    ).fetch_one(pool)
+    .await
+    .map_err(|_|CustomError{error:"Error while fetching user".to_string()})?;
+
+    Ok(result.email_verified_at.is_some())
+}
@@ -1,31 +1,75 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::types::Uuid;
+use validator::{Validate, ValidationError};
 
-#[derive(Debug, Deserialize, Serialize)]
+use crate::models::{coupon::{FIXED_COUPON, PERCENT_COUPON}, course::CourseStatus};
+
+use super::{double_option, not_blank};
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
 pub struct CreateAdmin{
+    #[validate(custom(function = "not_blank"), length(max = 255, message = "must be at most 255 characters"))]
     pub name: String,
+    #[validate(email(message = "must be a valid email"), length(max = 255, message = "must be at most 255 characters"))]
     pub email: String,
+    #[validate(length(min = 8, max = 128, message = "must be 8 to 128 characters"))]
     pub password: String,
 }
 pub struct CreateCourse {
     pub title: String,
     pub image_url: Option<String>,
     pub price: i32,
+    pub category: Option<String>,
+    pub description: Option<String>,
     pub admin_id: Uuid,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Validate)]
 pub struct CreateCourseWithoutAdminId {
+    #[validate(custom(function = "not_blank"), length(max = 255, message = "must be at most 255 characters"))]
     pub title: String,
+    #[validate(url(message = "must be a valid url"), length(max = 255, message = "must be at most 255 characters"))]
     pub image_url: Option<String>,
+    #[validate(range(min = 0, message = "can't be negative"))]
     pub price: i32,
+    #[serde(default)]
+    #[validate(length(max = 64, message = "must be at most 64 characters"))]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Validate)]
 pub struct UpdateCourse {
+    #[validate(custom(function = "not_blank"), length(max = 255, message = "must be at most 255 characters"))]
     pub title: String,
+    #[validate(url(message = "must be a valid url"), length(max = 255, message = "must be at most 255 characters"))]
     pub image_url: Option<String>,
+    #[validate(range(min = 0, message = "can't be negative"))]
     pub price: i32,
+    #[serde(default)]
+    #[validate(length(max = 64, message = "must be at most 64 characters"))]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Fields left out of a PATCH keep their current value, the nullable ones are cleared by sending `null`.
+#[derive(Deserialize, Serialize, Default, Validate)]
+pub struct PatchCourse {
+    #[validate(custom(function = "not_blank"), length(max = 255, message = "must be at most 255 characters"))]
+    pub title: Option<String>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    #[validate(url(message = "must be a valid url"), length(max = 255, message = "must be at most 255 characters"))]
+    pub image_url: Option<Option<String>>,
+    #[validate(range(min = 0, message = "can't be negative"))]
+    pub price: Option<i32>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    #[validate(length(max = 64, message = "must be at most 64 characters"))]
+    pub category: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub description: Option<Option<String>>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -35,4 +79,110 @@ pub struct CourseResponse{
     pub image_url: Option<String>,
     pub price: i32,
     pub admin_id: String,
-}
\ No newline at end of file
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub status: String,
+    pub version: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Serialize, Validate)]
+pub struct RefundRequest{
+    #[validate(length(max = 1000, message = "must be at most 1000 characters"))]
+    pub reason: Option<String>,
+}
+
+/// Codes are trimmed and upper-cased before they are stored, so the rule applies to the trimmed code.
+fn coupon_code(code:&str) -> Result<(), ValidationError> {
+    let code = code.trim();
+
+    let valid = (3..=64).contains(&code.len())
+    && code.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if !valid{
+        return Err(ValidationError::new("coupon_code").with_message("must be 3 to 64 letters, digits, - or _".into()));
+    }
+
+    Ok(())
+}
+
+fn coupon_kind(kind:&str) -> Result<(), ValidationError> {
+    if kind != PERCENT_COUPON && kind != FIXED_COUPON{
+        return Err(ValidationError::new("coupon_kind").with_message("must be percent or fixed".into()));
+    }
+
+    Ok(())
+}
+
+fn percent_off(input:&CouponInput) -> Result<(), ValidationError> {
+    if input.kind == PERCENT_COUPON && input.value > 100{
+        return Err(ValidationError::new("percent_off").with_message("a percent coupon takes 1 to 100 percent off".into()));
+    }
+
+    Ok(())
+}
+
+fn in_future(at:&DateTime<Utc>) -> Result<(), ValidationError> {
+    if *at <= Utc::now(){
+        return Err(ValidationError::new("in_future").with_message("must be in the future".into()));
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize, Serialize, Validate)]
+#[validate(schema(function = "percent_off"))]
+pub struct CouponInput{
+    #[validate(custom(function = "coupon_code"))]
+    pub code: String,
+    #[validate(custom(function = "coupon_kind"))]
+    pub kind: String,
+    #[validate(range(min = 1, message = "must take something off"))]
+    pub value: i32,
+    // leave empty for a coupon that works on every course
+    pub course_id: Option<String>,
+    #[validate(custom(function = "in_future"))]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    pub max_redemptions: Option<i32>,
+}
+
+/// Repeated ids are merged, so at least two of them have to differ.
+fn bundle_course_ids(ids:&[String]) -> Result<(), ValidationError> {
+    let mut distinct: Vec<String> = ids.iter().map(|id| id.to_ascii_lowercase()).collect();
+    distinct.sort();
+    distinct.dedup();
+
+    if distinct.len() < 2{
+        return Err(ValidationError::new("bundle_course_ids").with_message("a bundle needs at least two courses".into()));
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize, Serialize, Validate)]
+pub struct BundleInput{
+    #[validate(custom(function = "not_blank"), length(max = 255, message = "must be at most 255 characters"))]
+    pub title: String,
+    #[validate(range(min = 0, message = "can't be negative"))]
+    pub price: i32,
+    #[validate(custom(function = "bundle_course_ids"))]
+    pub course_ids: Vec<String>,
+}
+
+fn course_status(status:&str) -> Result<(), ValidationError> {
+    if CourseStatus::parse(status).is_none(){
+        return Err(ValidationError::new("course_status").with_message("must be one of draft, published, unlisted, archived".into()));
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize, Serialize, Validate)]
+pub struct CourseStatusInput{
+    #[validate(custom(function = "course_status"))]
+    pub status: String,
+}
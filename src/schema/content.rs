@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use super::not_blank;
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+pub struct SectionInput{
+    #[validate(custom(function = "not_blank"), length(max = 255, message = "must be at most 255 characters"))]
+    pub title: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+pub struct LessonInput{
+    #[validate(custom(function = "not_blank"), length(max = 255, message = "must be at most 255 characters"))]
+    pub title: String,
+    // markdown
+    pub body: Option<String>,
+    #[validate(url(message = "must be a valid url"), length(max = 255, message = "must be at most 255 characters"))]
+    pub video_url: Option<String>,
+    #[serde(default)]
+    #[validate(range(min = 0, message = "can't be negative"))]
+    pub duration_seconds: i32,
+    #[serde(default)]
+    pub is_preview: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+pub struct ReorderRequest{
+    #[validate(length(min = 1, message = "can't be empty"))]
+    pub ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LessonResponse{
+    pub id: String,
+    pub section_id: String,
+    pub title: String,
+    pub body: Option<String>,
+    pub video_url: Option<String>,
+    pub duration_seconds: i32,
+    pub is_preview: bool,
+    pub position: i32,
+    // true when body and video_url were withheld because the caller has no access
+    pub locked: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SectionResponse{
+    pub id: String,
+    pub course_id: String,
+    pub title: String,
+    pub position: i32,
+    pub lessons: Vec<LessonResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CourseContentResponse{
+    pub course_id: String,
+    pub has_access: bool,
+    pub sections: Vec<SectionResponse>,
+}
@@ -1,11 +1,22 @@
 use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError};
+
+use crate::models::session::{ADMIN_SESSION, USER_SESSION};
 
 pub mod user;
 pub mod admin;
+pub mod content;
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct JWTClaims{
     pub sub: String,
+    // id of the user or admin, so handlers never look it up by email
+    pub uid: String,
+    // id of the session row backing this token, checked by the auth guard
+    pub sid: String,
+    // "user" or "admin", the table the subject lives in
+    pub kind: String,
+    pub roles: Vec<String>,
     pub exp: usize,
 }
 pub struct StructWithId{
@@ -22,9 +33,31 @@ pub struct SignupResponse{
     pub id: String
 }
 
-#[derive(Deserialize, Serialize)]
+/// Keeps a field sent as `null` apart from a missing one: missing stays `None`
+/// (with `#[serde(default)]`) while `null` becomes `Some(None)`.
+pub fn double_option<'de, D, T>(deserializer:D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Rejects strings that are empty once trimmed, `length(min = 1)` lets "   " through.
+pub fn not_blank(value:&str) -> Result<(), ValidationError> {
+    if value.trim().is_empty(){
+        return Err(ValidationError::new("blank").with_message("can't be blank".into()));
+    }
+
+    Ok(())
+}
+
+// passwords aren't length checked here, accounts made before the rules still need to sign in
+#[derive(Deserialize, Serialize, Validate)]
 pub struct EmailAndPassword{
+    #[validate(email(message = "must be a valid email"))]
     pub email: String,
+    #[validate(length(min = 1, message = "can't be empty"))]
     pub password: String,
 }
 
@@ -32,15 +65,83 @@ pub struct EmailAndPassword{
 pub struct SigninResponse{
     pub message: String,
     pub token: String,
+    pub refresh_token: String,
+}
+
+#[derive(Deserialize, Serialize, Validate)]
+pub struct RefreshTokenRequest{
+    #[validate(length(min = 1, message = "can't be empty"))]
+    pub refresh_token: String,
 }
 
-#[derive(Serialize, Clone)]
-pub struct StructWithEmail{
+#[derive(Deserialize, Serialize, Validate)]
+pub struct EmailRequest{
+    #[validate(email(message = "must be a valid email"))]
     pub email: String,
 }
 
+#[derive(Deserialize, Serialize, Validate)]
+pub struct ResetPasswordRequest{
+    pub token: String,
+    #[validate(length(min = 8, max = 128, message = "must be 8 to 128 characters"))]
+    pub password: String,
+}
+
+fn principal_kind(kind:&str) -> Result<(), ValidationError> {
+    if kind != USER_SESSION && kind != ADMIN_SESSION{
+        return Err(ValidationError::new("principal_kind").with_message("must be user or admin".into()));
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize, Serialize, Clone, Validate)]
+pub struct RoleAssignment{
+    pub principal_id: String,
+    #[validate(custom(function = "principal_kind"))]
+    pub principal_kind: String,
+    #[validate(custom(function = "not_blank"), length(max = 64, message = "must be at most 64 characters"))]
+    pub role: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MessageResponse{
+    pub message: String,
+}
+
 #[derive(Serialize, Deserialize)]
-pub struct PurchaseResponse{
-    pub id: String,
+pub struct CheckoutResponse{
     pub message: String,
-}
\ No newline at end of file
+    pub order_id: String,
+    pub status: String,
+    pub amount: i32,
+    pub provider: String,
+    // none when a coupon made the course free and it was granted right away
+    pub checkout_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HealthResponse{
+    pub status: String,
+}
+
+/// "up" or "down", with what was found or what went wrong.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DependencyStatus{
+    pub status: String,
+    pub detail: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReadinessChecks{
+    pub database: DependencyStatus,
+    pub migrations: DependencyStatus,
+    pub shutdown: DependencyStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReadinessResponse{
+    // "ready" only when every check is up
+    pub status: String,
+    pub checks: ReadinessChecks,
+}
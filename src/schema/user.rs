@@ -1,8 +1,102 @@
 use serde::{Deserialize, Serialize};
+use validator::Validate;
 
-#[derive(Debug, Deserialize, Serialize)]
+use super::{admin::CourseResponse, not_blank};
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
 pub struct CreateUser{
+    #[validate(custom(function = "not_blank"), length(max = 255, message = "must be at most 255 characters"))]
     pub name: String,
+    #[validate(email(message = "must be a valid email"), length(max = 255, message = "must be at most 255 characters"))]
     pub email: String,
+    #[validate(length(min = 8, max = 128, message = "must be 8 to 128 characters"))]
     pub password: String,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct VerifyEmailQuery{
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+pub struct LessonPositionInput{
+    #[validate(range(min = 0, message = "can't be negative"))]
+    pub position_seconds: i32,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LessonProgressResponse{
+    pub lesson_id: String,
+    pub completed: bool,
+    pub last_position_seconds: i32,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PurchaseQuery{
+    pub coupon: Option<String>,
+}
+
+/// Query string of the public catalog, every field is optional.
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct CatalogQuery{
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    pub min_price: Option<i32>,
+    pub max_price: Option<i32>,
+    pub instructor: Option<String>,
+    pub category: Option<String>,
+    pub free: Option<bool>,
+    pub sort: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CatalogResponse{
+    pub courses: Vec<CourseResponse>,
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64,
+    pub total_pages: i64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SearchQuery{
+    pub q: String,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SearchResult{
+    pub course: CourseResponse,
+    pub rank: f32,
+    pub title_highlight: String,
+    pub snippet: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SearchResponse{
+    pub results: Vec<SearchResult>,
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64,
+    pub total_pages: i64,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CourseDetailResponse{
+    pub course: CourseResponse,
+    pub instructor_name: String,
+    pub lesson_count: i64,
+    pub total_duration_seconds: i64,
+    pub purchase_count: i64,
+    pub average_rating: Option<f64>,
+    pub rating_count: i64,
+    // true when the caller is a signed in user holding an active purchase
+    pub owned: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+pub struct RatingInput{
+    #[validate(range(min = 1, max = 5, message = "must be between 1 and 5"))]
+    pub rating: i32,
+}
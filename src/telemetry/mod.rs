@@ -0,0 +1,72 @@
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+use tracing_subscriber::{fmt::{self, format::FmtSpan}, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+use crate::config::{LogConfig, LogFormat};
+
+/// Service name the spans are exported under.
+pub const SERVICE_NAME: &str = "courser";
+
+/// Keeps the exporter alive, call `shutdown` before exiting so buffered spans are flushed.
+pub struct Telemetry{
+    provider: Option<SdkTracerProvider>,
+}
+
+/// Installs the global subscriber: logs go to stdout in the configured format, spans also go
+/// to the OTLP collector when one is configured. Must run outside the async runtime, the
+/// exporter uses a blocking http client.
+pub fn init(log:&LogConfig) -> Result<Telemetry, String> {
+
+    // closing a span logs how long it took, which gives request and query timings for free
+    let fmt_layer = match log.format {
+        LogFormat::Pretty => fmt::layer().pretty().with_span_events(FmtSpan::CLOSE).boxed(),
+        LogFormat::Json => fmt::layer().json().with_current_span(true).with_span_list(true).with_span_events(FmtSpan::CLOSE).boxed(),
+    };
+
+    let fmt_filter = EnvFilter::try_new(&log.level).map_err(|e| e.to_string())?;
+
+    let provider = match &log.otlp_endpoint {
+        Some(endpoint) => {
+
+            let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .build()
+            .map_err(|e| e.to_string())?;
+
+            Some(SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build())
+            .build())
+        },
+        None => None,
+    };
+
+    // query spans are debug level so they stay out of the logs, but traces always get them
+    let otel_layer = match &provider {
+        Some(provider) => {
+            let otel_filter = EnvFilter::try_new(format!("{},course_selling=debug", log.level)).map_err(|e| e.to_string())?;
+            Some(tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME)).with_filter(otel_filter))
+        },
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+    .with(fmt_layer.with_filter(fmt_filter))
+    .with(otel_layer)
+    .try_init()
+    .map_err(|e| e.to_string())?;
+
+    Ok(Telemetry{provider})
+}
+
+impl Telemetry{
+    pub fn shutdown(self){
+        if let Some(provider) = self.provider{
+            if let Err(e) = provider.shutdown(){
+                eprintln!("Cant flush the trace exporter: {}", e);
+            }
+        }
+    }
+}
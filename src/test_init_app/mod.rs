@@ -1,74 +1,96 @@
-use actix_web::{test::{self}, App, web, dev::{HttpServiceFactory, ServiceResponse}, Error};
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use actix_web::{body::{BoxBody, EitherBody}, test::{self}, App, web, dev::ServiceResponse, Error};
 use actix_service::Service;
 use actix_http::{Request};
-use crate::{handlers, middlewares, GlobalState};
+use crate::{config::AppConfig, configure_app, health::Health, mailer::{FileMailSender, Mail}, metrics::Metrics, payments::MockPaymentProvider, utils::DEFAULT_REFUND_WINDOW_DAYS, GlobalState};
 use dotenv::dotenv;
-use actix_web::{middleware::from_fn, web::scope};
-use sqlx::{postgres::{PgPoolOptions, Postgres}, Pool};
+use sqlx::{postgres::Postgres, Pool};
+
+/// Every mail sent while testing ends up in this outbox file.
+pub fn outbox_path() -> PathBuf {
+    std::env::temp_dir().join("courser_test_outbox.jsonl")
+}
 
+/// Returns the latest mail sent to the given address, tests use unique emails so this is safe in parallel.
+pub fn last_mail_to(to:&str) -> Option<Mail> {
+    let outbox = std::fs::read_to_string(outbox_path()).ok()?;
+
+    outbox.lines()
+    .rev()
+    .filter_map(|line| serde_json::from_str::<Mail>(line).ok())
+    .find(|mail| mail.to == to)
+}
+
+/// Tokens are always the last word of a mail body, either bare or as a `?token=` link.
+pub fn token_from_mail(mail:&Mail) -> String {
+    let last_word = mail.body.split_whitespace().last().unwrap_or_default();
+    last_word.rsplit('=').next().unwrap_or_default().to_string()
+}
+
+/// Webhooks in tests are signed with this, see `sign_webhook`.
+const TEST_WEBHOOK_SECRET: &str = "test-webhook-secret";
+
+/// Signs a payload the way the mock payment provider expects it in `X-Signature`.
+pub fn sign_webhook(payload:&[u8]) -> String {
+    MockPaymentProvider{secret:TEST_WEBHOOK_SECRET.to_string()}.sign(payload)
+}
+
+/// Scrapes of `/metrics` in tests send this as a bearer token.
+pub const TEST_METRICS_TOKEN: &str = "test-metrics-token";
 
 #[cfg(test)]
-pub async fn init(_service_factory: impl HttpServiceFactory + 'static) -> (impl Service<Request, Response = ServiceResponse, Error = Error>, Pool<Postgres>) {
+pub async fn init() -> (impl Service<Request, Response = ServiceResponse<EitherBody<BoxBody>>, Error = Error>, Pool<Postgres>) {
+    let (app, pool, _state) = init_with_state().await;
+    (app, pool)
+}
 
-    dotenv().ok();
+/// Like `init`, also hands back the shared state for tests that flip it, e.g. draining.
+pub async fn init_with_state() -> (impl Service<Request, Response = ServiceResponse<EitherBody<BoxBody>>, Error = Error>, Pool<Postgres>, web::Data<GlobalState>) {
 
-    let database_url = std::env::var("DATABASE_URL").expect("DATABASE URL must be set");
+    dotenv().ok();
 
-    std::env::var("USER_JWT_PASSWORD").expect("USER_JWT_PASSWORD must be set");
-    std::env::var("ADMIN_JWT_PASSWORD").expect("ADMIN_JWT_PASSWORD must be set");
+    // tests share the database, so each app keeps a small pool
+    let config = AppConfig::load_with(|config| {
+        config.database.max_connections = 2;
+        config.mail.outbox_path = Some(outbox_path());
+        config.payments.webhook_secret = TEST_WEBHOOK_SECRET.to_string();
+        config.payments.refund_window_days = DEFAULT_REFUND_WINDOW_DAYS;
+        config.metrics.token = Some(TEST_METRICS_TOKEN.to_string());
+    })
+    .expect("Invalid test configuration");
 
-    let pool = PgPoolOptions::new()
-    .max_connections(2)
-    .connect(&database_url)
+    let pool = config.database.connect()
     .await
     .expect("Cant connect to the database");
 
-    let global_state = GlobalState{pool:pool.clone()};
+    let global_state = GlobalState{
+        pool:pool.clone(),
+        mailer:Arc::new(FileMailSender{path:outbox_path()}),
+        payments:Arc::new(MockPaymentProvider{secret:TEST_WEBHOOK_SECRET.to_string()}),
+        auth:config.auth.clone(),
+        refund_window_days:config.payments.refund_window_days,
+        metrics:Metrics::new(&config.payments.currency),
+        metrics_token:config.metrics.token.clone(),
+        health:Health::new(Duration::from_millis(config.database.health_check_timeout_ms)),
+    };
 
     let app_data = web::Data::new(global_state);
 
     let app = test::init_service(
         App::new()
-            .service(
-                scope("/api/v1")
-                .app_data(app_data.clone())
-                .service(handlers::hello_world)
-                // place this before /user , else other will get matched
-                .service(
-                    scope("/user/purchases")
-                    .wrap(from_fn(middlewares::user::user_middleware))
-                    .service(handlers::user::user_purchases)
-                )
-                .service(
-                    scope("/user")
-                    .service(handlers::user::signup_user)
-                    .service(handlers::user::signin_user)
-                )
-                .service(
-                    // guard the purchase handler
-                    scope("/courses/purchase")
-                    .wrap(from_fn(middlewares::user::user_middleware))
-                    .service(handlers::course::purchase_course_handler)
-                )
-                .service(
-                    //this will match /course/{id} as well as /courses
-                    scope("/admin/course")
-                    .wrap(from_fn(middlewares::admin::admin_middleware))
-                    .service(handlers::admin::create_course_handler)
-                    .service(handlers::admin::update_course_handler)
-                    .service(handlers::admin::get_all_courses_handler)
-                )
-                .service(
-                    scope("/admin")
-                    .service(handlers::admin::signup_admin)
-                    .service(handlers::admin::signin_admin)
-                )
-                .service(
-                    scope("/courses")
-                    .service(handlers::course::get_all_courses_handler)
-                )
-            )
+        .wrap(config.cors.middleware())
+        .configure(|cfg| configure_app(cfg, app_data.clone()))
     ).await;
 
-    (app, pool.clone())
+    (app, pool, app_data)
+}
+
+/// Makes a course live without the publish checks, for tests that only need something to buy.
+pub async fn publish_course(pool:&Pool<Postgres>, course_id:&str) {
+    sqlx::query("UPDATE course_table SET status = 'published' WHERE id = $1::uuid")
+    .bind(course_id)
+    .execute(pool)
+    .await
+    .unwrap();
 }
@@ -1,8 +1,22 @@
 use argon2::{
     password_hash::{
-        rand_core::OsRng, Error, PasswordHasher, SaltString
+        rand_core::{OsRng, RngCore}, Error, PasswordHasher, SaltString
     }, Argon2, PasswordHash, PasswordVerifier
 };
+use chrono::{Duration, Utc};
+use jsonwebtoken::{encode, EncodingKey, Header};
+use sha2::{Digest, Sha256};
+use sqlx::types::Uuid;
+
+use crate::{config::AuthConfig, schema::JWTClaims};
+
+/// Defaults of `auth.access_token_ttl_minutes` and `auth.refresh_token_ttl_days`.
+pub const ACCESS_TOKEN_TTL_MINUTES: i64 = 15;
+pub const REFRESH_TOKEN_TTL_DAYS: i64 = 30;
+pub const PASSWORD_RESET_TOKEN_TTL_MINUTES: i64 = 30;
+pub const EMAIL_VERIFICATION_TOKEN_TTL_HOURS: i64 = 48;
+/// Used when `payments.refund_window_days` is not configured.
+pub const DEFAULT_REFUND_WINDOW_DAYS: i32 = 30;
 
 pub fn hash_password(password:&str)->Result<String, Error>{
 
@@ -16,8 +30,36 @@ pub fn hash_password(password:&str)->Result<String, Error>{
 pub fn verify_password(password:&str, hash:&str)->Result<(), Error>{
 
     let argon2 = Argon2::default(); 
-    let parsed_hash = PasswordHash::new(&hash)?;
+    let parsed_hash = PasswordHash::new(hash)?;
     argon2.verify_password(password.as_bytes(), &parsed_hash)?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Generates an opaque random token, used for refresh, password reset and verification tokens.
+pub fn generate_token() -> String{
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Opaque tokens are stored as a SHA-256 digest, so a leaked table can't be replayed.
+pub fn hash_token(token:&str) -> String{
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+pub fn create_access_token(auth:&AuthConfig, id:Uuid, email:&str, session_id:&str, kind:&str, roles:Vec<String>) -> Result<String, jsonwebtoken::errors::Error>{
+
+    let expiry = Utc::now() + Duration::minutes(auth.access_token_ttl_minutes);
+
+    let claims = JWTClaims{
+        sub: email.to_string(),
+        uid: id.to_string(),
+        sid: session_id.to_string(),
+        kind: kind.to_string(),
+        roles,
+        exp: expiry.timestamp() as usize
+    };
+
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(auth.jwt_secret.as_bytes()))
+}